@@ -0,0 +1,417 @@
+use crate::game_container::{GameContainer, LocalGameRoot};
+use crate::net_game_control_manager::{
+    ConnectionTarget, SendControlEventToClient, ServerControlEvent,
+};
+use crate::root::{GameId, GameRoot, GarbageAppliedEvent};
+use crate::states::PlayingState;
+use crate::system_sets::UpdateSystems;
+use crate::{assets, states};
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy::text::FontSmoothing;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// Badge points needed for each step of attack boost.
+const BADGE_BOOSTS: [(u32, f32); 4] = [(2, 0.25), (6, 0.5), (14, 0.75), (30, 1.0)];
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct PlayerStanding {
+    pub game_id: GameId,
+    pub team: Option<u8>,
+    pub kos: u32,
+    /// One point per KO, plus every point the knocked out player held.
+    pub badges: u32,
+    pub topped_out: bool,
+    /// Finishing position, set once the player's whole team has topped out, or once theirs is
+    /// the last team left.
+    pub placement: Option<usize>,
+}
+
+/// Server side record of who knocked out whom.
+#[derive(Resource, Default)]
+pub struct BattleRoyale {
+    /// Attacks sent to each game which haven't landed on its field yet, oldest first.
+    incoming: BTreeMap<GameId, VecDeque<(GameId, usize)>>,
+    last_attacker: BTreeMap<GameId, GameId>,
+    standings: BTreeMap<GameId, PlayerStanding>,
+}
+
+/// Client side copy of the server's standings.
+#[derive(Resource, Default)]
+pub struct StandingsView {
+    standings: BTreeMap<GameId, PlayerStanding>,
+}
+
+/// How the local player finished their last multiplayer game, kept for the main menu.
+#[derive(Resource, Clone, Debug)]
+pub struct LastMatchResult {
+    pub standing: PlayerStanding,
+    pub num_players: usize,
+}
+
+#[derive(Component)]
+#[require(Text2d)]
+struct StandingLabel;
+
+pub fn plugin(app: &mut App) {
+    app.init_resource::<BattleRoyale>()
+        .init_resource::<StandingsView>()
+        .add_systems(OnEnter(PlayingState::Playing), reset_standings_view)
+        .add_systems(
+            Update,
+            (
+                (add_new_games, track_landed_garbage).run_if(states::is_server),
+                accept_standings_updates.run_if(states::is_multiplayer_client),
+                (
+                    add_standing_label_to_root.in_set(UpdateSystems::PreRender),
+                    update_standing_labels.in_set(UpdateSystems::Render),
+                )
+                    .run_if(states::is_multiplayer_client)
+                    .run_if(states::headed),
+            )
+                .run_if(in_state(PlayingState::Playing)),
+        );
+}
+
+impl BattleRoyale {
+    /// Garbage lines to send for an attack, after the attacker's badge boost.
+    pub fn boosted_attack(&self, game_id: GameId, attack: usize) -> usize {
+        let badges = self.standings.get(&game_id).map_or(0, |s| s.badges);
+        let boost = BADGE_BOOSTS
+            .iter()
+            .rev()
+            .find(|(needed, _)| badges >= *needed)
+            .map_or(0.0, |(_, boost)| *boost);
+        (attack as f32 * (1.0 + boost)).floor() as usize
+    }
+
+    pub fn record_attack(&mut self, from: GameId, to: GameId, num_lines: usize) {
+        self.incoming
+            .entry(to)
+            .or_default()
+            .push_back((from, num_lines));
+    }
+
+    fn record_garbage_landed(&mut self, game_id: GameId, mut num_lines: usize) {
+        let queue = self.incoming.entry(game_id).or_default();
+        while num_lines > 0 {
+            let Some((from, remaining)) = queue.front_mut() else {
+                break;
+            };
+            self.last_attacker.insert(game_id, *from);
+            let landed = num_lines.min(*remaining);
+            *remaining -= landed;
+            num_lines -= landed;
+            if *remaining == 0 {
+                queue.pop_front();
+            }
+        }
+    }
+
+    /// Mark the game as topped out, crediting the KO to whoever's garbage last landed on it.
+    ///
+    /// Once every member of the game's team is out, the team is placed, and if only one team is
+    /// left it wins.
+    pub fn eliminate(&mut self, game_id: GameId, knocked_out: bool) {
+        let num_alive_teams = self.num_alive_teams();
+        let Some(victim) = self.standings.get_mut(&game_id) else {
+            return;
+        };
+        if victim.topped_out {
+            return;
+        }
+        victim.topped_out = true;
+        let victim_badges = victim.badges;
+        let victim_team = victim.team;
+
+        self.incoming.remove(&game_id);
+        let attacker = self.last_attacker.remove(&game_id);
+        if knocked_out {
+            if let Some(standing) = attacker
+                .and_then(|a| self.standings.get_mut(&a))
+                .filter(|s| !s.topped_out)
+            {
+                standing.kos += 1;
+                standing.badges += 1 + victim_badges;
+            }
+        }
+
+        if self.team_of(game_id, victim_team).all(|s| s.topped_out) {
+            self.place_team(game_id, victim_team, num_alive_teams);
+
+            if self.num_alive_teams() == 1 {
+                let winner = self.standings.values().find(|s| !s.topped_out).unwrap();
+                let (winner_id, winner_team) = (winner.game_id, winner.team);
+                self.place_team(winner_id, winner_team, 1);
+            }
+        }
+    }
+
+    /// The standings of the game's team, or just the game's if it's playing solo.
+    fn team_of(&self, game_id: GameId, team: Option<u8>) -> impl Iterator<Item = &PlayerStanding> {
+        self.standings.values().filter(move |s| match team {
+            Some(team) => s.team == Some(team),
+            None => s.game_id == game_id,
+        })
+    }
+
+    fn place_team(&mut self, game_id: GameId, team: Option<u8>, placement: usize) {
+        let members: Vec<GameId> = self.team_of(game_id, team).map(|s| s.game_id).collect();
+        for member in members {
+            self.standings.get_mut(&member).unwrap().placement = Some(placement);
+        }
+    }
+
+    fn num_alive_teams(&self) -> usize {
+        let alive = self.standings.values().filter(|s| !s.topped_out);
+        let solo = alive.clone().filter(|s| s.team.is_none()).count();
+        let teams: BTreeSet<u8> = alive.filter_map(|s| s.team).collect();
+        solo + teams.len()
+    }
+
+    fn add_player(&mut self, game_id: GameId, team: Option<u8>) {
+        self.standings.insert(
+            game_id,
+            PlayerStanding {
+                game_id,
+                team,
+                kos: 0,
+                badges: 0,
+                topped_out: false,
+                placement: None,
+            },
+        );
+    }
+
+    pub fn standings_event(&self) -> SendControlEventToClient {
+        SendControlEventToClient {
+            event: ServerControlEvent::StandingsUpdate(self.standings.values().cloned().collect()),
+            to_connection: ConnectionTarget::AllExcept(None),
+        }
+    }
+}
+
+fn add_new_games(
+    q_roots: Query<&GameRoot, Added<GameRoot>>,
+    q_container: Query<&GameContainer>,
+    mut battle_royale: ResMut<BattleRoyale>,
+    mut control_event_writer: EventWriter<SendControlEventToClient>,
+) {
+    if q_roots.is_empty() {
+        return;
+    }
+    // Start a fresh match once the last one is decided, with the winners still playing in it.
+    if battle_royale
+        .standings
+        .values()
+        .all(|s| s.placement.is_some())
+    {
+        let survivors: Vec<(GameId, Option<u8>)> = battle_royale
+            .standings
+            .values()
+            .filter(|s| !s.topped_out)
+            .map(|s| (s.game_id, s.team))
+            .collect();
+        *battle_royale = BattleRoyale::default();
+        for (game_id, team) in survivors {
+            battle_royale.add_player(game_id, team);
+        }
+    }
+    let container = q_container.single();
+    for gr in &q_roots {
+        battle_royale.add_player(gr.game_id, container.team_for_game(&gr.game_id));
+    }
+    control_event_writer.send(battle_royale.standings_event());
+}
+
+/// Must run before lock events are handled, so a top out caused by garbage is credited to the
+/// player who sent it.
+pub fn track_landed_garbage(
+    mut garbage_events: EventReader<GarbageAppliedEvent>,
+    mut battle_royale: ResMut<BattleRoyale>,
+) {
+    for event in garbage_events.read() {
+        battle_royale.record_garbage_landed(event.game_id, event.num_lines);
+    }
+}
+
+fn reset_standings_view(mut view: ResMut<StandingsView>) {
+    *view = StandingsView::default();
+}
+
+fn accept_standings_updates(
+    mut commands: Commands,
+    mut events: EventReader<ServerControlEvent>,
+    mut view: ResMut<StandingsView>,
+    local_game_root_res: Option<Res<LocalGameRoot>>,
+) {
+    let local_id = local_game_root_res.map(|lgr| lgr.game_id);
+    for event in events.read() {
+        let ServerControlEvent::StandingsUpdate(standings) = event else {
+            continue;
+        };
+        view.standings = standings.iter().map(|s| (s.game_id, s.clone())).collect();
+
+        if let Some(local) = local_id.and_then(|id| view.standings.get(&id)) {
+            if let Some(placement) = local.placement {
+                println!("Finished in place {placement}");
+                commands.insert_resource(LastMatchResult {
+                    standing: local.clone(),
+                    num_players: view.standings.len(),
+                });
+            }
+        }
+    }
+}
+
+fn add_standing_label_to_root(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    root_q: Query<(Entity, &GameRoot), Added<GameRoot>>,
+    local_game_root_res: Option<Res<LocalGameRoot>>,
+) {
+    let local_id = local_game_root_res.map(|lgr| lgr.game_id);
+    for (root_entity, game_root) in &root_q {
+        // Opponent boards are drawn scaled down, so make their labels big enough to read.
+        let font_size = if Some(game_root.game_id) == local_id {
+            15.
+        } else {
+            60.
+        };
+
+        commands
+            .spawn((
+                StandingLabel,
+                TextFont {
+                    font: asset_server.load("fonts/white-rabbit.ttf"),
+                    font_size,
+                    font_smoothing: FontSmoothing::None,
+                },
+                TextColor(Color::hsl(60., 1., 0.7)),
+                Transform::from_xyz(0., -assets::BLOCK_SIZE * 0.5, 0.),
+                Anchor::TopLeft,
+            ))
+            .set_parent(root_entity);
+    }
+}
+
+fn update_standing_labels(
+    q_root: Query<&GameRoot>,
+    mut q_label: Query<(&mut Text2d, &Parent), With<StandingLabel>>,
+    view: Res<StandingsView>,
+) {
+    let num_alive = view.standings.values().filter(|s| !s.topped_out).count();
+
+    for (mut label, parent) in q_label.iter_mut() {
+        let Ok(game_root) = q_root.get(parent.get()) else {
+            continue;
+        };
+        let Some(standing) = view.standings.get(&game_root.game_id) else {
+            continue;
+        };
+
+        let team = standing
+            .team
+            .map_or(String::new(), |team| format!("Team {team}  "));
+        label.0 = match standing.placement {
+            Some(placement) => format!("{team}#{placement}  KOs: {}", standing.kos),
+            None => format!(
+                "{team}KOs: {}  Badges: {}  Alive: {num_alive}",
+                standing.kos, standing.badges
+            ),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A match with a game for each team given, None playing solo.
+    fn new_match(teams: &[Option<u8>]) -> (BattleRoyale, Vec<GameId>) {
+        let mut battle_royale = BattleRoyale::default();
+        let games: Vec<GameId> = teams.iter().map(|_| GameId::new()).collect();
+        for (game_id, team) in games.iter().zip(teams) {
+            battle_royale.add_player(*game_id, *team);
+        }
+        (battle_royale, games)
+    }
+
+    #[test]
+    fn credits_the_ko_to_the_last_garbage_landed() {
+        let (mut br, games) = new_match(&[None, None, None]);
+        br.record_attack(games[0], games[2], 2);
+        br.record_attack(games[1], games[2], 3);
+        br.record_garbage_landed(games[2], 2);
+        br.record_garbage_landed(games[2], 1);
+        br.eliminate(games[2], true);
+
+        assert_eq!(br.standings[&games[0]].kos, 0);
+        assert_eq!(br.standings[&games[1]].kos, 1);
+        assert_eq!(br.standings[&games[1]].badges, 1);
+    }
+
+    #[test]
+    fn takes_the_victims_badges() {
+        let (mut br, games) = new_match(&[None, None, None]);
+        br.standings.get_mut(&games[2]).unwrap().badges = 3;
+        br.record_attack(games[0], games[2], 1);
+        br.record_garbage_landed(games[2], 1);
+        br.eliminate(games[2], true);
+
+        assert_eq!(br.standings[&games[0]].badges, 4);
+    }
+
+    #[test]
+    fn tops_out_without_a_ko() {
+        let (mut br, games) = new_match(&[None, None, None]);
+        br.record_attack(games[0], games[2], 1);
+        br.record_garbage_landed(games[2], 1);
+        br.eliminate(games[2], false);
+
+        assert_eq!(br.standings[&games[0]].kos, 0);
+        assert!(br.standings[&games[2]].topped_out);
+    }
+
+    #[test]
+    fn boosts_attack_by_badges() {
+        let (mut br, games) = new_match(&[None, None]);
+        assert_eq!(br.boosted_attack(games[0], 4), 4);
+        br.standings.get_mut(&games[0]).unwrap().badges = 2;
+        assert_eq!(br.boosted_attack(games[0], 4), 5);
+        br.standings.get_mut(&games[0]).unwrap().badges = 30;
+        assert_eq!(br.boosted_attack(games[0], 4), 8);
+    }
+
+    #[test]
+    fn places_players_as_they_top_out() {
+        let (mut br, games) = new_match(&[None, None, None]);
+        br.eliminate(games[2], false);
+        assert_eq!(br.standings[&games[2]].placement, Some(3));
+        assert_eq!(br.standings[&games[0]].placement, None);
+
+        br.eliminate(games[1], false);
+        assert_eq!(br.standings[&games[1]].placement, Some(2));
+        // The last one left wins without topping out.
+        assert_eq!(br.standings[&games[0]].placement, Some(1));
+        assert!(!br.standings[&games[0]].topped_out);
+    }
+
+    #[test]
+    fn places_whole_teams() {
+        let (mut br, games) = new_match(&[Some(1), Some(1), Some(2), Some(2)]);
+        assert_eq!(br.num_alive_teams(), 2);
+
+        br.eliminate(games[0], false);
+        br.eliminate(games[2], false);
+        assert_eq!(br.num_alive_teams(), 2);
+        assert!(games.iter().all(|g| br.standings[g].placement.is_none()));
+
+        br.eliminate(games[3], false);
+        assert_eq!(br.standings[&games[2]].placement, Some(2));
+        assert_eq!(br.standings[&games[3]].placement, Some(2));
+        assert_eq!(br.standings[&games[0]].placement, Some(1));
+        assert_eq!(br.standings[&games[1]].placement, Some(1));
+    }
+}
@@ -0,0 +1,33 @@
+use bevy::prelude::*;
+use clap::Args;
+#[cfg(feature = "bot")]
+use manytris_bot::backend::{BotBackend, BotSearchArgs};
+use serde::Serialize;
+
+/// How bots search for their moves. Builds without bots have no search to configure.
+#[derive(Args, Resource, Clone, Debug, Default, Serialize)]
+pub struct BotSearchConfig {
+    #[cfg(feature = "bot")]
+    #[command(flatten)]
+    pub search: BotSearchArgs,
+
+    /// Search each move deeper, up to the difficulty's depth, until this many milliseconds have
+    /// passed, or the difficulty's time between pieces if that's shorter, and play the deepest
+    /// finished search's move. Unset always searches the difficulty's depth.
+    #[arg(long)]
+    pub bot_time_budget_millis: Option<u64>,
+}
+
+/// Backends compiled into this build, with the exhaustive searches fastest first.
+#[cfg(feature = "bot")]
+pub fn available_backends() -> Vec<BotBackend> {
+    let mut backends = vec![];
+    if cfg!(feature = "bot_metal") {
+        backends.push(BotBackend::Metal);
+    }
+    if cfg!(feature = "bot_vulkan") {
+        backends.push(BotBackend::Vulkan);
+    }
+    backends.extend([BotBackend::Cpu, BotBackend::Beam]);
+    backends
+}
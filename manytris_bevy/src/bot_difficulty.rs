@@ -0,0 +1,64 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Named bot skill levels, from a gentle sparring partner up to full strength.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum BotDifficulty {
+    Beginner,
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Max,
+}
+
+/// Everything a difficulty changes about how the bot plays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DifficultyParams {
+    pub search_depth: usize,
+    /// Most pieces placed per second, or None to place them as fast as the bot is asked to.
+    pub max_pieces_per_second: Option<f32>,
+    /// Chance of playing one of the next best moves instead of the best.
+    pub mistake_chance: f32,
+    /// How far each scoring weight may be randomly scaled from its tuned value.
+    pub weight_noise: f32,
+    /// Pause before the next move after garbage arrives.
+    pub garbage_reaction_delay: Duration,
+}
+
+impl BotDifficulty {
+    pub fn params(&self) -> DifficultyParams {
+        use BotDifficulty::*;
+        let (search_depth, max_pieces_per_second, mistake_chance, weight_noise, reaction_millis) =
+            match self {
+                Beginner => (1, Some(0.6), 0.3, 0.5, 1500),
+                Easy => (1, Some(1.0), 0.15, 0.3, 1000),
+                Normal => (2, Some(1.5), 0.05, 0.1, 600),
+                Hard => (2, Some(3.0), 0.01, 0.0, 250),
+                Max => (3, None, 0.0, 0.0, 0),
+            };
+        DifficultyParams {
+            search_depth,
+            max_pieces_per_second,
+            mistake_chance,
+            weight_noise,
+            garbage_reaction_delay: Duration::from_millis(reaction_millis),
+        }
+    }
+
+    /// The next preset, wrapping around, for cycling through them in menus.
+    pub fn next(&self) -> Self {
+        let all = Self::value_variants();
+        let idx = all.iter().position(|d| d == self).unwrap();
+        all[(idx + 1) % all.len()]
+    }
+}
+
+impl DifficultyParams {
+    /// Shortest time allowed between pieces.
+    pub fn min_move_period(&self) -> Duration {
+        self.max_pieces_per_second
+            .map_or(Duration::ZERO, |pps| Duration::from_secs_f32(1.0 / pps))
+    }
+}
@@ -1,28 +1,38 @@
 #![cfg(feature = "bot")]
 
+use crate::bot_difficulty::{BotDifficulty, DifficultyParams};
+use crate::bot_worker::{ensure_bot_worker, follow_match_attack_table, BotWorker};
 use crate::game_container::LocalGameRoot;
 use crate::input::{InputEvent, InputType};
-use crate::root::{GameRoot, TickEvent, TickMutationMessage};
+use crate::local_versus::{LocalVersus, VsCpuSettings};
+use crate::match_rules::MatchRules;
+use crate::root::{GameId, GameRoot, TickEvent, TickMutationMessage};
 use crate::states;
-use crate::states::PlayingState;
+use crate::states::{is_unpaused, PlayingState};
 use crate::system_sets::UpdateSystems;
 use bevy::prelude::*;
 use manytris_bot::bot_start_positions::START_POSITIONS;
-use manytris_bot::{bot_player, BotContext};
+use manytris_bot::fair_play::FairPlayMove;
+use manytris_core::game_state::GameState;
 use manytris_core::game_state::TickMutation::JumpToBotStartPosition;
-use manytris_core::game_state::{GameState, TickMutation};
+use std::collections::BTreeMap;
 use std::time::Duration;
 
 #[derive(Clone, Resource)]
 pub struct BotInputPlugin {
     pub bot_period_millis: u64,
+    pub difficulty: BotDifficulty,
+    /// Play moves with real inputs from the spawn instead of teleporting pieces.
+    pub fair_play: bool,
+    /// Time between inputs in fair play.
+    pub input_period_millis: u64,
 }
 
 impl Plugin for BotInputPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             OnEnter(PlayingState::Playing),
-            init_bot_input.run_if(states::is_bot),
+            (init_bot_input, ensure_bot_worker).run_if(states::is_bot),
         )
         .add_systems(
             OnExit(PlayingState::Playing),
@@ -33,6 +43,7 @@ impl Plugin for BotInputPlugin {
             (
                 apply_bot_input.in_set(UpdateSystems::Input),
                 apply_bot_tick_events.in_set(UpdateSystems::LocalEventProducers),
+                follow_match_attack_table.run_if(resource_changed::<MatchRules>),
             )
                 .run_if(in_state(PlayingState::Playing))
                 .run_if(states::is_bot),
@@ -41,9 +52,69 @@ impl Plugin for BotInputPlugin {
     }
 }
 
+/// Runs the bot boards of a vs CPU game.
+pub fn local_bots_plugin(app: &mut App) {
+    app.init_resource::<LocalBotTimers>()
+        .add_systems(
+            OnEnter(PlayingState::Playing),
+            (reset_local_bot_timers, ensure_bot_worker).run_if(states::is_vs_cpu),
+        )
+        .add_systems(
+            Update,
+            apply_local_bot_moves
+                .in_set(UpdateSystems::LocalEventProducers)
+                .run_if(in_state(PlayingState::Playing))
+                .run_if(states::is_vs_cpu)
+                .run_if(is_unpaused),
+        );
+}
+
+/// When each local bot places its next piece.
+#[derive(Resource, Default)]
+struct LocalBotTimers {
+    pacers: BTreeMap<GameId, BotPacer>,
+}
+
 #[derive(Component)]
 struct BotInputState {
-    prev_piece_time: Option<Duration>,
+    pacer: BotPacer,
+    /// The move being played in fair play, one input at a time.
+    fair_move: Option<FairPlayMove>,
+    next_input_time: Duration,
+}
+
+/// Spaces out a bot's pieces, and makes it hesitate when garbage arrives.
+#[derive(Default)]
+struct BotPacer {
+    next_move_time: Option<Duration>,
+    pending_garbage: usize,
+}
+
+impl BotPacer {
+    /// True if the bot may place a piece now.
+    fn ready(
+        &mut self,
+        cur_time: Duration,
+        period: Duration,
+        game: &GameState,
+        params: &DifficultyParams,
+    ) -> bool {
+        let pending_garbage = game.pending_garbage();
+        if pending_garbage > self.pending_garbage {
+            let reaction_time = cur_time + params.garbage_reaction_delay;
+            let next = self.next_move_time.get_or_insert(reaction_time);
+            *next = (*next).max(reaction_time);
+        }
+        self.pending_garbage = pending_garbage;
+
+        let next_move_time = *self.next_move_time.get_or_insert(cur_time + period);
+        next_move_time <= cur_time
+    }
+
+    /// Start waiting for the next piece. Moves missed while paused aren't caught up on.
+    fn moved(&mut self, cur_time: Duration, period: Duration) {
+        self.next_move_time = Some(cur_time + period);
+    }
 }
 
 #[derive(Bundle)]
@@ -53,7 +124,9 @@ struct BotInputBundle {
 
 fn init_bot_input(mut cmds: Commands) {
     cmds.spawn(BotInputState {
-        prev_piece_time: None,
+        pacer: BotPacer::default(),
+        fair_move: None,
+        next_input_time: Duration::ZERO,
     });
 }
 
@@ -66,20 +139,39 @@ fn apply_bot_input(
     mut input_writer: EventWriter<InputEvent>,
     time: Res<Time<Fixed>>,
     input_config: Res<BotInputPlugin>,
+    q_root: Query<&GameRoot>,
+    local_game_root_res: Option<Res<LocalGameRoot>>,
+    worker: Res<BotWorker>,
 ) {
-    let mut is = input_state.single_mut();
-    let cur_time = time.elapsed();
-    let Some(prev_time) = is.prev_piece_time.as_mut() else {
-        is.prev_piece_time = Some(cur_time);
+    let Some(local_game_root) = local_game_root_res else {
+        return;
+    };
+    let Some(game_root) = q_root
+        .iter()
+        .find(|gr| gr.game_id == local_game_root.game_id)
+    else {
         return;
     };
-    let target_time = *prev_time + Duration::from_millis(input_config.bot_period_millis);
-    if target_time <= cur_time {
+    if worker.is_searching(game_root.game_id) {
+        return;
+    }
+
+    let params = input_config.difficulty.params();
+    let mut is = input_state.single_mut();
+    if is.fair_move.is_some() {
+        return;
+    }
+    if is.pacer.ready(
+        time.elapsed(),
+        network_bot_period(&input_config),
+        &game_root.active_game.game,
+        &params,
+    ) {
         input_writer.send(InputEvent {
             input_type: InputType::PerformBotMoveEvent,
             is_repeat: false,
+            game_id: None,
         });
-        *prev_time = target_time;
     }
 }
 
@@ -88,6 +180,11 @@ fn apply_bot_tick_events(
     mut tick_event_writer: EventWriter<TickEvent>,
     q_root: Query<&GameRoot>,
     local_game_root_res: Option<Res<LocalGameRoot>>,
+    input_config: Res<BotInputPlugin>,
+    match_rules: Res<MatchRules>,
+    time: Res<Time<Fixed>>,
+    mut input_state: Query<&mut BotInputState>,
+    mut worker: ResMut<BotWorker>,
 ) {
     let Some(local_game_root) = local_game_root_res else {
         return;
@@ -99,38 +196,123 @@ fn apply_bot_tick_events(
 
     let game = &game_root.active_game.game;
 
-    input_events
-        .read()
-        .map(|e| match e.input_type {
-            InputType::JumpToBotStartPositionEvent => {
-                vec![JumpToBotStartPosition(
+    let cur_time = time.elapsed();
+    let fair_play = input_config.fair_play || match_rules.forbid_teleports;
+    let mut is = input_state.single_mut();
+    let mut mutations = vec![];
+    for (_, bot_move) in worker.take_moves(|id| (id == game_id).then_some(game)) {
+        is.pacer.moved(cur_time, network_bot_period(&input_config));
+        if fair_play {
+            is.fair_move = Some(FairPlayMove::new(game, &bot_move));
+        } else {
+            mutations.extend(bot_move.as_tick_mutations());
+        }
+    }
+
+    // One input at a time, each from where the last one left the tetromino.
+    if is.next_input_time <= cur_time {
+        if let Some(fair_move) = &mut is.fair_move {
+            match fair_move.next_input(game) {
+                Some(input) => {
+                    mutations.push(input);
+                    is.next_input_time =
+                        cur_time + Duration::from_millis(input_config.input_period_millis);
+                }
+                None => {
+                    if !fair_move.dropped() {
+                        warn!(
+                            "Bot move for game {game_id:?} can't be played from the game's \
+                             state any more, giving up on it"
+                        );
+                    }
+                    is.fair_move = None;
+                }
+            }
+        }
+    }
+
+    for e in input_events.read() {
+        match e.input_type {
+            // The server disconnects clients which teleport when the match forbids it.
+            InputType::JumpToBotStartPositionEvent if !match_rules.forbid_teleports => {
+                mutations.push(JumpToBotStartPosition(
                     (*START_POSITIONS)
                         .bot_start_position(game.active_shape(), 0)
                         .clone(),
-                )]
+                ));
             }
-            InputType::PerformBotMoveEvent => make_bot_move_events(game),
-            _ => vec![],
-        })
-        .flatten()
-        .for_each(|mutation| {
-            tick_event_writer.send(TickEvent::new_local(TickMutationMessage {
-                mutation,
-                game_id,
-            }));
-        });
+            InputType::PerformBotMoveEvent => {
+                worker.request_move(game_id, game, &input_config.difficulty.params(), fair_play);
+            }
+            _ => {}
+        }
+    }
+
+    mutations.into_iter().for_each(|mutation| {
+        tick_event_writer.send(TickEvent::new_local(TickMutationMessage {
+            mutation,
+            game_id,
+        }));
+    });
 }
 
-fn make_bot_move_events(game: &GameState) -> Vec<TickMutation> {
-    let bot_context = make_context();
-    let mr = bot_player::select_next_move(game, &bot_context, &bot_player::BEST_BOT_KS, 3).unwrap();
-    mr.moves[0].as_tick_mutations()
+/// Time between the network bot's pieces, the slower of its configured period and difficulty.
+fn network_bot_period(input_config: &BotInputPlugin) -> Duration {
+    input_config
+        .difficulty
+        .params()
+        .min_move_period()
+        .max(Duration::from_millis(input_config.bot_period_millis))
 }
 
-fn make_context() -> impl BotContext {
-    #[cfg(feature = "bot_vulkan")]
-    {
-        use manytris_bot_vulkan::VulkanBotContext;
-        VulkanBotContext::init().unwrap()
+fn reset_local_bot_timers(mut timers: ResMut<LocalBotTimers>) {
+    *timers = LocalBotTimers::default();
+}
+
+fn apply_local_bot_moves(
+    time: Res<Time<Fixed>>,
+    q_root: Query<&GameRoot>,
+    local_versus: Res<LocalVersus>,
+    settings: Res<VsCpuSettings>,
+    mut timers: ResMut<LocalBotTimers>,
+    mut worker: ResMut<BotWorker>,
+    mut tick_event_writer: EventWriter<TickEvent>,
+) {
+    let cur_time = time.elapsed();
+    let params = settings.difficulty.params();
+    let period = params.min_move_period();
+    let bot_games = local_versus.bot_games();
+    let find_game = |game_id| {
+        q_root
+            .iter()
+            .find(|gr| gr.game_id == game_id && bot_games.contains(&game_id))
+            .map(|gr| &gr.active_game.game)
+    };
+
+    for (game_id, bot_move) in worker.take_moves(find_game) {
+        timers
+            .pacers
+            .entry(game_id)
+            .or_default()
+            .moved(cur_time, period);
+        tick_event_writer.send_batch(
+            bot_move
+                .as_tick_mutations()
+                .into_iter()
+                .map(|mutation| TickEvent::new_local(TickMutationMessage { mutation, game_id })),
+        );
+    }
+
+    for &game_id in &bot_games {
+        let Some(game) = find_game(game_id) else {
+            continue;
+        };
+        if worker.is_searching(game_id) {
+            continue;
+        }
+        let pacer = timers.pacers.entry(game_id).or_default();
+        if pacer.ready(cur_time, period, game, &params) {
+            worker.request_move(game_id, game, &params, false);
+        }
     }
 }
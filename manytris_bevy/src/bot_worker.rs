@@ -0,0 +1,381 @@
+#![cfg(feature = "bot")]
+
+use crate::bot_backend::{available_backends, BotSearchConfig};
+use crate::bot_difficulty::DifficultyParams;
+use crate::match_rules::MatchRules;
+use crate::root::GameId;
+use anyhow::Result;
+use bevy::prelude::*;
+use manytris_bot::backend::{BotBackend, CpuSearchContext};
+use manytris_bot::bot_player::{self, MoveResult, MovementDescriptor};
+use manytris_bot::fair_play;
+use manytris_bot::features::WeightProfile;
+use manytris_bot::BotContext;
+use manytris_core::attack::AttackTable;
+use manytris_core::bitmap_field::BitmapField;
+use manytris_core::consts;
+use manytris_core::game_state::GameState;
+use manytris_core::shapes::Shape;
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// Runs bot searches on a long-lived background thread, so they don't stall the frame.
+///
+/// The thread owns a single bot context, created once when the worker starts. Each game has at
+/// most one search in flight, and results for positions which changed while the search ran
+/// are dropped.
+#[derive(Resource)]
+pub struct BotWorker {
+    /// The match's attack table when the worker started, which its CPU searches score under.
+    attack_table: AttackTable,
+    requests: Sender<SearchRequest>,
+    results: Mutex<Receiver<SearchResponse>>,
+    in_flight: BTreeMap<GameId, InFlightSearch>,
+    next_request_id: u64,
+}
+
+struct SearchRequest {
+    request_id: u64,
+    game_id: GameId,
+    game: GameState,
+    params: DifficultyParams,
+    /// Only pick moves which can be played from the spawn without teleporting.
+    fair_play: bool,
+}
+
+struct SearchResponse {
+    request_id: u64,
+    game_id: GameId,
+    /// None if no move could be played.
+    result: Result<Option<MoveResult>>,
+}
+
+struct InFlightSearch {
+    request_id: u64,
+    position: SearchPosition,
+}
+
+/// The parts of a game a search result depends on. The active piece's location isn't included,
+/// since bot moves start by jumping it to a fixed start position.
+#[derive(PartialEq)]
+struct SearchPosition {
+    field: BitmapField,
+    active: Shape,
+    upcoming: [Shape; consts::NUM_PREVIEWS],
+    held: Option<Shape>,
+    pending_garbage: usize,
+}
+
+impl BotWorker {
+    pub fn spawn(search_config: BotSearchConfig, attack_table: AttackTable) -> Self {
+        let (request_sender, request_receiver) = mpsc::channel::<SearchRequest>();
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        let worker_attack_table = attack_table.clone();
+        thread::Builder::new()
+            .name("bot_worker".into())
+            .spawn(move || {
+                serve_with_backend(
+                    search_config,
+                    worker_attack_table,
+                    request_receiver,
+                    result_sender,
+                )
+            })
+            .unwrap();
+
+        Self {
+            attack_table,
+            requests: request_sender,
+            results: Mutex::new(result_receiver),
+            in_flight: BTreeMap::new(),
+            next_request_id: 0,
+        }
+    }
+
+    /// True if a search for the game hasn't returned yet.
+    pub fn is_searching(&self, game_id: GameId) -> bool {
+        self.in_flight.contains_key(&game_id)
+    }
+
+    /// Start searching for the game's next move, unless a search for it is already running. In
+    /// fair play, the move can be played without teleporting from where the tetromino is.
+    pub fn request_move(
+        &mut self,
+        game_id: GameId,
+        game: &GameState,
+        params: &DifficultyParams,
+        fair_play: bool,
+    ) {
+        if self.is_searching(game_id) {
+            return;
+        }
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+
+        let request = SearchRequest {
+            request_id,
+            game_id,
+            game: game.clone(),
+            params: *params,
+            fair_play,
+        };
+        if self.requests.send(request).is_err() {
+            warn!("Bot worker has stopped, not searching for game {game_id:?}");
+            return;
+        }
+        self.in_flight.insert(
+            game_id,
+            InFlightSearch {
+                request_id,
+                position: SearchPosition::of(game),
+            },
+        );
+    }
+
+    /// The next move from finished searches whose game is still in the position searched.
+    /// `current_game` looks up the latest state of each game.
+    pub fn take_moves<'a>(
+        &mut self,
+        current_game: impl Fn(GameId) -> Option<&'a GameState>,
+    ) -> Vec<(GameId, MovementDescriptor)> {
+        let responses: Vec<SearchResponse> = self.results.get_mut().unwrap().try_iter().collect();
+
+        let mut moves = vec![];
+        for response in responses {
+            let game_id = response.game_id;
+            // Responses to searches from before the last reset aren't in flight any more.
+            let Some(in_flight) = self
+                .in_flight
+                .remove(&game_id)
+                .filter(|f| f.request_id == response.request_id)
+            else {
+                continue;
+            };
+            let is_current = current_game(game_id)
+                .is_some_and(|game| SearchPosition::of(game) == in_flight.position);
+            if !is_current {
+                continue;
+            }
+            match response.result {
+                Ok(Some(mut mr)) => moves.push((game_id, mr.moves.swap_remove(0))),
+                Ok(None) => warn!("Bot found no move it can play for game {game_id:?}"),
+                Err(e) => warn!("Bot search failed for game {game_id:?}: {e}"),
+            }
+        }
+        moves
+    }
+
+    /// Forget all searches in flight, so their results are ignored.
+    pub fn reset(&mut self) {
+        self.in_flight.clear();
+    }
+}
+
+impl SearchPosition {
+    fn of(game: &GameState) -> Self {
+        Self {
+            field: game.make_bitmap_field(),
+            active: game.active_shape(),
+            upcoming: game.upcoming_shapes(),
+            held: game.held_tetromino().map(|t| t.shape),
+            pending_garbage: game.pending_garbage(),
+        }
+    }
+}
+
+/// Start the worker the first time a bot is needed, and keep it for later games with the same
+/// attack table. Replacing the worker stops the old one's thread.
+pub fn ensure_bot_worker(
+    mut commands: Commands,
+    worker: Option<ResMut<BotWorker>>,
+    search_config: Res<BotSearchConfig>,
+    match_rules: Res<MatchRules>,
+) {
+    match worker {
+        Some(mut worker) if worker.attack_table == match_rules.attack_table => worker.reset(),
+        _ => commands.insert_resource(BotWorker::spawn(
+            search_config.clone(),
+            match_rules.attack_table.clone(),
+        )),
+    }
+}
+
+/// Replace the worker when the match's attack table changes mid-game, such as when a network bot
+/// receives the server's rules after joining, so its searches score under the match's table.
+pub fn follow_match_attack_table(
+    mut commands: Commands,
+    worker: Option<Res<BotWorker>>,
+    search_config: Res<BotSearchConfig>,
+    match_rules: Res<MatchRules>,
+) {
+    if worker.is_some_and(|worker| worker.attack_table != match_rules.attack_table) {
+        commands.insert_resource(BotWorker::spawn(
+            search_config.clone(),
+            match_rules.attack_table.clone(),
+        ));
+    }
+}
+
+/// Start the first candidate backend which initializes, then serve requests with it until the
+/// worker is dropped.
+fn serve_with_backend(
+    search_config: BotSearchConfig,
+    attack_table: AttackTable,
+    requests: Receiver<SearchRequest>,
+    results: Sender<SearchResponse>,
+) {
+    let search = &search_config.search;
+    let weights = WeightProfile::load(&search.bot_weights).unwrap_or_else(|e| {
+        warn!(
+            "Failed to load bot weights {:?}, using versus weights: {e}",
+            search.bot_weights
+        );
+        WeightProfile::versus()
+    });
+
+    let time_budget = search_config
+        .bot_time_budget_millis
+        .map(Duration::from_millis);
+
+    let available = available_backends();
+    for candidate in search.bot_backend.candidates(&available) {
+        if !available.contains(&candidate) {
+            info!("{candidate:?} bot backend isn't in this build");
+            continue;
+        }
+        if let Some(ctx) = CpuSearchContext::new(candidate, &search.cpu, attack_table.clone()) {
+            return serve(
+                candidate,
+                ctx,
+                &weights,
+                &attack_table,
+                time_budget,
+                requests,
+                results,
+            );
+        }
+        match candidate {
+            BotBackend::Vulkan =>
+            {
+                #[cfg(feature = "bot_vulkan")]
+                match manytris_bot_vulkan::VulkanBotContext::init() {
+                    Ok(ctx) => {
+                        return serve(
+                            candidate,
+                            ctx,
+                            &weights,
+                            &attack_table,
+                            time_budget,
+                            requests,
+                            results,
+                        )
+                    }
+                    Err(e) => warn!("Failed to start Vulkan bot backend: {e}"),
+                }
+            }
+            BotBackend::Metal =>
+            {
+                #[cfg(feature = "bot_metal")]
+                match manytris_bot_metal::BotShaderContext::new() {
+                    Ok(ctx) => {
+                        return serve(
+                            candidate,
+                            ctx,
+                            &weights,
+                            &attack_table,
+                            time_budget,
+                            requests,
+                            results,
+                        )
+                    }
+                    Err(e) => warn!("Failed to start Metal bot backend: {e}"),
+                }
+            }
+            BotBackend::Auto | BotBackend::Cpu | BotBackend::Beam => {}
+        }
+    }
+}
+
+fn serve(
+    backend: BotBackend,
+    bot_context: impl BotContext,
+    weights: &WeightProfile,
+    attack_table: &AttackTable,
+    time_budget: Option<Duration>,
+    requests: Receiver<SearchRequest>,
+    results: Sender<SearchResponse>,
+) {
+    info!("Bot searches running on the {backend:?} backend");
+    let supported = bot_context.supported_features();
+    for feature in weights.weighted_features() {
+        if !supported.contains(&feature) {
+            warn!("The {backend:?} backend doesn't support {feature:?}, so it won't count");
+        }
+    }
+    // Runs until the worker is dropped and the request channel closes.
+    for request in requests {
+        let search_depth = request.params.search_depth;
+        let imperfection = bot_player::Imperfection {
+            mistake_chance: request.params.mistake_chance,
+            weight_noise: request.params.weight_noise,
+        };
+        let game = &request.game;
+        let playable = |md: &MovementDescriptor| {
+            !request.fair_play || fair_play::fair_play_inputs(game, md).is_some()
+        };
+        let result = match time_budget {
+            Some(time) => {
+                // Never search for longer than the difficulty leaves between pieces.
+                let min_period = request.params.min_move_period();
+                let budget = bot_player::TimeBudget {
+                    time: if min_period.is_zero() {
+                        time
+                    } else {
+                        time.min(min_period)
+                    },
+                    max_depth: search_depth,
+                };
+                bot_player::select_imperfect_move_within(
+                    &request.game,
+                    &bot_context,
+                    weights,
+                    &budget,
+                    &imperfection,
+                    &mut rand::thread_rng(),
+                    playable,
+                )
+            }
+            None => bot_player::select_imperfect_move(
+                &request.game,
+                &bot_context,
+                weights,
+                search_depth,
+                &imperfection,
+                &mut rand::thread_rng(),
+                playable,
+            ),
+        };
+        let result = result.map(|picked| {
+            picked.or_else(|| {
+                info!(
+                    "None of the bot's best moves for game {:?} are in reach of the spawn, \
+                     falling back to the best drop that is",
+                    request.game_id
+                );
+                fair_play::best_fair_drop(game, weights, attack_table)
+            })
+        });
+        let response = SearchResponse {
+            request_id: request.request_id,
+            game_id: request.game_id,
+            result,
+        };
+        if results.send(response).is_err() {
+            break;
+        }
+    }
+}
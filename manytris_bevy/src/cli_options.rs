@@ -1,6 +1,12 @@
+use crate::bot_backend::BotSearchConfig;
+use crate::bot_difficulty::BotDifficulty;
+use crate::handicap::{Handicap, HandicapLimits};
+use crate::local_versus::{MAX_CPU_OPPONENTS, MAX_LOCAL_PLAYERS};
 use crate::states::{ExecType, MultiplayerType, PlayingState, StatesPlugin};
+use anyhow::Result;
 use bevy::prelude::*;
 use clap::{ArgAction, Args, Parser, Subcommand};
+use manytris_core::attack::AttackTable;
 use serde::Serialize;
 
 // TODO: replace with "https://manytris-manager-265251374100.us-west1.run.app"
@@ -26,6 +32,28 @@ pub enum ExecCommand {
 pub struct ClientConfig {
     #[clap(flatten)]
     pub manager_server: ManagerServerConfig,
+
+    /// Team to join in multiplayer. Players on the same team don't attack each other.
+    #[arg(long)]
+    pub team: Option<u8>,
+
+    #[clap(flatten)]
+    pub handicap: Handicap,
+
+    /// Number of boards in a local versus game.
+    #[arg(long, default_value = "2", value_parser = parse_local_players)]
+    pub local_players: usize,
+
+    /// Number of bots in a vs CPU game.
+    #[arg(long, default_value = "1", value_parser = parse_cpu_opponents)]
+    pub cpu_opponents: usize,
+
+    /// How hard the bots play in a vs CPU game.
+    #[arg(long, value_enum, default_value_t)]
+    pub cpu_difficulty: BotDifficulty,
+
+    #[clap(flatten)]
+    pub bot_search: BotSearchConfig,
 }
 
 #[derive(Args, Clone, Debug, Serialize, Resource)]
@@ -48,6 +76,18 @@ pub struct ServerConfig {
     pub server: HostConfig,
     #[clap(long, action=ArgAction::SetTrue)]
     pub headless: bool,
+
+    /// Attack table for the match: "classic", "guideline", or the path to a JSON table.
+    #[arg(long, default_value = "classic", value_parser = parse_attack_table)]
+    pub attack_table: AttackTable,
+
+    /// Disconnect clients teleporting their pieces into place. Bots in the match switch to fair
+    /// play.
+    #[clap(long, action=ArgAction::SetTrue)]
+    pub forbid_teleports: bool,
+
+    #[clap(flatten)]
+    pub handicap_limits: HandicapLimits,
 }
 
 #[derive(Args, Clone, Debug, Serialize)]
@@ -58,6 +98,29 @@ pub struct BotConfig {
     #[arg(long, default_value = "1000")]
     pub bot_millis: u64,
 
+    /// How well the bot plays. Pieces are never placed faster than bot_millis apart.
+    #[arg(long, value_enum, default_value = "max")]
+    pub difficulty: BotDifficulty,
+
+    #[clap(flatten)]
+    pub bot_search: BotSearchConfig,
+
+    /// Team to join. Players on the same team don't attack each other.
+    #[arg(long)]
+    pub team: Option<u8>,
+
+    #[clap(flatten)]
+    pub handicap: Handicap,
+
+    /// Move pieces into place with rotate, shift and drop inputs from the spawn, like a person,
+    /// instead of teleporting them. Always on in matches which forbid teleports.
+    #[clap(long, action=ArgAction::SetTrue)]
+    pub fair_play: bool,
+
+    /// Time between the bot's inputs in fair play.
+    #[arg(long, default_value = "80")]
+    pub input_millis: u64,
+
     #[clap(long, action=ArgAction::SetTrue)]
     pub headless: bool,
 }
@@ -69,10 +132,42 @@ pub fn web_client_args() -> GameArgs {
                 manager_server: REMOTE_MANAGER_SERVER.into(),
                 // manager_server: LOCAL_MANAGER_SERVER.into(),
             },
+            team: None,
+            handicap: Handicap::default(),
+            local_players: 2,
+            cpu_opponents: 1,
+            cpu_difficulty: BotDifficulty::default(),
+            bot_search: BotSearchConfig::default(),
         }),
     }
 }
 
+fn parse_attack_table(arg: &str) -> Result<AttackTable> {
+    Ok(match arg {
+        "classic" => AttackTable::default(),
+        "guideline" => AttackTable::guideline(),
+        path => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+    })
+}
+
+fn parse_local_players(arg: &str) -> Result<usize> {
+    let players = arg.parse()?;
+    anyhow::ensure!(
+        (2..=MAX_LOCAL_PLAYERS).contains(&players),
+        "Local versus needs 2 to {MAX_LOCAL_PLAYERS} players"
+    );
+    Ok(players)
+}
+
+fn parse_cpu_opponents(arg: &str) -> Result<usize> {
+    let opponents = arg.parse()?;
+    anyhow::ensure!(
+        (1..=MAX_CPU_OPPONENTS).contains(&opponents),
+        "vs CPU needs 1 to {MAX_CPU_OPPONENTS} bots"
+    );
+    Ok(opponents)
+}
+
 impl ExecCommand {
     pub fn configure_states_plugin(&self) -> StatesPlugin {
         use ExecCommand::*;
@@ -1,5 +1,9 @@
 use crate::assets::BLOCK_SIZE;
-use crate::input::{InputEvent, InputType};
+use crate::battle_royale::BattleRoyale;
+use crate::handicap::Handicap;
+use crate::input::{InputEvent, InputType, KeyBindings};
+use crate::local_versus::LocalVersus;
+use crate::match_rules::MatchRules;
 use crate::net_game_control_manager::{
     ClientControlEvent, ConnectionDropped, ConnectionId, ConnectionTarget,
     ReceiveControlEventFromClient, SendControlEventToClient, ServerControlEvent,
@@ -7,11 +11,15 @@ use crate::net_game_control_manager::{
 use crate::root::{GameId, GameRoot, LockEvent};
 use crate::shape_producer::ShapeProducer;
 use crate::states::{ExecType, MultiplayerType, PlayingState};
-use crate::{root, shape_producer, states};
+use crate::targeting::Targeting;
+use crate::{battle_royale, root, shape_producer, states};
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use bevy::window::{WindowResized, WindowResolution};
 use manytris_core::game_state::{GameState, LockResult};
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::iter;
 use std::time::{Duration, Instant};
 
 const HEIGHT_IN_BLOCKS: f32 = 26.;
@@ -25,22 +33,43 @@ const OPPONENT_HORIZONTAL_TILES: usize = 2;
 const OPPONENT_VERTICAL_TILES: usize = 5;
 const OPPONENT_SCALE: f32 = 1.0 / OPPONENT_VERTICAL_TILES as f32;
 
+const TEAMMATE_VERTICAL_TILES: usize = 3;
+const TEAMMATE_SCALE: f32 = 1.0 / TEAMMATE_VERTICAL_TILES as f32;
+
 #[derive(Component)]
 #[require(Transform, Visibility)]
 pub struct GameContainer {
     tiled_games: Vec<(GameId, Entity)>,
+    /// Clients tile their teammates apart from the opponents in `tiled_games`.
+    teammate_games: Vec<(GameId, Entity)>,
+    player_settings: BTreeMap<GameId, PlayerSettings>,
     connection_map: BTreeMap<GameId, ConnectionId>,
     disconnected_games: BTreeMap<GameId, Instant>,
     container_type: ContainerType,
 }
 
+/// What a player asks for when joining a multiplayer game.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct PlayerSettings {
+    pub team: Option<u8>,
+    pub handicap: Handicap,
+}
+
 #[derive(Resource)]
 pub struct LocalGameRoot {
     pub game_id: GameId,
 }
 
+/// The games this process drives with its own clock and inputs.
+#[derive(SystemParam)]
+pub struct LocalGames<'w> {
+    local_game_root: Option<Res<'w, LocalGameRoot>>,
+    local_versus: Option<Res<'w, LocalVersus>>,
+}
+
 enum ContainerType {
     StandAlone,
+    LocalVersus(usize),
     MultiplayerClient,
     ServerTiles,
 }
@@ -52,6 +81,9 @@ pub fn plugin(app: &mut App) {
             setup_stand_alone
                 .after(shape_producer::setup)
                 .run_if(states::is_stand_alone),
+            setup_local_match
+                .after(shape_producer::setup)
+                .run_if(states::is_local_match),
             setup_multiplayer_client.run_if(states::is_multiplayer_client),
             setup_server.run_if(states::is_server),
         ),
@@ -64,7 +96,7 @@ pub fn plugin(app: &mut App) {
             accept_server_control_events.run_if(states::is_multiplayer_client),
             (
                 accept_client_control_events,
-                accept_server_lock_events,
+                accept_server_lock_events.after(battle_royale::track_landed_garbage),
                 handle_disconnections,
                 expire_disconnected_games,
             )
@@ -100,6 +132,7 @@ fn setup_stand_alone(
         ContainerType::StandAlone,
         q_window.get_single().ok().map(|w| w.resolution.clone()),
     );
+    commands.insert_resource(MatchRules::default());
     let start_time = time.elapsed();
     let (_, game_id, _) = root::create_new_root(
         &mut commands,
@@ -107,10 +140,57 @@ fn setup_stand_alone(
         active_game_transform(),
         start_time,
         shape_producer.single_mut().as_mut(),
+        &Handicap::default(),
     );
     set_local_game_root(&mut commands, game_id);
 }
 
+fn setup_local_match(
+    mut commands: Commands,
+    q_window: Query<&Window>,
+    time: Res<Time<Fixed>>,
+    mut shape_producer: Query<&mut ShapeProducer>,
+    exec_type: Res<ExecType>,
+) {
+    // Key bindings for each board, None for bots.
+    let bindings: Vec<Option<KeyBindings>> = match *exec_type {
+        ExecType::LocalVersus(num_players) => KeyBindings::LOCAL_VERSUS[..num_players]
+            .iter()
+            .copied()
+            .map(Some)
+            .collect(),
+        ExecType::VsCpu(num_bots) => iter::once(Some(KeyBindings::SINGLE_PLAYER))
+            .chain(iter::repeat_n(None, num_bots))
+            .collect(),
+        _ => return,
+    };
+    let num_boards = bindings.len();
+
+    let container_entity = spawn_container(
+        &mut commands,
+        ContainerType::LocalVersus(num_boards),
+        q_window.get_single().ok().map(|w| w.resolution.clone()),
+    );
+    commands.insert_resource(MatchRules::default());
+    let start_time = time.elapsed();
+    let boards = bindings
+        .into_iter()
+        .enumerate()
+        .map(|(idx, bindings)| {
+            let (_, game_id, _) = root::create_new_root(
+                &mut commands,
+                container_entity,
+                local_versus_game_transform(idx, num_boards),
+                start_time,
+                shape_producer.single_mut().as_mut(),
+                &Handicap::default(),
+            );
+            (game_id, bindings)
+        })
+        .collect();
+    commands.insert_resource(LocalVersus::new(boards));
+}
+
 fn tear_down_container(mut commands: Commands, container_q: Query<Entity, With<GameContainer>>) {
     commands.entity(container_q.single()).despawn_recursive();
     commands.remove_resource::<LocalGameRoot>();
@@ -136,6 +216,7 @@ fn accept_server_control_events(
     mut root_xforms_q: Query<&mut Transform>,
     mut game_root_q: Query<&mut GameRoot>,
     mut app_exit: EventWriter<AppExit>,
+    q_window: Query<&Window>,
 ) {
     let mut local_game_id = local_game_root_res.map(|lgr| lgr.game_id);
     let (container_entity, mut game_container) = q_container.single_mut();
@@ -147,6 +228,12 @@ fn accept_server_control_events(
                 local_game_id = Some(game_id.clone());
                 println!("Assigned gameid {game_id:?}");
             }
+            ServerControlEvent::SetMatchRules(rules) => {
+                commands.insert_resource(rules.clone());
+            }
+            ServerControlEvent::PlayerSettingsUpdate(settings) => {
+                game_container.player_settings = settings.clone();
+            }
             ServerControlEvent::SnapshotResponse(gs, game_id) => {
                 println!("Received snapshot for gameid {game_id:?}");
 
@@ -161,8 +248,14 @@ fn accept_server_control_events(
                 } else {
                     println!("Creating new game root for snapshot of gameid {game_id:?}");
                     // TODO: better define multiplayer tiling
-                    let transform = if Some(game_id) == local_game_id.as_ref() {
+                    let is_local = Some(game_id) == local_game_id.as_ref();
+                    let is_teammate = !is_local
+                        && local_game_id
+                            .is_some_and(|local| game_container.are_teammates(&local, game_id));
+                    let transform = if is_local {
                         active_game_transform()
+                    } else if is_teammate {
+                        client_teammate_game_transform(game_container.teammate_games.len())
                     } else {
                         client_opponent_game_transform(game_container.tiled_games.len())
                     };
@@ -176,9 +269,19 @@ fn accept_server_control_events(
                         gs.clone(),
                         time.elapsed(),
                         game_id.clone(),
+                        game_container.handicap_for_game(game_id).start_level,
                     );
 
-                    if Some(game_id) != local_game_id.as_ref() {
+                    if is_teammate {
+                        game_container.teammate_games.push((*game_id, entity));
+                        // Make room for the teammate column.
+                        if let (Ok(window), Ok(mut xform)) = (
+                            q_window.get_single(),
+                            root_xforms_q.get_mut(container_entity),
+                        ) {
+                            *xform = game_container.get_transform(window.width(), window.height());
+                        }
+                    } else if !is_local {
                         game_container.tiled_games.push((*game_id, entity));
                     }
                 }
@@ -191,6 +294,7 @@ fn accept_server_control_events(
                     input_writer.send(InputEvent {
                         input_type: InputType::EnqueueGarbageEvent(*num_lines),
                         is_repeat: false,
+                        game_id: None,
                     });
                 }
             }
@@ -209,6 +313,12 @@ fn accept_server_control_events(
             ServerControlEvent::RejectConnectionRequest => {
                 exit_game_safely(exec_type.as_ref(), &mut play_state, &mut app_exit);
             }
+            ServerControlEvent::TargetingUpdate(_) => {
+                // Handled by the targeting plugin.
+            }
+            ServerControlEvent::StandingsUpdate(_) => {
+                // Handled by the battle royale plugin.
+            }
         }
     }
 }
@@ -249,6 +359,7 @@ fn accept_client_control_events(
     time: Res<Time<Fixed>>,
     mut q_shape_producer: Query<&mut ShapeProducer>,
     q_roots: Query<&GameRoot>,
+    match_rules: Res<MatchRules>,
 ) {
     let (container_entity, mut container) = q_container.single_mut();
 
@@ -258,20 +369,45 @@ fn accept_client_control_events(
             from_connection,
         } = rce;
         match event {
-            ClientControlEvent::JoinRequest => {
+            ClientControlEvent::JoinRequest { settings } => {
+                let handicap = settings.handicap.clamped(&match_rules.handicap_limits);
+                if handicap != settings.handicap {
+                    eprintln!(
+                        "Clamped handicap {:?} from {from_connection:?} to {handicap:?}",
+                        settings.handicap
+                    );
+                }
                 let (game_state, game_id) = container.create_server_game(
                     &mut commands,
                     container_entity,
                     time.elapsed(),
                     q_shape_producer.single_mut().as_mut(),
                     *from_connection,
+                    PlayerSettings {
+                        handicap,
+                        ..settings.clone()
+                    },
                 );
 
+                // Everyone needs the teams and handicaps before the new snapshots.
                 control_event_writer.send(SendControlEventToClient {
-                    event: ServerControlEvent::AssignGameId(game_id),
-                    to_connection: ConnectionTarget::To(from_connection.clone()),
+                    event: ServerControlEvent::PlayerSettingsUpdate(
+                        container.player_settings.clone(),
+                    ),
+                    to_connection: ConnectionTarget::AllExcept(None),
                 });
 
+                control_event_writer.send_batch(
+                    [
+                        ServerControlEvent::AssignGameId(game_id),
+                        ServerControlEvent::SetMatchRules(match_rules.clone()),
+                    ]
+                    .map(|event| SendControlEventToClient {
+                        event,
+                        to_connection: ConnectionTarget::To(*from_connection),
+                    }),
+                );
+
                 // Send existing game snapshots to the current connection.
                 control_event_writer.send_batch(q_roots.iter().map(|gr| {
                     SendControlEventToClient {
@@ -294,16 +430,19 @@ fn accept_client_control_events(
                     .transfer_game(*game_id, *from_connection)
                     .is_some()
                 {
-                    // Send updated snapshots of every game.
-                    q_roots
-                        .iter()
-                        .map(|gr| {
-                            ServerControlEvent::SnapshotResponse(
-                                gr.active_game.game.clone(),
-                                gr.game_id,
-                            )
-                        })
-                        .collect()
+                    // Send the rules, player settings and updated snapshots of every game.
+                    [
+                        ServerControlEvent::SetMatchRules(match_rules.clone()),
+                        ServerControlEvent::PlayerSettingsUpdate(container.player_settings.clone()),
+                    ]
+                    .into_iter()
+                    .chain(q_roots.iter().map(|gr| {
+                        ServerControlEvent::SnapshotResponse(
+                            gr.active_game.game.clone(),
+                            gr.game_id,
+                        )
+                    }))
+                    .collect()
                 } else {
                     // We don't know this client, tell them to go away
                     vec![ServerControlEvent::RejectConnectionRequest]
@@ -317,6 +456,9 @@ fn accept_client_control_events(
                     }
                 }));
             }
+            ClientControlEvent::SetTargetingStrategy(_) => {
+                // Handled by the targeting plugin.
+            }
         }
     }
 }
@@ -327,35 +469,47 @@ fn accept_server_lock_events(
     mut control_event_writer: EventWriter<SendControlEventToClient>,
     mut q_game_container: Query<&mut GameContainer>,
     mut root_xform_q: Query<&mut Transform>,
+    mut targeting: ResMut<Targeting>,
+    mut battle_royale: ResMut<BattleRoyale>,
 ) {
     let mut game_container = q_game_container.single_mut();
     for LockEvent {
         game_id,
         lock_result,
+        attack,
     } in lock_events.read()
     {
         match lock_result {
-            LockResult::Ok { lines_cleared } => {
-                if *lines_cleared <= 1 {
+            LockResult::Ok { .. } => {
+                let attack = game_container
+                    .handicap_for_game(game_id)
+                    .scale_outgoing(battle_royale.boosted_attack(*game_id, *attack));
+                if attack == 0 {
                     continue;
                 }
-                let num_lines: usize = match *lines_cleared {
-                    n if n <= 1 => continue,
-                    2 => 1,
-                    3 => 2,
-                    n => n as usize,
-                };
-                if let Some(conn_id) = game_container.connection_for_game(game_id) {
-                    control_event_writer.send(SendControlEventToClient {
-                        event: ServerControlEvent::DeliverGarbage {
-                            from_game_id: *game_id,
-                            num_lines,
-                        },
-                        to_connection: ConnectionTarget::AllExcept(Some(conn_id)),
-                    });
+                for (target_id, num_lines) in targeting.route_attack(*game_id, attack) {
+                    let num_lines = game_container
+                        .handicap_for_game(&target_id)
+                        .scale_incoming(num_lines);
+                    if num_lines == 0 {
+                        continue;
+                    }
+                    if let Some(conn_id) = game_container.connection_for_game(&target_id) {
+                        battle_royale.record_attack(*game_id, target_id, num_lines);
+                        control_event_writer.send(SendControlEventToClient {
+                            event: ServerControlEvent::DeliverGarbage {
+                                from_game_id: *game_id,
+                                num_lines,
+                            },
+                            to_connection: ConnectionTarget::To(conn_id),
+                        });
+                    }
                 }
             }
             LockResult::GameOver => {
+                // Placements go out before the game over, so the player sees their result.
+                battle_royale.eliminate(*game_id, true);
+                control_event_writer.send(battle_royale.standings_event());
                 control_event_writer.send(SendControlEventToClient {
                     event: ServerControlEvent::ClientGameOver(*game_id),
                     to_connection: ConnectionTarget::AllExcept(None),
@@ -390,6 +544,13 @@ fn active_game_transform() -> Transform {
     )
 }
 
+/// Boards side by side, centered in the window.
+fn local_versus_game_transform(board_index: usize, num_boards: usize) -> Transform {
+    let offset = board_index as f32 - (num_boards - 1) as f32 / 2.;
+    let active = active_game_transform();
+    active.with_translation(active.translation + Vec3::X * WIDTH_IN_BLOCKS * BLOCK_SIZE * offset)
+}
+
 fn tiled_game_transform(game_index: usize) -> Transform {
     let game_index = game_index as isize;
     let tile_x = (game_index % HORIZONTAL_TILES) as f32;
@@ -417,6 +578,16 @@ fn client_opponent_game_transform(opponent_index: usize) -> Transform {
     )
 }
 
+fn client_teammate_game_transform(teammate_index: usize) -> Transform {
+    let scale = TEAMMATE_SCALE;
+    let active = active_game_transform();
+    // Stack bottom-to-top in a column left of the active game
+    active.with_scale(Vec3::splat(scale)).with_translation(
+        active.translation - Vec3::X * WIDTH_IN_BLOCKS * BLOCK_SIZE * scale
+            + Vec3::Y * HEIGHT_IN_BLOCKS * BLOCK_SIZE * scale * (teammate_index as f32),
+    )
+}
+
 fn spawn_container(
     commands: &mut Commands,
     container_type: ContainerType,
@@ -424,6 +595,8 @@ fn spawn_container(
 ) -> Entity {
     let game_container = GameContainer {
         tiled_games: default(),
+        teammate_games: default(),
+        player_settings: default(),
         connection_map: default(),
         disconnected_games: default(),
         container_type,
@@ -441,6 +614,23 @@ fn set_local_game_root(commands: &mut Commands, game_id: GameId) {
     commands.insert_resource(LocalGameRoot { game_id });
 }
 
+impl LocalGames<'_> {
+    pub fn ids(&self) -> Vec<GameId> {
+        match (&self.local_versus, &self.local_game_root) {
+            (Some(local_versus), _) => local_versus.live_games(),
+            (None, Some(local_game_root)) => vec![local_game_root.game_id],
+            (None, None) => vec![],
+        }
+    }
+
+    /// The game an input event should be applied to.
+    pub fn input_target(&self, event: &InputEvent) -> Option<GameId> {
+        event
+            .game_id
+            .or(self.local_game_root.as_ref().map(|lgr| lgr.game_id))
+    }
+}
+
 impl GameContainer {
     pub fn get_num_active_games(&self) -> usize {
         self.tiled_games.len()
@@ -452,9 +642,15 @@ impl GameContainer {
 
         let scale = match self.container_type {
             ContainerType::StandAlone => x_scale.min(y_scale),
+            ContainerType::LocalVersus(num_boards) => (x_scale / num_boards as f32).min(y_scale),
             ContainerType::MultiplayerClient => {
-                let total_width =
-                    WIDTH_IN_BLOCKS * (1.0 + OPPONENT_HORIZONTAL_TILES as f32 * OPPONENT_SCALE);
+                let teammates_width = if self.teammate_games.is_empty() {
+                    0.0
+                } else {
+                    TEAMMATE_SCALE
+                };
+                let total_width = WIDTH_IN_BLOCKS
+                    * (1.0 + OPPONENT_HORIZONTAL_TILES as f32 * OPPONENT_SCALE + teammates_width);
                 let x_scale = width_pixels / (total_width * BLOCK_SIZE);
                 x_scale.min(y_scale)
             }
@@ -473,6 +669,7 @@ impl GameContainer {
         cur_time: Duration,
         shape_producer: &mut ShapeProducer,
         connection_id: ConnectionId,
+        settings: PlayerSettings,
     ) -> (GameState, GameId) {
         let new_idx = self.tiled_games.len();
         let (game_state, game_id, root_entity) = root::create_new_root(
@@ -481,12 +678,33 @@ impl GameContainer {
             tiled_game_transform(new_idx),
             cur_time,
             shape_producer,
+            &settings.handicap,
         );
         self.tiled_games.push((game_id, root_entity));
         self.connection_map.insert(game_id, connection_id);
+        self.player_settings.insert(game_id, settings);
         (game_state, game_id)
     }
 
+    pub fn team_for_game(&self, game_id: &GameId) -> Option<u8> {
+        self.player_settings.get(game_id).and_then(|s| s.team)
+    }
+
+    pub fn handicap_for_game(&self, game_id: &GameId) -> Handicap {
+        self.player_settings
+            .get(game_id)
+            .map(|s| s.handicap)
+            .unwrap_or_default()
+    }
+
+    /// True if the games are on the same team. Games without a team are only their own teammate.
+    pub fn are_teammates(&self, a: &GameId, b: &GameId) -> bool {
+        a == b
+            || self
+                .team_for_game(a)
+                .is_some_and(|team| self.team_for_game(b) == Some(team))
+    }
+
     pub fn connection_for_game(&self, game_id: &GameId) -> Option<ConnectionId> {
         self.connection_map.get(game_id).copied()
     }
@@ -498,6 +716,22 @@ impl GameContainer {
         root_xform_q: &mut Query<&mut Transform>,
         xform_function: fn(usize) -> Transform,
     ) {
+        self.player_settings.remove(&game_id);
+
+        if let Some(idx) = self
+            .teammate_games
+            .iter()
+            .position(|(gid, _)| gid == &game_id)
+        {
+            let (_, entity) = self.teammate_games.remove(idx);
+            commands.entity(entity).despawn_recursive();
+            for (i, (_, entity)) in self.teammate_games.iter().enumerate().skip(idx) {
+                let mut xform = root_xform_q.get_mut(*entity).unwrap();
+                *xform = client_teammate_game_transform(i);
+            }
+            return;
+        }
+
         // find the entity if it exists
         let Some(idx) = self.tiled_games.iter().position(|(gid, _)| gid == &game_id) else {
             return;
@@ -526,7 +760,7 @@ impl GameContainer {
         Some(*entity)
     }
 
-    fn games_for_connection(&self, connection_id: &ConnectionId) -> Vec<GameId> {
+    pub fn games_for_connection(&self, connection_id: &ConnectionId) -> Vec<GameId> {
         self.connection_map
             .iter()
             .filter(|(_, cid)| *cid == connection_id)
@@ -559,6 +793,7 @@ fn expire_disconnected_games(
     mut q_container: Query<&mut GameContainer>,
     mut control_event_writer: EventWriter<SendControlEventToClient>,
     mut root_xform_q: Query<&mut Transform>,
+    mut battle_royale: ResMut<BattleRoyale>,
 ) {
     let mut container = q_container.single_mut();
     let now = Instant::now();
@@ -575,6 +810,8 @@ fn expire_disconnected_games(
         container.disconnected_games.remove(&game_id);
         container.connection_map.remove(&game_id);
 
+        battle_royale.eliminate(game_id, false);
+        control_event_writer.send(battle_royale.standings_event());
         control_event_writer.send(SendControlEventToClient {
             event: ServerControlEvent::ClientGameOver(game_id),
             to_connection: ConnectionTarget::AllExcept(None),
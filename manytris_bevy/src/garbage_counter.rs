@@ -61,7 +61,9 @@ fn render_garbage_counter(
         let count_value = gr.active_game.game.get_garbage_element_countdown(ge.index);
 
         material.0 = if let Some(count) = count_value {
-            ra.garbage_counter_materials[count - 1].clone()
+            // Handicapped games can wait longer than the usual delay, shown with the last color.
+            let idx = count.min(consts::GARBAGE_TURN_COUNT) - 1;
+            ra.garbage_counter_materials[idx].clone()
         } else {
             ra.empty_material.clone()
         };
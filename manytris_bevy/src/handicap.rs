@@ -0,0 +1,204 @@
+use crate::game_container::{GameContainer, LocalGameRoot};
+use crate::root::GameRoot;
+use crate::states::PlayingState;
+use crate::system_sets::UpdateSystems;
+use crate::{assets, states};
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy::text::FontSmoothing;
+use clap::Args;
+use manytris_core::consts;
+use serde::{Deserialize, Serialize};
+
+/// Per-player balancing, stored by the server and applied to everything the player sends and
+/// receives.
+#[derive(Args, Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Handicap {
+    /// Multiplier on the garbage this player sends.
+    #[arg(long, default_value = "1.0")]
+    pub attack_multiplier: f32,
+    /// Multiplier on the garbage this player receives.
+    #[arg(long, default_value = "1.0")]
+    pub incoming_multiplier: f32,
+    /// Turns before received garbage rises, instead of the usual delay.
+    #[arg(long)]
+    pub garbage_delay: Option<usize>,
+    /// Gravity level to start at.
+    #[arg(long, default_value = "1")]
+    pub start_level: i32,
+}
+
+/// The handicaps a server accepts. Handicaps players ask for outside them are clamped.
+#[derive(Args, Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct HandicapLimits {
+    /// Smallest multiplier players may put on the garbage they send or receive.
+    #[arg(long, default_value = "0.5")]
+    pub min_multiplier: f32,
+    /// Largest multiplier players may put on the garbage they send or receive.
+    #[arg(long, default_value = "2.0")]
+    pub max_multiplier: f32,
+    /// Most turns players may hold received garbage back before it rises.
+    #[arg(long, default_value = "10")]
+    pub max_garbage_delay: usize,
+    /// Highest gravity level players may start at.
+    #[arg(long, default_value = "20")]
+    pub max_start_level: i32,
+}
+
+#[derive(Component)]
+#[require(Text2d)]
+struct HandicapLabel;
+
+pub fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            add_handicap_label_to_root.in_set(UpdateSystems::PreRender),
+            update_handicap_labels.in_set(UpdateSystems::Render),
+        )
+            .run_if(in_state(PlayingState::Playing))
+            .run_if(states::is_multiplayer_client)
+            .run_if(states::headed),
+    );
+}
+
+impl Default for Handicap {
+    fn default() -> Self {
+        Self {
+            attack_multiplier: 1.0,
+            incoming_multiplier: 1.0,
+            garbage_delay: None,
+            start_level: 1,
+        }
+    }
+}
+
+impl Default for HandicapLimits {
+    fn default() -> Self {
+        Self {
+            min_multiplier: 0.5,
+            max_multiplier: 2.0,
+            max_garbage_delay: 10,
+            max_start_level: 20,
+        }
+    }
+}
+
+impl Handicap {
+    /// The handicap brought within the limits. Multipliers which aren't numbers are reset.
+    pub fn clamped(&self, limits: &HandicapLimits) -> Self {
+        let clamp_multiplier = |m: f32| {
+            if m.is_nan() {
+                1.0
+            } else {
+                m.clamp(limits.min_multiplier, limits.max_multiplier)
+            }
+        };
+        Self {
+            attack_multiplier: clamp_multiplier(self.attack_multiplier),
+            incoming_multiplier: clamp_multiplier(self.incoming_multiplier),
+            garbage_delay: self
+                .garbage_delay
+                .map(|delay| delay.clamp(1, limits.max_garbage_delay.max(1))),
+            start_level: self.start_level.clamp(1, limits.max_start_level.max(1)),
+        }
+    }
+
+    pub fn garbage_delay(&self) -> usize {
+        self.garbage_delay.unwrap_or(consts::GARBAGE_TURN_COUNT)
+    }
+
+    pub fn scale_outgoing(&self, num_lines: usize) -> usize {
+        (num_lines as f32 * self.attack_multiplier).round() as usize
+    }
+
+    pub fn scale_incoming(&self, num_lines: usize) -> usize {
+        (num_lines as f32 * self.incoming_multiplier).round() as usize
+    }
+
+    fn describe(&self) -> String {
+        let mut parts = vec![];
+        if self.attack_multiplier != 1.0 {
+            parts.push(format!("ATK x{:.2}", self.attack_multiplier));
+        }
+        if self.incoming_multiplier != 1.0 {
+            parts.push(format!("IN x{:.2}", self.incoming_multiplier));
+        }
+        if let Some(delay) = self.garbage_delay {
+            parts.push(format!("DELAY {delay}"));
+        }
+        if self.start_level != 1 {
+            parts.push(format!("LVL {}", self.start_level));
+        }
+        parts.join(" ")
+    }
+}
+
+fn add_handicap_label_to_root(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    root_q: Query<(Entity, &GameRoot), Added<GameRoot>>,
+    local_game_root_res: Option<Res<LocalGameRoot>>,
+) {
+    let local_id = local_game_root_res.map(|lgr| lgr.game_id);
+    for (root_entity, game_root) in &root_q {
+        // Only shown in the opponent view.
+        if Some(game_root.game_id) == local_id {
+            continue;
+        }
+        commands
+            .spawn((
+                HandicapLabel,
+                TextFont {
+                    font: asset_server.load("fonts/white-rabbit.ttf"),
+                    font_size: 60.,
+                    font_smoothing: FontSmoothing::None,
+                },
+                TextColor(Color::hsl(180., 1., 0.7)),
+                Transform::from_xyz(0., -assets::BLOCK_SIZE * 3., 0.),
+                Anchor::TopLeft,
+            ))
+            .set_parent(root_entity);
+    }
+}
+
+fn update_handicap_labels(
+    q_root: Query<&GameRoot>,
+    q_container: Query<&GameContainer>,
+    mut q_label: Query<(&mut Text2d, &Parent), With<HandicapLabel>>,
+) {
+    let container = q_container.single();
+    for (mut label, parent) in q_label.iter_mut() {
+        let Ok(game_root) = q_root.get(parent.get()) else {
+            continue;
+        };
+        label.0 = container.handicap_for_game(&game_root.game_id).describe();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clamps_to_the_limits() {
+        let limits = HandicapLimits::default();
+        assert_eq!(Handicap::default().clamped(&limits), Handicap::default());
+
+        let greedy = Handicap {
+            attack_multiplier: 100.0,
+            incoming_multiplier: f32::NAN,
+            garbage_delay: Some(1000),
+            start_level: -5,
+        };
+        assert_eq!(
+            greedy.clamped(&limits),
+            Handicap {
+                attack_multiplier: limits.max_multiplier,
+                incoming_multiplier: 1.0,
+                garbage_delay: Some(limits.max_garbage_delay),
+                start_level: 1,
+            }
+        );
+    }
+}
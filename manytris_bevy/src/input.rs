@@ -1,3 +1,4 @@
+use crate::root::GameId;
 use crate::states;
 use crate::states::{
     should_accept_game_input, ConnectionState, ExecType, MenuState, PauseState, PlayingState,
@@ -11,7 +12,7 @@ const INITIAL_REPEAT: Duration = Duration::from_millis(160);
 const REPEAT: Duration = Duration::from_millis(30);
 
 pub fn plugin(app: &mut App) {
-    app.init_resource::<RepeatTimes>().add_systems(
+    app.init_resource::<PlayerInput>().add_systems(
         Update,
         (
             handle_menu_input
@@ -22,6 +23,7 @@ pub fn plugin(app: &mut App) {
                 .in_set(UpdateSystems::Input)
                 .run_if(in_state(PlayingState::Playing))
                 .run_if(states::is_human)
+                .run_if(not(states::is_local_match))
                 .run_if(should_accept_game_input),
         )
             .chain(),
@@ -32,6 +34,8 @@ pub fn plugin(app: &mut App) {
 pub struct InputEvent {
     pub input_type: InputType,
     pub is_repeat: bool,
+    /// Game the input is for, or the local game if None.
+    pub game_id: Option<GameId>,
 }
 
 #[derive(Copy, Clone)]
@@ -46,46 +50,173 @@ pub enum InputType {
     EnqueueGarbageEvent(usize),
 }
 
+/// Keys controlling one board.
+#[derive(Clone, Copy, Debug)]
+pub struct KeyBindings {
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub down: KeyCode,
+    pub rotate_ccw: KeyCode,
+    pub rotate_cw: KeyCode,
+    pub drop: KeyCode,
+    pub hold: KeyCode,
+}
+
+impl KeyBindings {
+    pub const SINGLE_PLAYER: Self = Self {
+        left: KeyCode::ArrowLeft,
+        right: KeyCode::ArrowRight,
+        down: KeyCode::ArrowDown,
+        rotate_ccw: KeyCode::KeyZ,
+        rotate_cw: KeyCode::KeyX,
+        drop: KeyCode::Space,
+        hold: KeyCode::KeyC,
+    };
+
+    /// One set per board of a local versus game, spread out so players don't share keys.
+    pub const LOCAL_VERSUS: [Self; 4] = [
+        Self {
+            left: KeyCode::KeyA,
+            right: KeyCode::KeyD,
+            down: KeyCode::KeyS,
+            rotate_ccw: KeyCode::KeyQ,
+            rotate_cw: KeyCode::KeyE,
+            drop: KeyCode::KeyW,
+            hold: KeyCode::ShiftLeft,
+        },
+        Self {
+            left: KeyCode::ArrowLeft,
+            right: KeyCode::ArrowRight,
+            down: KeyCode::ArrowDown,
+            rotate_ccw: KeyCode::Comma,
+            rotate_cw: KeyCode::Period,
+            drop: KeyCode::ArrowUp,
+            hold: KeyCode::Slash,
+        },
+        Self {
+            left: KeyCode::KeyJ,
+            right: KeyCode::KeyL,
+            down: KeyCode::KeyK,
+            rotate_ccw: KeyCode::KeyU,
+            rotate_cw: KeyCode::KeyO,
+            drop: KeyCode::KeyI,
+            hold: KeyCode::KeyH,
+        },
+        Self {
+            left: KeyCode::Numpad4,
+            right: KeyCode::Numpad6,
+            down: KeyCode::Numpad5,
+            rotate_ccw: KeyCode::Numpad7,
+            rotate_cw: KeyCode::Numpad9,
+            drop: KeyCode::Numpad8,
+            hold: KeyCode::Numpad0,
+        },
+    ];
+}
+
+/// Turns one player's keys, and optionally a gamepad, into input events.
 #[derive(Resource)]
-pub struct RepeatTimes {
+pub struct PlayerInput {
     repeating_inputs: Vec<RepeatingInput>,
+    drop: (KeyCode, GamepadButton),
+    hold: (KeyCode, GamepadButton),
 }
 
 struct RepeatingInput {
     next_time: Option<Duration>,
     input_type: InputType,
     key: KeyCode,
+    button: GamepadButton,
 }
 
-impl Default for RepeatTimes {
+impl Default for PlayerInput {
     fn default() -> Self {
+        Self::new(&KeyBindings::SINGLE_PLAYER)
+    }
+}
+
+impl PlayerInput {
+    pub fn new(bindings: &KeyBindings) -> Self {
         use InputType::*;
         Self {
             repeating_inputs: vec![
-                RepeatingInput::new(ShiftEvent(Shift::Left), KeyCode::ArrowLeft),
-                RepeatingInput::new(ShiftEvent(Shift::Right), KeyCode::ArrowRight),
-                RepeatingInput::new(RotateEvent(Rot::Ccw), KeyCode::KeyZ),
-                RepeatingInput::new(RotateEvent(Rot::Cw), KeyCode::KeyX),
-                RepeatingInput::new(DownEvent, KeyCode::ArrowDown),
+                RepeatingInput::new(
+                    ShiftEvent(Shift::Left),
+                    bindings.left,
+                    GamepadButton::DPadLeft,
+                ),
+                RepeatingInput::new(
+                    ShiftEvent(Shift::Right),
+                    bindings.right,
+                    GamepadButton::DPadRight,
+                ),
+                RepeatingInput::new(
+                    RotateEvent(Rot::Ccw),
+                    bindings.rotate_ccw,
+                    GamepadButton::West,
+                ),
+                RepeatingInput::new(
+                    RotateEvent(Rot::Cw),
+                    bindings.rotate_cw,
+                    GamepadButton::South,
+                ),
+                RepeatingInput::new(DownEvent, bindings.down, GamepadButton::DPadDown),
             ],
+            drop: (bindings.drop, GamepadButton::DPadUp),
+            hold: (bindings.hold, GamepadButton::LeftTrigger),
         }
     }
+
+    /// Events for everything pressed or repeating this frame, for the local game.
+    pub fn read(
+        &mut self,
+        now: Duration,
+        keys: &ButtonInput<KeyCode>,
+        gamepad: Option<&Gamepad>,
+    ) -> Vec<InputEvent> {
+        let mut events: Vec<InputEvent> = self
+            .repeating_inputs
+            .iter_mut()
+            .filter_map(|repeating| {
+                let pressed = keys.pressed(repeating.key)
+                    || keys.just_pressed(repeating.key)
+                    || gamepad.is_some_and(|g| g.pressed(repeating.button));
+                repeating.get_event(now, pressed)
+            })
+            .collect();
+
+        // Non-repeating events
+        let just_pressed = |(key, button): (KeyCode, GamepadButton)| {
+            keys.just_pressed(key) || gamepad.is_some_and(|g| g.just_pressed(button))
+        };
+        for (binding, input_type) in [
+            (self.drop, InputType::DropEvent),
+            (self.hold, InputType::HoldEvent),
+        ] {
+            if just_pressed(binding) {
+                events.push(InputEvent {
+                    input_type,
+                    is_repeat: false,
+                    game_id: None,
+                });
+            }
+        }
+        events
+    }
 }
 
 impl RepeatingInput {
-    fn new(input_type: InputType, key: KeyCode) -> Self {
+    fn new(input_type: InputType, key: KeyCode, button: GamepadButton) -> Self {
         Self {
             next_time: None,
             input_type,
             key,
+            button,
         }
     }
 
-    fn get_event(&mut self, now: Duration, keys: &ButtonInput<KeyCode>) -> Option<InputEvent> {
-        match (
-            keys.pressed(self.key) || keys.just_pressed(self.key),
-            self.next_time,
-        ) {
+    fn get_event(&mut self, now: Duration, pressed: bool) -> Option<InputEvent> {
+        match (pressed, self.next_time) {
             // Not pressed, reset
             (false, _) => {
                 self.next_time = None;
@@ -97,6 +228,7 @@ impl RepeatingInput {
                 Some(InputEvent {
                     input_type: self.input_type,
                     is_repeat: false,
+                    game_id: None,
                 })
             }
             // Button is being Held
@@ -106,6 +238,7 @@ impl RepeatingInput {
                     Some(InputEvent {
                         input_type: self.input_type,
                         is_repeat: true,
+                        game_id: None,
                     })
                 } else {
                     None
@@ -134,7 +267,7 @@ fn handle_menu_input(
         };
         *menu_state = new_menu;
 
-        if *exec_type == ExecType::StandAlone {
+        if exec_type.is_offline() {
             *pause_state = match new_menu {
                 MenuState::Open => PauseState::Paused,
                 MenuState::Closed => PauseState::Unpaused,
@@ -145,51 +278,27 @@ fn handle_menu_input(
 
 fn update_for_input(
     keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
     time: Res<Time<Fixed>>,
-    mut repeat_times: ResMut<RepeatTimes>,
+    mut player_input: ResMut<PlayerInput>,
     mut input_event_writer: EventWriter<InputEvent>,
 ) {
     let now = time.elapsed();
+    input_event_writer.send_batch(player_input.read(now, &keys, gamepads.iter().next()));
 
-    for repeating in &mut repeat_times.repeating_inputs {
-        if let Some(event) = repeating.get_event(now, &keys) {
-            input_event_writer.send(event);
+    // Debug events
+    let debug_inputs = [
+        (KeyCode::KeyQ, InputType::JumpToBotStartPositionEvent),
+        (KeyCode::KeyW, InputType::PerformBotMoveEvent),
+        (KeyCode::KeyG, InputType::EnqueueGarbageEvent(1)),
+    ];
+    for (key, input_type) in debug_inputs {
+        if keys.just_pressed(key) {
+            input_event_writer.send(InputEvent {
+                input_type,
+                is_repeat: false,
+                game_id: None,
+            });
         }
     }
-
-    // Non-repeating events
-    if keys.just_pressed(KeyCode::Space) {
-        input_event_writer.send(InputEvent {
-            input_type: InputType::DropEvent,
-            is_repeat: false,
-        });
-    }
-
-    if keys.just_pressed(KeyCode::KeyC) {
-        input_event_writer.send(InputEvent {
-            input_type: InputType::HoldEvent,
-            is_repeat: false,
-        });
-    }
-
-    if keys.just_pressed(KeyCode::KeyQ) {
-        input_event_writer.send(InputEvent {
-            input_type: InputType::JumpToBotStartPositionEvent,
-            is_repeat: false,
-        });
-    }
-
-    if keys.just_pressed(KeyCode::KeyW) {
-        input_event_writer.send(InputEvent {
-            input_type: InputType::PerformBotMoveEvent,
-            is_repeat: false,
-        });
-    }
-
-    if keys.just_pressed(KeyCode::KeyG) {
-        input_event_writer.send(InputEvent {
-            input_type: InputType::EnqueueGarbageEvent(1),
-            is_repeat: false,
-        });
-    }
 }
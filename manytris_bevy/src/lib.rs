@@ -1,12 +1,18 @@
 pub mod assets;
+pub mod battle_royale;
 pub mod block_render;
+pub mod bot_backend;
+pub mod bot_difficulty;
 pub mod cli_options;
 pub mod connecting_screen;
 pub mod field_blocks;
 pub mod game_container;
 pub mod garbage_counter;
+pub mod handicap;
 pub mod input;
+pub mod local_versus;
 pub mod main_menu;
+pub mod match_rules;
 pub mod net_client;
 mod net_game_control_manager;
 pub mod net_listener;
@@ -19,8 +25,11 @@ pub mod shape_producer;
 pub mod states;
 pub mod stats_server;
 pub mod system_sets;
+pub mod targeting;
 pub mod tick_limiter;
 pub mod window_blocks;
 
 #[cfg(feature = "bot")]
 pub mod bot_input;
+#[cfg(feature = "bot")]
+pub mod bot_worker;
@@ -0,0 +1,281 @@
+use crate::bot_difficulty::BotDifficulty;
+use crate::input::{InputEvent, InputType, KeyBindings, PlayerInput};
+use crate::root::{GameId, GameRoot, LockEvent};
+use crate::states::{should_accept_game_input, PlayingState};
+use crate::system_sets::UpdateSystems;
+use crate::targeting::{self, Targeting};
+use crate::{assets, states};
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy::text::FontSmoothing;
+use manytris_core::game_state::LockResult;
+
+pub const MAX_LOCAL_PLAYERS: usize = KeyBindings::LOCAL_VERSUS.len();
+pub const MAX_CPU_OPPONENTS: usize = 3;
+
+/// Number of boards to start a local versus game with.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct LocalPlayerCount(pub usize);
+
+/// Opponents to start a vs CPU game with.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct VsCpuSettings {
+    pub opponents: usize,
+    pub difficulty: BotDifficulty,
+}
+
+/// Boards sharing one window, each played from its own keys or gamepad, or by a bot.
+#[derive(Resource)]
+pub struct LocalVersus {
+    boards: Vec<LocalBoard>,
+}
+
+struct LocalBoard {
+    game_id: GameId,
+    /// None for boards played by a bot.
+    input: Option<PlayerInput>,
+    topped_out: bool,
+}
+
+#[derive(Component)]
+#[require(Text2d)]
+struct PlayerLabel;
+
+pub fn plugin(app: &mut App) {
+    app.init_resource::<LocalPlayerCount>()
+        .init_resource::<VsCpuSettings>()
+        .add_systems(
+            OnEnter(PlayingState::Playing),
+            reset_targeting.run_if(states::is_local_match),
+        )
+        .add_systems(
+            OnExit(PlayingState::Playing),
+            teardown.run_if(states::is_local_match),
+        )
+        .add_systems(
+            Update,
+            (
+                read_board_inputs
+                    .in_set(UpdateSystems::Input)
+                    .run_if(should_accept_game_input),
+                exchange_garbage.in_set(UpdateSystems::Input),
+                (
+                    add_player_label_to_root.in_set(UpdateSystems::PreRender),
+                    update_player_labels.in_set(UpdateSystems::Render),
+                )
+                    .run_if(states::headed),
+            )
+                .run_if(in_state(PlayingState::Playing))
+                .run_if(states::is_local_match),
+        );
+}
+
+impl Default for LocalPlayerCount {
+    fn default() -> Self {
+        Self(2)
+    }
+}
+
+impl Default for VsCpuSettings {
+    fn default() -> Self {
+        Self {
+            opponents: 1,
+            difficulty: BotDifficulty::default(),
+        }
+    }
+}
+
+impl LocalVersus {
+    /// Each human board is played with the given bindings, and the gamepads are handed out to
+    /// the human boards in the order they connected. Boards without bindings are bots.
+    pub fn new(boards: Vec<(GameId, Option<KeyBindings>)>) -> Self {
+        let boards = boards
+            .into_iter()
+            .map(|(game_id, bindings)| LocalBoard {
+                game_id,
+                input: bindings.as_ref().map(PlayerInput::new),
+                topped_out: false,
+            })
+            .collect();
+        Self { boards }
+    }
+
+    /// Live games played by a bot.
+    pub fn bot_games(&self) -> Vec<GameId> {
+        self.boards
+            .iter()
+            .filter(|b| b.input.is_none() && !b.topped_out)
+            .map(|b| b.game_id)
+            .collect()
+    }
+
+    /// Games which haven't topped out yet.
+    pub fn live_games(&self) -> Vec<GameId> {
+        self.boards
+            .iter()
+            .filter(|b| !b.topped_out)
+            .map(|b| b.game_id)
+            .collect()
+    }
+
+    fn player_name(&self, game_id: GameId) -> Option<String> {
+        let idx = self.boards.iter().position(|b| b.game_id == game_id)?;
+        let board = &self.boards[idx];
+        let same_kind_before = self.boards[..idx]
+            .iter()
+            .filter(|b| b.input.is_some() == board.input.is_some())
+            .count();
+        Some(if board.input.is_some() {
+            format!("P{}", same_kind_before + 1)
+        } else {
+            format!("CPU {}", same_kind_before + 1)
+        })
+    }
+
+    fn board(&self, game_id: GameId) -> Option<&LocalBoard> {
+        self.boards.iter().find(|b| b.game_id == game_id)
+    }
+}
+
+fn reset_targeting(mut targeting: ResMut<Targeting>) {
+    *targeting = Targeting::default();
+}
+
+fn teardown(mut commands: Commands) {
+    commands.remove_resource::<LocalVersus>();
+}
+
+fn read_board_inputs(
+    keys: Res<ButtonInput<KeyCode>>,
+    q_gamepads: Query<(Entity, &Gamepad)>,
+    time: Res<Time<Fixed>>,
+    mut local_versus: ResMut<LocalVersus>,
+    mut input_event_writer: EventWriter<InputEvent>,
+) {
+    let mut gamepads: Vec<_> = q_gamepads.iter().collect();
+    gamepads.sort_by_key(|(entity, _)| *entity);
+
+    let now = time.elapsed();
+    let human_boards = local_versus.boards.iter_mut().filter_map(|b| {
+        b.input
+            .as_mut()
+            .map(|input| (b.game_id, b.topped_out, input))
+    });
+    for (idx, (game_id, topped_out, input)) in human_boards.enumerate() {
+        if topped_out {
+            continue;
+        }
+        let gamepad = gamepads.get(idx).map(|(_, gamepad)| *gamepad);
+        let game_id = Some(game_id);
+        input_event_writer.send_batch(
+            input
+                .read(now, &keys, gamepad)
+                .into_iter()
+                .map(|event| InputEvent { game_id, ..event }),
+        );
+    }
+}
+
+/// Send each board's attacks to the others, like the server does for networked games.
+fn exchange_garbage(
+    mut lock_events: EventReader<LockEvent>,
+    q_roots: Query<&GameRoot>,
+    mut local_versus: ResMut<LocalVersus>,
+    mut targeting: ResMut<Targeting>,
+    mut input_event_writer: EventWriter<InputEvent>,
+    mut play_state: ResMut<NextState<PlayingState>>,
+) {
+    for LockEvent {
+        game_id,
+        lock_result,
+        attack,
+    } in lock_events.read()
+    {
+        match lock_result {
+            LockResult::Ok { .. } => {
+                if *attack == 0 {
+                    continue;
+                }
+                let live_games = local_versus.live_games();
+                let dangers = q_roots
+                    .iter()
+                    .filter(|gr| live_games.contains(&gr.game_id))
+                    .map(|gr| (gr.game_id, targeting::danger(&gr.active_game.game)))
+                    .collect();
+                targeting.update(&dangers, |a, b| a == b, &mut rand::thread_rng());
+
+                for (target_id, num_lines) in targeting.route_attack(*game_id, *attack) {
+                    input_event_writer.send(InputEvent {
+                        input_type: InputType::EnqueueGarbageEvent(num_lines),
+                        is_repeat: false,
+                        game_id: Some(target_id),
+                    });
+                }
+            }
+            LockResult::GameOver => {
+                if let Some(board) = local_versus
+                    .boards
+                    .iter_mut()
+                    .find(|b| b.game_id == *game_id)
+                {
+                    board.topped_out = true;
+                }
+
+                let live_games = local_versus.live_games();
+                if live_games.len() <= 1 {
+                    match live_games.first() {
+                        Some(winner) => {
+                            println!("{} wins!", local_versus.player_name(*winner).unwrap())
+                        }
+                        None => println!("Draw!"),
+                    }
+                    play_state.set(PlayingState::MainMenu);
+                }
+            }
+        }
+    }
+}
+
+fn add_player_label_to_root(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    root_q: Query<Entity, Added<GameRoot>>,
+) {
+    for root_entity in &root_q {
+        commands
+            .spawn((
+                PlayerLabel,
+                TextFont {
+                    font: asset_server.load("fonts/white-rabbit.ttf"),
+                    font_size: 15.,
+                    font_smoothing: FontSmoothing::None,
+                },
+                TextColor(Color::WHITE),
+                Transform::from_xyz(0., assets::BLOCK_SIZE * 22.5, 0.),
+                Anchor::BottomLeft,
+            ))
+            .set_parent(root_entity);
+    }
+}
+
+fn update_player_labels(
+    q_root: Query<&GameRoot>,
+    mut q_label: Query<(&mut Text2d, &Parent), With<PlayerLabel>>,
+    local_versus: Res<LocalVersus>,
+) {
+    for (mut label, parent) in q_label.iter_mut() {
+        let Ok(game_root) = q_root.get(parent.get()) else {
+            continue;
+        };
+        let game_id = game_root.game_id;
+        let Some(name) = local_versus.player_name(game_id) else {
+            continue;
+        };
+        let topped_out = local_versus.board(game_id).is_some_and(|b| b.topped_out);
+        label.0 = if topped_out {
+            format!("{name}  OUT")
+        } else {
+            name
+        };
+    }
+}
@@ -1,3 +1,5 @@
+use crate::battle_royale::LastMatchResult;
+use crate::local_versus::{LocalPlayerCount, VsCpuSettings};
 use crate::states::{ExecType, MultiplayerType, PlayingState};
 use bevy::color::palettes::basic::*;
 use bevy::prelude::*;
@@ -11,13 +13,50 @@ pub fn plugin(app: &mut App) {
 #[derive(Component, Debug)]
 pub enum MainMenuButtons {
     StartStandAloneButton,
+    StartLocalVersusButton,
+    StartVsCpuButton,
+    CpuDifficultyButton,
     StartMultiplayerButton,
 }
 
+#[derive(Component, Debug)]
+struct CpuDifficultyText;
+
 #[derive(Component, Debug)]
 pub struct MainMenu;
 
-fn setup(mut commands: Commands) {
+fn setup(
+    mut commands: Commands,
+    last_match_result: Option<Res<LastMatchResult>>,
+    local_player_count: Res<LocalPlayerCount>,
+    vs_cpu_settings: Res<VsCpuSettings>,
+) {
+    if let Some(result) = last_match_result {
+        commands
+            .spawn(Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(40.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            })
+            .insert(MainMenu)
+            .with_child((
+                Text(format!(
+                    "Last game: #{} of {}, {} KOs, {} badges",
+                    result.standing.placement.unwrap_or(0),
+                    result.num_players,
+                    result.standing.kos,
+                    result.standing.badges
+                )),
+                TextFont {
+                    font_size: 30.0,
+                    ..default()
+                },
+                TextColor(WHITE.into()),
+            ));
+    }
+
     let main_menu_container = commands
         .spawn(Node {
             width: Val::Percent(100.0),
@@ -59,6 +98,55 @@ fn setup(mut commands: Commands) {
         ))
         .id();
 
+    let start_local_versus_button = commands
+        .spawn(button_template.clone())
+        .insert(MainMenuButtons::StartLocalVersusButton)
+        .id();
+    let start_local_versus_text = commands
+        .spawn((
+            Text(format!("Local Versus ({}P)", local_player_count.0)),
+            button_text_font.clone(),
+            button_text_color,
+        ))
+        .id();
+
+    let mut buttons = vec![start_stand_alone_button, start_local_versus_button];
+
+    // Bots only exist in builds with the bot feature.
+    if cfg!(feature = "bot") {
+        let start_vs_cpu_button = commands
+            .spawn(button_template.clone())
+            .insert(MainMenuButtons::StartVsCpuButton)
+            .id();
+        let start_vs_cpu_text = commands
+            .spawn((
+                Text(format!("vs CPU ({})", vs_cpu_settings.opponents)),
+                button_text_font.clone(),
+                button_text_color,
+            ))
+            .id();
+        commands
+            .entity(start_vs_cpu_button)
+            .add_children(&[start_vs_cpu_text]);
+
+        let cpu_difficulty_button = commands
+            .spawn(button_template.clone())
+            .insert(MainMenuButtons::CpuDifficultyButton)
+            .id();
+        let cpu_difficulty_text = commands
+            .spawn((
+                CpuDifficultyText,
+                Text(format!("CPU: {:?}", vs_cpu_settings.difficulty)),
+                button_text_font.clone(),
+                button_text_color,
+            ))
+            .id();
+        commands
+            .entity(cpu_difficulty_button)
+            .add_children(&[cpu_difficulty_text]);
+        buttons.extend([start_vs_cpu_button, cpu_difficulty_button]);
+    }
+
     let start_multiplayer_button = commands
         .spawn(button_template)
         .insert(MainMenuButtons::StartMultiplayerButton)
@@ -71,12 +159,15 @@ fn setup(mut commands: Commands) {
         ))
         .id();
 
-    commands
-        .entity(main_menu_container)
-        .add_children(&[start_stand_alone_button, start_multiplayer_button]);
+    buttons.push(start_multiplayer_button);
+
+    commands.entity(main_menu_container).add_children(&buttons);
     commands
         .entity(start_stand_alone_button)
         .add_children(&[start_stand_alone_text]);
+    commands
+        .entity(start_local_versus_button)
+        .add_children(&[start_local_versus_text]);
     commands
         .entity(start_multiplayer_button)
         .add_children(&[start_multiplayer_text]);
@@ -86,6 +177,9 @@ fn update(
     interaction_q: Query<(&Interaction, &MainMenuButtons), Changed<Interaction>>,
     mut next_play_state: ResMut<NextState<PlayingState>>,
     mut exec_type: ResMut<ExecType>,
+    local_player_count: Res<LocalPlayerCount>,
+    mut vs_cpu_settings: ResMut<VsCpuSettings>,
+    mut difficulty_text_q: Query<&mut Text, With<CpuDifficultyText>>,
 ) {
     for (interaction, button) in &interaction_q {
         match interaction {
@@ -99,6 +193,20 @@ fn update(
                 *exec_type = ExecType::StandAlone;
                 next_play_state.set(PlayingState::Playing);
             }
+            MainMenuButtons::StartLocalVersusButton => {
+                *exec_type = ExecType::LocalVersus(local_player_count.0);
+                next_play_state.set(PlayingState::Playing);
+            }
+            MainMenuButtons::StartVsCpuButton => {
+                *exec_type = ExecType::VsCpu(vs_cpu_settings.opponents);
+                next_play_state.set(PlayingState::Playing);
+            }
+            MainMenuButtons::CpuDifficultyButton => {
+                vs_cpu_settings.difficulty = vs_cpu_settings.difficulty.next();
+                for mut text in &mut difficulty_text_q {
+                    text.0 = format!("CPU: {:?}", vs_cpu_settings.difficulty);
+                }
+            }
             MainMenuButtons::StartMultiplayerButton => {
                 *exec_type = ExecType::MultiplayerClient(MultiplayerType::Human);
                 next_play_state.set(PlayingState::Connecting);
@@ -0,0 +1,15 @@
+use crate::handicap::HandicapLimits;
+use bevy::prelude::*;
+use manytris_core::attack::AttackTable;
+use serde::{Deserialize, Serialize};
+
+/// Rules shared by every game in a match. Clients receive the server's rules when they join.
+#[derive(Resource, Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MatchRules {
+    pub attack_table: AttackTable,
+    /// Disconnect clients teleporting their pieces with `JumpToBotStartPosition`, so bots have to
+    /// play with the same inputs as people.
+    pub forbid_teleports: bool,
+    /// Bounds on the handicaps players ask for when joining.
+    pub handicap_limits: HandicapLimits,
+}
@@ -1,5 +1,5 @@
 use crate::cli_options::HostConfig;
-use crate::game_container::LocalGameRoot;
+use crate::game_container::{LocalGameRoot, PlayerSettings};
 use crate::net_game_control_manager::{ClientControlEvent, ServerControlEvent};
 use crate::net_protocol::NetMessage;
 use crate::root::TickEvent;
@@ -27,6 +27,10 @@ pub enum ClientNetComponent {
 #[derive(Resource)]
 pub struct NetClientConfig(pub HostConfig);
 
+/// Team and handicap to ask for when joining a server.
+#[derive(Resource, Default)]
+pub struct JoinSettings(pub PlayerSettings);
+
 pub fn plugin(app: &mut App) {
     app.add_systems(
         OnEnter(PlayingState::Playing),
@@ -47,7 +51,8 @@ pub fn plugin(app: &mut App) {
             .run_if(states::is_multiplayer_client),
     )
     .add_event::<ClientControlEvent>()
-    .add_event::<ServerControlEvent>();
+    .add_event::<ServerControlEvent>()
+    .init_resource::<JoinSettings>();
 
     #[cfg(feature = "debug_tools")]
     app.add_systems(
@@ -114,6 +119,7 @@ fn update_client_connect(
     mut control_events: EventWriter<ClientControlEvent>,
     config: Res<NetClientConfig>,
     local_game_root: Option<Res<LocalGameRoot>>,
+    join_settings: Res<JoinSettings>,
     mut connection_state: ResMut<ConnectionState>,
     mut menu_state: ResMut<MenuState>,
     #[cfg(feature = "debug_tools")] mut debug_delay: Option<ResMut<DebugReconnectDelay>>,
@@ -161,7 +167,9 @@ fn update_client_connect(
                     println!("Connected!");
                     new_net = Some(ClientNetComponent::Connected(sr_pair.clone()));
                     let request = match local_game_root {
-                        None => ClientControlEvent::JoinRequest,
+                        None => ClientControlEvent::JoinRequest {
+                            settings: join_settings.0.clone(),
+                        },
                         Some(game_root) => ClientControlEvent::ReconnectRequest(game_root.game_id),
                     };
                     control_events.send(request);
@@ -1,8 +1,13 @@
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
+use crate::battle_royale::PlayerStanding;
+use crate::game_container::PlayerSettings;
+use crate::match_rules::MatchRules;
 use crate::root::GameId;
+use crate::targeting::TargetingStrategy;
 use manytris_core::game_state::GameState;
 
 #[derive(Deserialize, Serialize, Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
@@ -10,13 +15,15 @@ pub struct ConnectionId(Uuid);
 
 #[derive(Clone, Deserialize, Serialize, Debug, Event)]
 pub enum ClientControlEvent {
-    JoinRequest,
+    JoinRequest { settings: PlayerSettings },
     ReconnectRequest(GameId),
+    SetTargetingStrategy(TargetingStrategy),
 }
 
 #[derive(Clone, Deserialize, Serialize, Debug, Event)]
 pub enum ServerControlEvent {
     AssignGameId(GameId),
+    SetMatchRules(MatchRules),
     SnapshotResponse(GameState, GameId),
     DeliverGarbage {
         from_game_id: GameId,
@@ -24,6 +31,11 @@ pub enum ServerControlEvent {
     },
     ClientGameOver(GameId),
     RejectConnectionRequest,
+    /// The games each game's attacks are currently going to.
+    TargetingUpdate(BTreeMap<GameId, Vec<GameId>>),
+    /// Every game's team and handicap.
+    PlayerSettingsUpdate(BTreeMap<GameId, PlayerSettings>),
+    StandingsUpdate(Vec<PlayerStanding>),
 }
 
 #[derive(Copy, Clone)]
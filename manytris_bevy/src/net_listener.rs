@@ -1,5 +1,6 @@
 use crate::cli_options::HostConfig;
 use crate::game_container::GameContainer;
+use crate::match_rules::MatchRules;
 use crate::net_game_control_manager::{
     ConnectionDropped, ConnectionId, ConnectionTarget, ReceiveControlEventFromClient,
     SendControlEventToClient,
@@ -11,6 +12,7 @@ use crate::states;
 use crate::states::PlayingState;
 use crate::system_sets::UpdateSystems;
 use bevy::prelude::*;
+use manytris_core::game_state::TickMutation;
 use std::collections::BTreeMap;
 use std::error::Error;
 use std::io;
@@ -75,6 +77,7 @@ fn listener_system(
     mut tick_writer: EventWriter<TickEvent>,
     mut control_writer: EventWriter<ReceiveControlEventFromClient>,
     mut disconnect_writer: EventWriter<ConnectionDropped>,
+    match_rules: Res<MatchRules>,
 ) {
     let listener = listener_q.single_mut().into_inner();
 
@@ -92,6 +95,18 @@ fn listener_system(
                 for m in msgs {
                     match m {
                         NetMessage::Tick(tm) => {
+                            // The client has already applied the teleport to its own game, so
+                            // ignoring it would leave the two out of step.
+                            if match_rules.forbid_teleports
+                                && matches!(tm.mutation, TickMutation::JumpToBotStartPosition(_))
+                            {
+                                eprintln!(
+                                    "Disconnecting connection {connection_id:?}, which teleported \
+                                     a piece in a match that forbids it"
+                                );
+                                remove_connections.push(*connection_id);
+                                break;
+                            }
                             tick_writer.send(TickEvent::new_remote(tm));
                         }
                         NetMessage::ClientControl(event) => {
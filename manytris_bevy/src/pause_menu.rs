@@ -1,5 +1,5 @@
 use crate::states::{
-    is_menu_closed, is_menu_open, is_stand_alone, ConnectionState, ExecType, MenuState, PauseState,
+    is_menu_closed, is_menu_open, is_offline, ConnectionState, ExecType, MenuState, PauseState,
     PlayingState,
 };
 use bevy::color::palettes::basic::*;
@@ -29,7 +29,7 @@ pub fn plugin(app: &mut App) {
     )
     .add_systems(
         OnEnter(PlayingState::Restarting),
-        apply_restart_transition.run_if(is_stand_alone),
+        apply_restart_transition.run_if(is_offline),
     );
 }
 
@@ -137,8 +137,8 @@ fn spawn_pause_ui(
         commands.entity(resume_button).add_children(&[resume_text]);
         children.push(resume_button);
 
-        // Restart button (standalone only)
-        if *exec_type == ExecType::StandAlone {
+        // Restart button (offline only)
+        if exec_type.is_offline() {
             let restart_button = commands
                 .spawn(button_template.clone())
                 .insert(PauseButton::Restart)
@@ -1,9 +1,12 @@
 use std::time::Duration;
 
+use crate::bot_difficulty::BotDifficulty;
 use crate::cli_options::{BotConfig, ClientConfig, ExecCommand, ServerConfig};
+use crate::game_container::PlayerSettings;
 use crate::{
-    assets, block_render, connecting_screen, field_blocks, game_container, garbage_counter, input,
-    main_menu, net_client, net_listener, pause_menu, root, scoreboard, shape_producer, system_sets,
+    assets, battle_royale, block_render, connecting_screen, field_blocks, game_container,
+    garbage_counter, handicap, input, local_versus, main_menu, match_rules, net_client,
+    net_listener, pause_menu, root, scoreboard, shape_producer, system_sets, targeting,
     tick_limiter, window_blocks,
 };
 use bevy::core::TaskPoolThreadAssignmentPolicy;
@@ -64,6 +67,10 @@ pub fn run(cfg: ExecCommand) {
         input::plugin,
         net_listener::plugin,
         shape_producer::plugin,
+        targeting::plugin,
+        battle_royale::plugin,
+        handicap::plugin,
+        local_versus::plugin,
     ));
 
     if false {
@@ -76,37 +83,120 @@ pub fn run(cfg: ExecCommand) {
 
     app.add_plugins(ReqwestPlugin::default());
 
-    if let ExecCommand::Client(ClientConfig { manager_server, .. }) = &cfg {
+    if let ExecCommand::Client(ClientConfig {
+        manager_server,
+        team,
+        handicap,
+        local_players,
+        cpu_opponents,
+        cpu_difficulty,
+        bot_search,
+    }) = &cfg
+    {
         app.insert_resource(manager_server.clone());
+        app.insert_resource(local_versus::LocalPlayerCount(*local_players));
+        app.insert_resource(local_versus::VsCpuSettings {
+            opponents: *cpu_opponents,
+            difficulty: *cpu_difficulty,
+        });
+        app.insert_resource(bot_search.clone());
+        add_local_bots_plugin(&mut app);
+        app.insert_resource(net_client::JoinSettings(PlayerSettings {
+            team: *team,
+            handicap: *handicap,
+        }));
     }
 
-    if let ExecCommand::Bot(BotConfig { server, .. }) = &cfg {
+    if let ExecCommand::Bot(BotConfig {
+        server,
+        team,
+        handicap,
+        bot_search,
+        ..
+    }) = &cfg
+    {
         app.insert_resource(net_client::NetClientConfig(server.clone()));
+        app.insert_resource(bot_search.clone());
+        app.insert_resource(net_client::JoinSettings(PlayerSettings {
+            team: *team,
+            handicap: *handicap,
+        }));
     }
 
-    if let ExecCommand::Server(ServerConfig { server, .. }) = &cfg {
+    if let ExecCommand::Server(ServerConfig {
+        server,
+        attack_table,
+        forbid_teleports,
+        handicap_limits,
+        ..
+    }) = &cfg
+    {
         app.insert_resource(net_listener::NetListenerConfig(server.clone()));
+        app.insert_resource(match_rules::MatchRules {
+            attack_table: attack_table.clone(),
+            forbid_teleports: *forbid_teleports,
+            handicap_limits: *handicap_limits,
+        });
         add_stats_server_plugin(&mut app);
     }
 
-    if let ExecCommand::Bot(BotConfig { bot_millis, .. }) = &cfg {
-        add_bot_input_plugin(&mut app, *bot_millis);
+    if let ExecCommand::Bot(BotConfig {
+        bot_millis,
+        difficulty,
+        fair_play,
+        input_millis,
+        ..
+    }) = &cfg
+    {
+        add_bot_input_plugin(
+            &mut app,
+            *bot_millis,
+            *difficulty,
+            *fair_play,
+            *input_millis,
+        );
     }
 
     app.run();
 }
 
 #[cfg(feature = "bot")]
-fn add_bot_input_plugin(app: &mut App, bot_millis: u64) {
+fn add_bot_input_plugin(
+    app: &mut App,
+    bot_millis: u64,
+    difficulty: BotDifficulty,
+    fair_play: bool,
+    input_millis: u64,
+) {
     use crate::bot_input;
 
     app.add_plugins(bot_input::BotInputPlugin {
         bot_period_millis: bot_millis,
+        difficulty,
+        fair_play,
+        input_period_millis: input_millis,
     });
 }
 
 #[cfg(not(feature = "bot"))]
-fn add_bot_input_plugin(_app: &mut App, _bot_millis: u64) {}
+fn add_bot_input_plugin(
+    _app: &mut App,
+    _bot_millis: u64,
+    _difficulty: BotDifficulty,
+    _fair_play: bool,
+    _input_millis: u64,
+) {
+}
+
+#[cfg(feature = "bot")]
+fn add_local_bots_plugin(app: &mut App) {
+    use crate::bot_input;
+
+    app.add_plugins(bot_input::local_bots_plugin);
+}
+
+#[cfg(not(feature = "bot"))]
+fn add_local_bots_plugin(_app: &mut App) {}
 
 #[cfg(feature = "stats_server")]
 fn add_stats_server_plugin(app: &mut App) {
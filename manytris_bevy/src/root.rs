@@ -1,5 +1,7 @@
-use crate::game_container::LocalGameRoot;
+use crate::game_container::LocalGames;
+use crate::handicap::Handicap;
 use crate::input::{InputEvent, InputType};
+use crate::match_rules::MatchRules;
 use crate::shape_producer::ShapeProducer;
 use crate::states;
 use crate::states::{is_paused, is_unpaused, PauseState, PlayingState};
@@ -7,9 +9,11 @@ use crate::system_sets::UpdateSystems;
 use bevy::prelude::*;
 use manytris_core::consts;
 use manytris_core::game_state::{DownType, GameState, LockResult, TickMutation, TickResult};
+use manytris_core::player_stats::{PlayerStats, StatsSummary};
 use manytris_core::shapes::Shape;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
 use std::time::Duration;
 use uuid::Uuid;
 
@@ -19,16 +23,22 @@ const LINES_PER_LEVEL: i32 = 10;
 #[derive(Resource, Default)]
 struct PauseTimerState {
     pause_time: Option<Duration>,
-    remaining_drop_time: Option<Duration>,
-    remaining_lock_time: Option<Duration>,
+    remaining_times: BTreeMap<GameId, RemainingTimes>,
+}
+
+struct RemainingTimes {
+    drop_time: Duration,
+    lock_time: Option<Duration>,
 }
 
 /// This plugin must be used for all executable variants.
 pub fn common_plugin(app: &mut App) {
     app.init_resource::<PauseTimerState>()
+        .init_resource::<MatchRules>()
         .add_event::<InputEvent>()
         .add_event::<TickEvent>()
         .add_event::<LockEvent>()
+        .add_event::<GarbageAppliedEvent>()
         .add_systems(
             Update,
             (
@@ -36,12 +46,12 @@ pub fn common_plugin(app: &mut App) {
                     .run_if(resource_changed::<PauseState>)
                     .run_if(is_paused)
                     .run_if(in_state(PlayingState::Playing))
-                    .run_if(states::is_stand_alone),
+                    .run_if(states::is_offline),
                 restore_timer_state_on_unpause
                     .run_if(resource_changed::<PauseState>)
                     .run_if(is_unpaused)
                     .run_if(in_state(PlayingState::Playing))
-                    .run_if(states::is_stand_alone),
+                    .run_if(states::is_offline),
                 produce_tick_events
                     .in_set(UpdateSystems::LocalEventProducers)
                     .run_if(in_state(PlayingState::Playing))
@@ -70,6 +80,9 @@ pub struct ActiveGame {
     lines_to_next_level: i32,
     next_drop_time: Duration,
     lock_timer_target: Option<Duration>,
+    pub stats: PlayerStats,
+    /// Time play started, moved forward by time spent paused.
+    start_time: Duration,
 }
 
 #[derive(Clone, Deserialize, Serialize, Debug)]
@@ -109,10 +122,24 @@ impl GameId {
     }
 }
 
+impl Display for GameId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 #[derive(Event, Deserialize, Serialize)]
 pub struct LockEvent {
     pub game_id: GameId,
     pub lock_result: LockResult,
+    /// Garbage lines sent to opponents by this lock.
+    pub attack: usize,
+}
+
+#[derive(Event)]
+pub struct GarbageAppliedEvent {
+    pub game_id: GameId,
+    pub num_lines: usize,
 }
 
 pub fn create_new_root(
@@ -121,6 +148,7 @@ pub fn create_new_root(
     transform: Transform,
     cur_time: Duration,
     shape_producer: &mut ShapeProducer,
+    handicap: &Handicap,
 ) -> (GameState, GameId, Entity) {
     let game_id = GameId::new();
     let initial_shapes = (0..consts::NUM_PREVIEWS * 2)
@@ -128,7 +156,8 @@ pub fn create_new_root(
         .map(|_| shape_producer.take(&game_id))
         .collect();
 
-    let active_game = ActiveGame::new(cur_time, initial_shapes);
+    let mut active_game = ActiveGame::new(cur_time, initial_shapes, handicap.start_level);
+    active_game.game.set_garbage_delay(handicap.garbage_delay());
     let game_state = active_game.game.clone();
     let entity = spawn_root(commands, container_entity, transform, active_game, game_id);
     (game_state, game_id, entity)
@@ -141,8 +170,9 @@ pub fn create_root_from_snapshot(
     gs: GameState,
     cur_time: Duration,
     game_id: GameId,
+    start_level: i32,
 ) -> Entity {
-    let active_game = ActiveGame::from_snapshot(gs, cur_time);
+    let active_game = ActiveGame::from_snapshot(gs, cur_time, start_level);
     spawn_root(commands, container_entity, transform, active_game, game_id)
 }
 
@@ -172,67 +202,77 @@ fn produce_tick_events(
     time: Res<Time<Fixed>>,
     mut q_root: Query<&mut GameRoot>,
     mut tick_event_writer: EventWriter<TickEvent>,
-    local_game_root_res: Option<Res<LocalGameRoot>>,
+    local_games: LocalGames,
 ) {
-    let Some(local_game_root) = local_game_root_res else {
-        return;
-    };
-    let Some(mut game_root) = q_root
-        .iter_mut()
-        .filter(|gr| gr.game_id == local_game_root.game_id)
-        .next()
-    else {
+    let local_ids = local_games.ids();
+    if local_ids.is_empty() {
         return;
-    };
-
-    let game_id = local_game_root.game_id;
-    let game = &mut game_root.active_game;
-
-    let mut tick_events = vec![];
-    use InputType::*;
-    use TickMutation::*;
-
-    tick_events.extend(
-        input_events
-            .read()
-            .map(|e| match e.input_type {
-                ShiftEvent(s) => vec![ShiftInput(s)],
-                RotateEvent(r) => vec![RotateInput(r)],
-                DownEvent => vec![DownInput(if e.is_repeat {
-                    DownType::HoldRepeat
-                } else {
-                    DownType::FirstPress
-                })],
-                DropEvent => vec![DropInput],
-                HoldEvent => vec![HoldInput],
-                EnqueueGarbageEvent(lines) => vec![EnqueueGarbage(lines)],
-                JumpToBotStartPositionEvent | PerformBotMoveEvent => vec![],
-            })
-            .flatten(),
-    );
+    }
 
-    let cur_time = time.elapsed();
-    while cur_time > game.next_drop_time {
-        tick_events.push(DownInput(DownType::Gravity));
-        let level = game.level;
-        game.next_drop_time += time_to_drop(level);
+    let mut inputs_by_game: BTreeMap<GameId, Vec<InputEvent>> = BTreeMap::new();
+    for event in input_events.read() {
+        if let Some(game_id) = local_games.input_target(event) {
+            inputs_by_game.entry(game_id).or_default().push(*event);
+        }
     }
 
-    if game.lock_timer_target.filter(|t| t <= &cur_time).is_some() {
-        tick_events.push(LockTimerExpired);
+    let cur_time = time.elapsed();
+    for mut game_root in q_root.iter_mut() {
+        let game_id = game_root.game_id;
+        if !local_ids.contains(&game_id) {
+            continue;
+        }
+        let game = &mut game_root.active_game;
+
+        let mut tick_events = vec![];
+        use InputType::*;
+        use TickMutation::*;
+
+        tick_events.extend(
+            inputs_by_game
+                .remove(&game_id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|e| match e.input_type {
+                    ShiftEvent(s) => vec![ShiftInput(s)],
+                    RotateEvent(r) => vec![RotateInput(r)],
+                    DownEvent => vec![DownInput(if e.is_repeat {
+                        DownType::HoldRepeat
+                    } else {
+                        DownType::FirstPress
+                    })],
+                    DropEvent => vec![DropInput],
+                    HoldEvent => vec![HoldInput],
+                    EnqueueGarbageEvent(lines) => vec![EnqueueGarbage(lines)],
+                    JumpToBotStartPositionEvent | PerformBotMoveEvent => vec![],
+                })
+                .flatten(),
+        );
+
+        while cur_time > game.next_drop_time {
+            tick_events.push(DownInput(DownType::Gravity));
+            let level = game.level;
+            game.next_drop_time += time_to_drop(level);
+        }
+
+        if game.lock_timer_target.filter(|t| t <= &cur_time).is_some() {
+            tick_events.push(LockTimerExpired);
+        }
+        tick_event_writer.send_batch(
+            tick_events
+                .into_iter()
+                .map(|mutation| TickEvent::new_local(TickMutationMessage { mutation, game_id })),
+        );
     }
-    tick_event_writer.send_batch(
-        tick_events
-            .into_iter()
-            .map(|mutation| TickEvent::new_local(TickMutationMessage { mutation, game_id })),
-    );
 }
 
 fn update_root_tick(
     mut q_root: Query<&mut GameRoot>,
     mut tick_event_reader: EventReader<TickEvent>,
     mut lock_event_writer: EventWriter<LockEvent>,
+    mut garbage_event_writer: EventWriter<GarbageAppliedEvent>,
     time: Res<Time<Fixed>>,
+    match_rules: Res<MatchRules>,
 ) {
     let cur_time = time.elapsed();
 
@@ -253,17 +293,35 @@ fn update_root_tick(
             continue;
         };
 
+        mutations
+            .iter()
+            .for_each(|m| active_game.stats.record_mutation(m));
+
+        // The game keeps its combo and back-to-back, but scores no attack.
+        let mut attack_state = active_game.game.attack_state().clone();
         // TODO: get game by game_id
         for tick_result in active_game.game.tick_mutation(mutations.clone()) {
             use manytris_core::consts;
             use TickResult::*;
             match tick_result {
                 Lock(lr) => {
+                    let play_time = active_game.play_time(cur_time);
+                    let attack =
+                        attack_state.record_lock(&match_rules.attack_table, &lr, play_time);
                     lock_event_writer.send(LockEvent {
                         game_id,
                         lock_result: lr.clone(),
+                        attack,
                     });
                     active_game.apply_lock_result(&lr);
+                    active_game.stats.record_lock(&lr);
+                    active_game.stats.record_attack(attack);
+                    if matches!(lr, LockResult::GameOver) {
+                        println!(
+                            "Final stats for {game_id:?}: {}",
+                            active_game.stats_summary(cur_time)
+                        );
+                    }
                 }
                 RestartLockTimer => {
                     active_game.lock_timer_target = Some(cur_time + consts::LOCK_TIMER_DURATION);
@@ -271,31 +329,45 @@ fn update_root_tick(
                 ClearLockTimer => {
                     active_game.lock_timer_target = None;
                 }
+                GarbageApplied(num_lines) => {
+                    garbage_event_writer.send(GarbageAppliedEvent { game_id, num_lines });
+                }
             }
         }
     }
 }
 
 impl ActiveGame {
-    fn new(start_time: Duration, initial_shapes: Vec<Shape>) -> Self {
-        Self::from_snapshot(GameState::new(initial_shapes), start_time)
+    fn new(start_time: Duration, initial_shapes: Vec<Shape>, start_level: i32) -> Self {
+        Self::from_snapshot(GameState::new(initial_shapes), start_time, start_level)
     }
 
-    fn from_snapshot(gs: GameState, start_time: Duration) -> Self {
+    fn from_snapshot(gs: GameState, start_time: Duration, start_level: i32) -> Self {
         Self {
             game: gs,
-            level: 1,
+            level: start_level,
             lines_cleared: 0,
             lines_to_next_level: LINES_PER_LEVEL,
-            next_drop_time: start_time + time_to_drop(1),
+            next_drop_time: start_time + time_to_drop(start_level),
             lock_timer_target: None,
+            stats: PlayerStats::default(),
+            start_time,
         }
     }
 
+    /// Time spent playing, not counting time paused.
+    pub fn play_time(&self, cur_time: Duration) -> Duration {
+        cur_time.saturating_sub(self.start_time)
+    }
+
+    pub fn stats_summary(&self, cur_time: Duration) -> StatsSummary {
+        self.stats.summary(self.play_time(cur_time))
+    }
+
     fn apply_lock_result(&mut self, lr: &LockResult) {
         match lr {
             LockResult::GameOver => println!("Game Over!!!"),
-            LockResult::Ok { lines_cleared } => {
+            LockResult::Ok { lines_cleared, .. } => {
                 self.lines_cleared += lines_cleared;
                 self.lines_to_next_level -= lines_cleared;
                 if self.lines_to_next_level <= 0 {
@@ -318,60 +390,50 @@ fn time_to_drop(mut level: i32) -> Duration {
 fn save_timer_state_on_pause(
     time: Res<Time<Fixed>>,
     q_root: Query<&GameRoot>,
-    local_game_root_res: Option<Res<LocalGameRoot>>,
+    local_games: LocalGames,
     mut pause_timer_state: ResMut<PauseTimerState>,
 ) {
-    let Some(local_game_root) = local_game_root_res else {
-        return;
-    };
-
-    let Some(game_root) = q_root
-        .iter()
-        .find(|gr| gr.game_id == local_game_root.game_id)
-    else {
-        return;
-    };
-
+    let local_ids = local_games.ids();
     let cur_time = time.elapsed();
-    let game = &game_root.active_game;
 
     // Store pause time and calculate remaining times
     pause_timer_state.pause_time = Some(cur_time);
-    pause_timer_state.remaining_drop_time = Some(game.next_drop_time.saturating_sub(cur_time));
-    pause_timer_state.remaining_lock_time = game
-        .lock_timer_target
-        .map(|target| target.saturating_sub(cur_time));
+    pause_timer_state.remaining_times = q_root
+        .iter()
+        .filter(|gr| local_ids.contains(&gr.game_id))
+        .map(|gr| {
+            let game = &gr.active_game;
+            let remaining = RemainingTimes {
+                drop_time: game.next_drop_time.saturating_sub(cur_time),
+                lock_time: game
+                    .lock_timer_target
+                    .map(|target| target.saturating_sub(cur_time)),
+            };
+            (gr.game_id, remaining)
+        })
+        .collect();
 }
 
 fn restore_timer_state_on_unpause(
     time: Res<Time<Fixed>>,
     mut q_root: Query<&mut GameRoot>,
-    local_game_root_res: Option<Res<LocalGameRoot>>,
     pause_timer_state: Res<PauseTimerState>,
 ) {
-    let Some(local_game_root) = local_game_root_res else {
-        return;
-    };
-
-    let Some(mut game_root) = q_root
-        .iter_mut()
-        .find(|gr| gr.game_id == local_game_root.game_id)
-    else {
-        return;
-    };
-
     let cur_time = time.elapsed();
-    let game = &mut game_root.active_game;
 
-    // Restore timers by adding remaining time to current time
-    if let Some(remaining) = pause_timer_state.remaining_drop_time {
-        game.next_drop_time = cur_time + remaining;
-    }
+    for mut game_root in q_root.iter_mut() {
+        let Some(remaining) = pause_timer_state.remaining_times.get(&game_root.game_id) else {
+            continue;
+        };
+        let game = &mut game_root.active_game;
+
+        // Don't count the paused time against the player's rates.
+        if let Some(pause_time) = pause_timer_state.pause_time {
+            game.start_time += cur_time.saturating_sub(pause_time);
+        }
 
-    if let Some(remaining) = pause_timer_state.remaining_lock_time {
-        game.lock_timer_target = Some(cur_time + remaining);
-    } else if pause_timer_state.pause_time.is_some() {
-        // If there was no lock timer when we paused, keep it None
-        game.lock_timer_target = None;
+        // Restore timers by adding remaining time to current time
+        game.next_drop_time = cur_time + remaining.drop_time;
+        game.lock_timer_target = remaining.lock_time.map(|lock_time| cur_time + lock_time);
     }
 }
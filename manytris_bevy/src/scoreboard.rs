@@ -31,7 +31,7 @@ fn add_scoreboard_to_root(
         let font = asset_server.load("fonts/white-rabbit.ttf");
 
         commands
-            .spawn((ScoreboardComponent, Text2d(get_score_text(0, 0))))
+            .spawn((ScoreboardComponent, Text2d(get_score_text(0, 0, 0., 0.))))
             .insert(TextFont {
                 font: font.clone(),
                 font_size: 15.,
@@ -51,16 +51,23 @@ fn add_scoreboard_to_root(
 fn update_scoreboard(
     q_root: Query<&GameRoot>,
     mut q_scoreboard: Query<(&mut Text, &Parent), With<ScoreboardComponent>>,
+    time: Res<Time<Fixed>>,
 ) {
     for (mut score_text, parent_entity) in q_scoreboard.iter_mut() {
         let game_root = q_root.get(parent_entity.get()).unwrap();
+        let stats = game_root.active_game.stats_summary(time.elapsed());
         score_text.0 = get_score_text(
             game_root.active_game.level,
             game_root.active_game.lines_cleared,
+            stats.pieces_per_second,
+            stats.attack_per_minute,
         );
     }
 }
 
-fn get_score_text(level: i32, lines_cleared: i32) -> String {
-    format!("Level: {}\n\nLines: {}", level, lines_cleared)
+fn get_score_text(level: i32, lines_cleared: i32, pps: f32, apm: f32) -> String {
+    format!(
+        "Level: {}\n\nLines: {}\n\nPPS: {:.2}\n\nAPM: {:.1}",
+        level, lines_cleared, pps, apm
+    )
 }
@@ -52,8 +52,9 @@ fn update(
 
     for event in reader.read() {
         if let LockEvent {
-            lock_result: LockResult::Ok { lines_cleared: _ },
+            lock_result: LockResult::Ok { .. },
             game_id,
+            ..
         } = event
         {
             writer.send(TickEvent::new_local(TickMutationMessage {
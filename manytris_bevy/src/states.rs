@@ -30,6 +30,10 @@ pub enum PlayingState {
 #[derive(Resource, Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ExecType {
     StandAlone,
+    /// Several players sharing one window, with the number of boards.
+    LocalVersus(usize),
+    /// One player against bots run in this process, with the number of bots.
+    VsCpu(usize),
     Server,
     MultiplayerClient(MultiplayerType),
 }
@@ -54,6 +58,29 @@ pub fn is_stand_alone(et: Res<ExecType>) -> bool {
     *et == ExecType::StandAlone
 }
 
+impl ExecType {
+    /// True if every game is played in this process, without a server.
+    pub fn is_offline(&self) -> bool {
+        matches!(
+            self,
+            ExecType::StandAlone | ExecType::LocalVersus(_) | ExecType::VsCpu(_)
+        )
+    }
+}
+
+/// True for games with several boards in one window.
+pub fn is_local_match(et: Res<ExecType>) -> bool {
+    matches!(*et, ExecType::LocalVersus(_) | ExecType::VsCpu(_))
+}
+
+pub fn is_vs_cpu(et: Res<ExecType>) -> bool {
+    matches!(*et, ExecType::VsCpu(_))
+}
+
+pub fn is_offline(et: Res<ExecType>) -> bool {
+    et.is_offline()
+}
+
 pub fn is_multiplayer_client(et: Res<ExecType>) -> bool {
     matches!(*et, ExecType::MultiplayerClient(_))
 }
@@ -63,13 +90,22 @@ pub fn is_server(et: Res<ExecType>) -> bool {
 }
 
 pub fn is_client(et: Res<ExecType>) -> bool {
-    matches!(*et, ExecType::StandAlone | ExecType::MultiplayerClient(_))
+    matches!(
+        *et,
+        ExecType::StandAlone
+            | ExecType::LocalVersus(_)
+            | ExecType::VsCpu(_)
+            | ExecType::MultiplayerClient(_)
+    )
 }
 
 pub fn is_human(et: Res<ExecType>) -> bool {
     matches!(
         *et,
-        ExecType::StandAlone | ExecType::MultiplayerClient(MultiplayerType::Human)
+        ExecType::StandAlone
+            | ExecType::LocalVersus(_)
+            | ExecType::VsCpu(_)
+            | ExecType::MultiplayerClient(MultiplayerType::Human)
     )
 }
 
@@ -78,7 +114,7 @@ pub fn is_bot(et: Res<ExecType>) -> bool {
 }
 
 pub fn produces_shapes(et: Res<ExecType>) -> bool {
-    matches!(*et, ExecType::StandAlone | ExecType::Server)
+    et.is_offline() || *et == ExecType::Server
 }
 
 pub fn headed(headless: Option<Res<Headless>>) -> bool {
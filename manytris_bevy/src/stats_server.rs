@@ -10,10 +10,17 @@ use axum::{response::IntoResponse, routing::get};
 use bevy::prelude::*;
 use bevy_defer::{AsyncAccess, AsyncWorld};
 use bevy_webserver::{BevyWebServerPlugin, RouterAppExt, WebServerConfig};
-use manytris_game_manager_proto::{StatsServerResponse, STATS_SERVER_PORT, STATS_SERVER_ROUTE};
+use manytris_game_manager_proto::{
+    PlayerStatsReport, StatsServerResponse, STATS_SERVER_PORT, STATS_SERVER_ROUTE,
+};
 
 use crate::game_container::GameContainer;
 use crate::net_listener::ServerListenerComponent;
+use crate::root::GameRoot;
+
+/// Latest stats of every game on the server, copied out for the stats route to read.
+#[derive(Component, Default)]
+struct PlayerStatsSnapshot(Vec<PlayerStatsReport>);
 
 struct AppError(anyhow::Error);
 
@@ -38,7 +45,39 @@ pub fn plugin(app: &mut App) {
             ip: IpAddr::V4(Ipv4Addr::from_str("0.0.0.0").unwrap()),
             port: STATS_SERVER_PORT,
         })
-        .route(STATS_SERVER_ROUTE, get(cur_players));
+        .route(STATS_SERVER_ROUTE, get(cur_players))
+        .add_systems(Startup, spawn_player_stats_snapshot)
+        .add_systems(Update, update_player_stats_snapshot);
+}
+
+fn spawn_player_stats_snapshot(mut commands: Commands) {
+    commands.spawn(PlayerStatsSnapshot::default());
+}
+
+fn update_player_stats_snapshot(
+    q_roots: Query<&GameRoot>,
+    mut q_snapshot: Query<&mut PlayerStatsSnapshot>,
+    time: Res<Time<Fixed>>,
+) {
+    q_snapshot.single_mut().0 = q_roots
+        .iter()
+        .map(|gr| {
+            let summary = gr.active_game.stats_summary(time.elapsed());
+            PlayerStatsReport {
+                game_id: gr.game_id.to_string(),
+                pieces_per_second: summary.pieces_per_second,
+                attack_per_minute: summary.attack_per_minute,
+                keys_per_piece: summary.keys_per_piece,
+                lines_per_minute: summary.lines_per_minute,
+                max_combo: summary.max_combo,
+                spins: summary
+                    .spins
+                    .iter()
+                    .map(|(spin, count)| (format!("{spin:?}"), *count))
+                    .collect(),
+            }
+        })
+        .collect();
 }
 
 async fn cur_players() -> Result<Json<StatsServerResponse>, AppError> {
@@ -55,9 +94,14 @@ async fn cur_players() -> Result<Json<StatsServerResponse>, AppError> {
         .query_single::<&mut GameContainer>()
         .get_mut(|gc| gc.get_num_active_games() as u16)?;
 
+    let player_stats = AsyncWorld
+        .query_single::<&mut PlayerStatsSnapshot>()
+        .get_mut(|snapshot| snapshot.0.clone())?;
+
     Ok(Json::from(StatsServerResponse {
         num_connected_players,
         num_active_games,
         connectionless_time_secs,
+        player_stats,
     }))
 }
@@ -0,0 +1,533 @@
+use crate::game_container::{GameContainer, LocalGameRoot};
+use crate::net_game_control_manager::{
+    ClientControlEvent, ConnectionTarget, ReceiveControlEventFromClient, SendControlEventToClient,
+    ServerControlEvent,
+};
+use crate::root::{GameId, GameRoot, GarbageAppliedEvent, LockEvent};
+use crate::states::{should_accept_game_input, PlayingState};
+use crate::system_sets::UpdateSystems;
+use crate::{assets, states};
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy::text::FontSmoothing;
+use manytris_core::field_metrics::FieldMetrics;
+use manytris_core::game_state::GameState;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How a player's attacks are split between their opponents.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum TargetingStrategy {
+    /// One random opponent, picked again after each attack.
+    #[default]
+    Random,
+    /// Every opponent, sharing the lines of each attack between them.
+    Even,
+    /// The opponents currently targeting this player, or random if there are none.
+    Attackers,
+    /// The opponent closest to topping out.
+    KOs,
+    /// One chosen opponent, or random once they're gone.
+    Manual(GameId),
+}
+
+/// Server side targeting state of every game.
+#[derive(Resource, Default)]
+pub struct Targeting {
+    strategies: BTreeMap<GameId, TargetingStrategy>,
+    targets: BTreeMap<GameId, Vec<GameId>>,
+    random_picks: BTreeMap<GameId, GameId>,
+    split_cursors: BTreeMap<GameId, usize>,
+}
+
+/// Client side copy of the server's targets, for display.
+#[derive(Resource, Default)]
+pub struct TargetingView {
+    targets: BTreeMap<GameId, Vec<GameId>>,
+    strategy: TargetingStrategy,
+}
+
+#[derive(Component)]
+#[require(Text2d)]
+struct TargetingLabel;
+
+pub fn plugin(app: &mut App) {
+    app.init_resource::<Targeting>()
+        .init_resource::<TargetingView>()
+        .add_systems(OnEnter(PlayingState::Playing), reset_targeting_view)
+        .add_systems(
+            Update,
+            (
+                (accept_targeting_requests, update_targets).run_if(states::is_server),
+                accept_targeting_updates.run_if(states::is_multiplayer_client),
+                select_strategy
+                    .in_set(UpdateSystems::Input)
+                    .run_if(states::is_multiplayer_client)
+                    .run_if(states::is_human)
+                    .run_if(should_accept_game_input),
+                (
+                    add_targeting_label_to_root.in_set(UpdateSystems::PreRender),
+                    update_targeting_labels.in_set(UpdateSystems::Render),
+                )
+                    .run_if(states::is_multiplayer_client)
+                    .run_if(states::headed),
+            )
+                .run_if(in_state(PlayingState::Playing)),
+        );
+}
+
+impl Targeting {
+    /// Pick the games each game's next attack will go to. Returns true if any targets changed.
+    ///
+    /// `dangers` has every live game, scored by how close it is to topping out. Games are never
+    /// targeted by their teammates.
+    pub fn update(
+        &mut self,
+        dangers: &BTreeMap<GameId, usize>,
+        are_teammates: impl Fn(&GameId, &GameId) -> bool,
+        rng: &mut impl Rng,
+    ) -> bool {
+        self.strategies.retain(|g, _| dangers.contains_key(g));
+        self.random_picks
+            .retain(|g, pick| dangers.contains_key(g) && dangers.contains_key(pick));
+        self.split_cursors.retain(|g, _| dangers.contains_key(g));
+
+        let mut targets = BTreeMap::new();
+        for &game in dangers.keys() {
+            let opponents: Vec<GameId> = dangers
+                .keys()
+                .filter(|g| !are_teammates(&game, g))
+                .copied()
+                .collect();
+            if opponents.is_empty() {
+                targets.insert(game, vec![]);
+                continue;
+            }
+
+            use TargetingStrategy::*;
+            let game_targets = match self.strategies.get(&game).copied().unwrap_or_default() {
+                Even => opponents,
+                KOs => vec![*opponents.iter().max_by_key(|g| dangers[g]).unwrap()],
+                Manual(target) if opponents.contains(&target) => vec![target],
+                Random | Attackers | Manual(_) => vec![*self
+                    .random_picks
+                    .entry(game)
+                    .or_insert_with(|| *opponents.choose(rng).unwrap())],
+            };
+            targets.insert(game, game_targets);
+        }
+
+        // Attackers are resolved from everyone's first pass targets, so that two players
+        // targeting attackers don't depend on each other.
+        for (game, strategy) in &self.strategies {
+            if *strategy != TargetingStrategy::Attackers {
+                continue;
+            }
+            let attackers: Vec<GameId> = targets
+                .iter()
+                .filter(|(g, ts)| *g != game && ts.contains(game))
+                .map(|(g, _)| *g)
+                .collect();
+            if !attackers.is_empty() {
+                targets.insert(*game, attackers);
+            }
+        }
+
+        let changed = targets != self.targets;
+        self.targets = targets;
+        changed
+    }
+
+    /// Split an attack between the game's current targets.
+    ///
+    /// Lines are dealt out one at a time, continuing from where the game's last attack left off.
+    pub fn route_attack(&mut self, from: GameId, num_lines: usize) -> Vec<(GameId, usize)> {
+        let targets = self.targets.get(&from).cloned().unwrap_or_default();
+        if targets.is_empty() {
+            return vec![];
+        }
+        // Random targeting picks someone new for every attack.
+        self.random_picks.remove(&from);
+
+        let cursor = self.split_cursors.entry(from).or_default();
+        let mut lines = vec![0; targets.len()];
+        for _ in 0..num_lines {
+            lines[*cursor % targets.len()] += 1;
+            *cursor += 1;
+        }
+        targets
+            .into_iter()
+            .zip(lines)
+            .filter(|(_, n)| *n > 0)
+            .collect()
+    }
+}
+
+impl TargetingView {
+    /// The opponent after the current manual target, cycling back to the first. Teammates are
+    /// skipped, as the server never targets them.
+    fn next_manual_target(
+        &self,
+        local_id: GameId,
+        are_teammates: impl Fn(&GameId, &GameId) -> bool,
+    ) -> Option<GameId> {
+        let opponents: Vec<GameId> = self
+            .targets
+            .keys()
+            .filter(|g| !are_teammates(&local_id, g))
+            .copied()
+            .collect();
+        let next_idx = match self.strategy {
+            TargetingStrategy::Manual(cur) => opponents
+                .iter()
+                .position(|g| *g == cur)
+                .map_or(0, |i| i + 1),
+            _ => 0,
+        };
+        opponents.get(next_idx).or(opponents.first()).copied()
+    }
+}
+
+/// How close the game is to topping out, counting the garbage about to rise.
+pub fn danger(game: &GameState) -> usize {
+    let metrics = FieldMetrics::compute(&game.make_bitmap_field());
+    metrics.max_height as usize + game.pending_garbage()
+}
+
+fn accept_targeting_requests(
+    mut control_event_reader: EventReader<ReceiveControlEventFromClient>,
+    q_container: Query<&GameContainer>,
+    mut targeting: ResMut<Targeting>,
+) {
+    let container = q_container.single();
+    for rce in control_event_reader.read() {
+        if let ClientControlEvent::SetTargetingStrategy(strategy) = rce.event {
+            for game_id in container.games_for_connection(&rce.from_connection) {
+                targeting.strategies.insert(game_id, strategy);
+            }
+        }
+    }
+}
+
+/// Dangers are only measured again for games whose field has changed, as measuring them means
+/// walking the whole field.
+fn update_targets(
+    q_roots: Query<&GameRoot>,
+    q_container: Query<&GameContainer>,
+    mut lock_events: EventReader<LockEvent>,
+    mut garbage_events: EventReader<GarbageAppliedEvent>,
+    mut dangers: Local<BTreeMap<GameId, usize>>,
+    mut targeting: ResMut<Targeting>,
+    mut control_event_writer: EventWriter<SendControlEventToClient>,
+) {
+    let mut changed: BTreeSet<GameId> = lock_events.read().map(|e| e.game_id).collect();
+    changed.extend(garbage_events.read().map(|e| e.game_id));
+
+    dangers.retain(|g, _| q_roots.iter().any(|gr| gr.game_id == *g));
+    for gr in &q_roots {
+        if changed.contains(&gr.game_id) || !dangers.contains_key(&gr.game_id) {
+            dangers.insert(gr.game_id, danger(&gr.active_game.game));
+        }
+    }
+
+    let container = q_container.single();
+    let are_teammates = |a: &GameId, b: &GameId| container.are_teammates(a, b);
+    if targeting.update(&dangers, are_teammates, &mut rand::thread_rng()) {
+        control_event_writer.send(SendControlEventToClient {
+            event: ServerControlEvent::TargetingUpdate(targeting.targets.clone()),
+            to_connection: ConnectionTarget::AllExcept(None),
+        });
+    }
+}
+
+fn reset_targeting_view(mut view: ResMut<TargetingView>) {
+    *view = TargetingView::default();
+}
+
+fn accept_targeting_updates(
+    mut events: EventReader<ServerControlEvent>,
+    mut view: ResMut<TargetingView>,
+) {
+    for event in events.read() {
+        if let ServerControlEvent::TargetingUpdate(targets) = event {
+            view.targets = targets.clone();
+        }
+    }
+}
+
+fn select_strategy(
+    keys: Res<ButtonInput<KeyCode>>,
+    local_game_root_res: Option<Res<LocalGameRoot>>,
+    q_container: Query<&GameContainer>,
+    mut view: ResMut<TargetingView>,
+    mut control_events: EventWriter<ClientControlEvent>,
+) {
+    let Some(local_game_root) = local_game_root_res else {
+        return;
+    };
+    let local_id = local_game_root.game_id;
+
+    use TargetingStrategy::*;
+    let strategy = if keys.just_pressed(KeyCode::Digit1) {
+        Random
+    } else if keys.just_pressed(KeyCode::Digit2) {
+        Even
+    } else if keys.just_pressed(KeyCode::Digit3) {
+        Attackers
+    } else if keys.just_pressed(KeyCode::Digit4) {
+        KOs
+    } else if keys.just_pressed(KeyCode::Digit5) {
+        let container = q_container.single();
+        let are_teammates = |a: &GameId, b: &GameId| container.are_teammates(a, b);
+        let Some(target) = view.next_manual_target(local_id, are_teammates) else {
+            return;
+        };
+        Manual(target)
+    } else {
+        return;
+    };
+
+    view.strategy = strategy;
+    control_events.send(ClientControlEvent::SetTargetingStrategy(strategy));
+}
+
+fn add_targeting_label_to_root(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    root_q: Query<(Entity, &GameRoot), Added<GameRoot>>,
+    local_game_root_res: Option<Res<LocalGameRoot>>,
+) {
+    let local_id = local_game_root_res.map(|lgr| lgr.game_id);
+    for (root_entity, game_root) in &root_q {
+        // Opponent boards are drawn scaled down, so make their labels big enough to read.
+        let font_size = if Some(game_root.game_id) == local_id {
+            15.
+        } else {
+            60.
+        };
+
+        commands
+            .spawn((
+                TargetingLabel,
+                TextFont {
+                    font: asset_server.load("fonts/white-rabbit.ttf"),
+                    font_size,
+                    font_smoothing: FontSmoothing::None,
+                },
+                TextColor(Color::hsl(0., 1., 0.7)),
+                Transform::from_xyz(0., assets::BLOCK_SIZE * 22.5, 0.),
+                Anchor::BottomLeft,
+            ))
+            .set_parent(root_entity);
+    }
+}
+
+fn update_targeting_labels(
+    q_root: Query<&GameRoot>,
+    mut q_label: Query<(&mut Text2d, &Parent), With<TargetingLabel>>,
+    local_game_root_res: Option<Res<LocalGameRoot>>,
+    view: Res<TargetingView>,
+) {
+    let Some(local_game_root) = local_game_root_res else {
+        return;
+    };
+    let local_id = local_game_root.game_id;
+    let targeted_by_local = view.targets.get(&local_id).cloned().unwrap_or_default();
+
+    for (mut label, parent) in q_label.iter_mut() {
+        let Ok(game_root) = q_root.get(parent.get()) else {
+            continue;
+        };
+        let game_id = game_root.game_id;
+
+        label.0 = if game_id == local_id {
+            let num_attackers = view
+                .targets
+                .values()
+                .filter(|ts| ts.contains(&local_id))
+                .count();
+            let strategy = match view.strategy {
+                TargetingStrategy::Manual(_) => "Manual".to_string(),
+                s => format!("{s:?}"),
+            };
+            format!("Targeting: {strategy}  Attackers: {num_attackers}")
+        } else {
+            let targeted = targeted_by_local.contains(&game_id);
+            let attacking = view
+                .targets
+                .get(&game_id)
+                .is_some_and(|ts| ts.contains(&local_id));
+            match (targeted, attacking) {
+                (true, true) => "TARGET <> ATTACKER",
+                (true, false) => "TARGET",
+                (false, true) => "ATTACKER",
+                (false, false) => "",
+            }
+            .to_string()
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn new_games(n: usize) -> Vec<GameId> {
+        (0..n).map(|_| GameId::new()).collect()
+    }
+
+    /// Every game live, in order of danger.
+    fn live(games: &[GameId]) -> BTreeMap<GameId, usize> {
+        games.iter().enumerate().map(|(i, g)| (*g, i)).collect()
+    }
+
+    fn no_teams(a: &GameId, b: &GameId) -> bool {
+        a == b
+    }
+
+    #[test]
+    fn random_targets_a_live_opponent() {
+        let mut rng = StdRng::seed_from_u64(30);
+        let games = new_games(3);
+        let mut targeting = Targeting::default();
+        targeting.update(&live(&games), no_teams, &mut rng);
+        let target = targeting.targets[&games[0]][0];
+        assert_ne!(target, games[0]);
+
+        // The pick sticks until an attack is sent.
+        targeting.update(&live(&games), no_teams, &mut rng);
+        assert_eq!(targeting.targets[&games[0]], vec![target]);
+
+        // Once the target is eliminated, someone else is picked.
+        let remaining: Vec<_> = games.iter().copied().filter(|g| *g != target).collect();
+        targeting.update(&live(&remaining), no_teams, &mut rng);
+        let other = remaining.iter().find(|g| **g != games[0]).unwrap();
+        assert_eq!(targeting.targets[&games[0]], vec![*other]);
+        assert_eq!(targeting.route_attack(games[0], 3), vec![(*other, 3)]);
+    }
+
+    #[test]
+    fn attackers_targets_the_games_targeting_it() {
+        let mut rng = StdRng::seed_from_u64(30);
+        let games = new_games(4);
+        let mut targeting = Targeting::default();
+        use TargetingStrategy::*;
+        targeting.strategies.insert(games[0], Attackers);
+        targeting.strategies.insert(games[1], Manual(games[0]));
+        targeting.strategies.insert(games[2], Manual(games[0]));
+        targeting.strategies.insert(games[3], Manual(games[1]));
+        targeting.update(&live(&games), no_teams, &mut rng);
+        let mut attackers = vec![games[1], games[2]];
+        attackers.sort();
+        assert_eq!(targeting.targets[&games[0]], attackers);
+
+        // With the attackers eliminated, it falls back to a random opponent.
+        targeting.update(&live(&[games[0], games[3]]), no_teams, &mut rng);
+        assert_eq!(targeting.targets[&games[0]], vec![games[3]]);
+    }
+
+    #[test]
+    fn manual_falls_back_once_the_target_is_gone() {
+        let mut rng = StdRng::seed_from_u64(30);
+        let games = new_games(3);
+        let mut targeting = Targeting::default();
+        targeting
+            .strategies
+            .insert(games[0], TargetingStrategy::Manual(games[2]));
+        targeting.update(&live(&games), no_teams, &mut rng);
+        assert_eq!(targeting.targets[&games[0]], vec![games[2]]);
+
+        targeting.update(&live(&games[0..2]), no_teams, &mut rng);
+        assert_eq!(targeting.targets[&games[0]], vec![games[1]]);
+    }
+
+    #[test]
+    fn teammates_are_never_targeted() {
+        let mut rng = StdRng::seed_from_u64(30);
+        let games = new_games(4);
+        // The first two games against the last two.
+        let team = |g: &GameId| games.iter().position(|o| o == g).unwrap() / 2;
+        let are_teammates = |a: &GameId, b: &GameId| team(a) == team(b);
+
+        use TargetingStrategy::*;
+        for strategy in [Random, Even, Attackers, KOs, Manual(games[1])] {
+            let mut targeting = Targeting::default();
+            for game in &games {
+                targeting.strategies.insert(*game, strategy);
+            }
+            targeting.update(&live(&games), are_teammates, &mut rng);
+            for (game, targets) in &targeting.targets {
+                assert!(!targets.is_empty(), "{strategy:?}");
+                assert!(
+                    targets.iter().all(|t| !are_teammates(game, t)),
+                    "{strategy:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_is_sent_without_opponents() {
+        let mut rng = StdRng::seed_from_u64(30);
+        let games = new_games(2);
+        let mut targeting = Targeting::default();
+        targeting.update(&live(&games), |_, _| true, &mut rng);
+        assert_eq!(targeting.targets[&games[0]], vec![]);
+        assert_eq!(targeting.route_attack(games[0], 4), vec![]);
+
+        // Including once everyone else is eliminated.
+        targeting.update(&live(&games[0..1]), no_teams, &mut rng);
+        assert_eq!(targeting.route_attack(games[0], 4), vec![]);
+    }
+
+    #[test]
+    fn manual_cycle_skips_teammates() {
+        let mut games = new_games(4);
+        games.sort();
+        // The local game is teamed with the third.
+        let team = |g: &GameId| [0, 1, 0, 2][games.iter().position(|o| o == g).unwrap()];
+        let are_teammates = |a: &GameId, b: &GameId| team(a) == team(b);
+        let mut view = TargetingView {
+            targets: live(&games).keys().map(|g| (*g, vec![])).collect(),
+            strategy: TargetingStrategy::Random,
+        };
+
+        let mut cycle = vec![];
+        for _ in 0..3 {
+            let target = view.next_manual_target(games[0], are_teammates).unwrap();
+            cycle.push(target);
+            view.strategy = TargetingStrategy::Manual(target);
+        }
+        assert_eq!(cycle, vec![games[1], games[3], games[1]]);
+
+        // Nothing to pick with only teammates left.
+        view.targets.retain(|g, _| team(g) == 0);
+        assert_eq!(view.next_manual_target(games[0], are_teammates), None);
+    }
+
+    #[test]
+    fn even_split_continues_between_attacks() {
+        let mut rng = StdRng::seed_from_u64(30);
+        let games = new_games(4);
+        let mut targeting = Targeting::default();
+        targeting
+            .strategies
+            .insert(games[0], TargetingStrategy::Even);
+        targeting.update(&live(&games), no_teams, &mut rng);
+        let opponents = targeting.targets[&games[0]].clone();
+        assert_eq!(opponents.len(), 3);
+
+        assert_eq!(
+            targeting.route_attack(games[0], 4),
+            vec![(opponents[0], 2), (opponents[1], 1), (opponents[2], 1)]
+        );
+        assert_eq!(
+            targeting.route_attack(games[0], 2),
+            vec![(opponents[1], 1), (opponents[2], 1)]
+        );
+    }
+}
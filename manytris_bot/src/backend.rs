@@ -0,0 +1,200 @@
+use std::time::Instant;
+
+use anyhow::Result;
+use clap::{Args, ValueEnum};
+use manytris_core::attack::AttackTable;
+use manytris_core::game_state::GameState;
+use serde::Serialize;
+
+use crate::bot_cpu::{BeamSearchContext, CpuBotContext, CpuBotResults};
+use crate::compute_types::UpcomingShapes;
+use crate::features::{Feature, WeightProfile};
+use crate::BotContext;
+
+/// Which implementation runs the bot's move search.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum BotBackend {
+    /// The fastest exhaustive search compiled into this build.
+    #[default]
+    Auto,
+    Cpu,
+    Vulkan,
+    Metal,
+    /// A CPU search which only follows the best positions of each level, to the search depth.
+    Beam,
+}
+
+/// How a bot searches for its moves and ranks them.
+#[derive(Args, Clone, Debug, Serialize)]
+pub struct BotSearchArgs {
+    /// What runs the bot's move search. Falls back to the CPU if a GPU backend fails to start.
+    #[arg(long, value_enum, default_value_t)]
+    pub bot_backend: BotBackend,
+
+    #[command(flatten)]
+    pub cpu: CpuSearchOptions,
+
+    /// Feature weights for ranking the bot's moves: "survival", "versus", or the path of a JSON
+    /// map from feature names to weights.
+    #[arg(long, default_value = "versus")]
+    pub bot_weights: String,
+}
+
+/// Settings of the CPU backends.
+#[derive(Args, Clone, Debug, Serialize)]
+pub struct CpuSearchOptions {
+    /// Positions kept at each level of the beam backend's search.
+    #[arg(long, default_value = "64")]
+    pub beam_width: usize,
+
+    /// Let the beam backend soft drop, tuck and spin pieces into place, not just hard drop them.
+    #[arg(long)]
+    pub bot_tucks: bool,
+}
+
+/// One of the CPU searches, picked at runtime.
+pub enum CpuSearchContext {
+    Exhaustive(CpuBotContext),
+    Beam(BeamSearchContext),
+}
+
+impl Default for BotSearchArgs {
+    fn default() -> Self {
+        Self {
+            bot_backend: BotBackend::default(),
+            cpu: CpuSearchOptions::default(),
+            bot_weights: "versus".into(),
+        }
+    }
+}
+
+impl Default for CpuSearchOptions {
+    fn default() -> Self {
+        Self {
+            beam_width: 64,
+            bot_tucks: false,
+        }
+    }
+}
+
+impl BotBackend {
+    /// Backends to try starting, in order, given the backends in the build with the exhaustive
+    /// searches fastest first. The CPU backends can't fail to start, so the exhaustive CPU search
+    /// is the last resort.
+    pub fn candidates(&self, available: &[BotBackend]) -> Vec<BotBackend> {
+        match self {
+            BotBackend::Auto => available
+                .iter()
+                .copied()
+                .filter(|b| *b != BotBackend::Beam)
+                .collect(),
+            BotBackend::Cpu | BotBackend::Beam => vec![*self],
+            gpu => vec![*gpu, BotBackend::Cpu],
+        }
+    }
+}
+
+impl CpuSearchContext {
+    /// The search run by a CPU backend, or None for the GPU backends. Auto is the exhaustive
+    /// search.
+    pub fn new(
+        backend: BotBackend,
+        options: &CpuSearchOptions,
+        attack_table: AttackTable,
+    ) -> Option<Self> {
+        match backend {
+            BotBackend::Auto | BotBackend::Cpu => {
+                Some(Self::Exhaustive(CpuBotContext { attack_table }))
+            }
+            BotBackend::Beam => Some(Self::Beam(BeamSearchContext {
+                beam_width: options.beam_width,
+                tucks: options.bot_tucks,
+                attack_table,
+            })),
+            BotBackend::Vulkan | BotBackend::Metal => None,
+        }
+    }
+}
+
+impl BotContext for CpuSearchContext {
+    type ResultType = CpuBotResults;
+
+    fn compute_drop_search(
+        &self,
+        search_depth: usize,
+        upcoming_shapes: &UpcomingShapes,
+        source_state: &GameState,
+        weights: &WeightProfile,
+    ) -> Result<CpuBotResults> {
+        match self {
+            Self::Exhaustive(ctx) => {
+                ctx.compute_drop_search(search_depth, upcoming_shapes, source_state, weights)
+            }
+            Self::Beam(ctx) => {
+                ctx.compute_drop_search(search_depth, upcoming_shapes, source_state, weights)
+            }
+        }
+    }
+
+    fn compute_drop_search_until(
+        &self,
+        search_depth: usize,
+        upcoming_shapes: &UpcomingShapes,
+        source_state: &GameState,
+        weights: &WeightProfile,
+        deadline: Instant,
+    ) -> Result<Option<CpuBotResults>> {
+        match self {
+            Self::Exhaustive(ctx) => ctx.compute_drop_search_until(
+                search_depth,
+                upcoming_shapes,
+                source_state,
+                weights,
+                deadline,
+            ),
+            Self::Beam(ctx) => ctx.compute_drop_search_until(
+                search_depth,
+                upcoming_shapes,
+                source_state,
+                weights,
+                deadline,
+            ),
+        }
+    }
+
+    fn supported_features(&self) -> Vec<Feature> {
+        match self {
+            Self::Exhaustive(ctx) => ctx.supported_features(),
+            Self::Beam(ctx) => ctx.supported_features(),
+        }
+    }
+
+    fn searches_hold(&self) -> bool {
+        match self {
+            Self::Exhaustive(ctx) => ctx.searches_hold(),
+            Self::Beam(ctx) => ctx.searches_hold(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_the_cpu() {
+        let available = [BotBackend::Vulkan, BotBackend::Cpu, BotBackend::Beam];
+        assert_eq!(
+            BotBackend::Auto.candidates(&available),
+            vec![BotBackend::Vulkan, BotBackend::Cpu]
+        );
+        assert_eq!(
+            BotBackend::Metal.candidates(&available),
+            vec![BotBackend::Metal, BotBackend::Cpu]
+        );
+        assert_eq!(
+            BotBackend::Beam.candidates(&available),
+            vec![BotBackend::Beam]
+        );
+    }
+}
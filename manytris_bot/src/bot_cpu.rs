@@ -1,22 +1,43 @@
-use std::cmp::max;
+use std::cmp::{max, Reverse};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use std::{iter, thread};
 
 use anyhow::Result;
-use manytris_core::{bitmap_field::BitmapField, game_state::GameState, shapes::Shape};
+use manytris_core::{
+    attack::{AttackState, AttackTable},
+    bitmap_field::{BitmapField, PieceMask},
+    consts,
+    game_state::{GameState, LockResult, Spin},
+    shapes::Shape,
+    tetromino::Tetromino,
+};
+use ordered_float::OrderedFloat;
 
 use crate::{
-    bot_player::MovementDescriptor,
+    bot_player::{weighted_result_score, MovementDescriptor},
     bot_start_positions::START_POSITIONS,
     compute_types::{ComputedDropConfig, MoveResultScore, UpcomingShapes},
-    evaluate_moves_cpu, BotContext, BotResults,
+    features::{Feature, WeightProfile},
+    placements::{reachable_placements, Placement, TuckInput},
+    score_field_cpu, BotContext, BotResults,
 };
 
-pub struct CpuBotContext;
+#[derive(Clone, Default)]
+pub struct CpuBotContext {
+    /// Rules for scoring the attack each move sends.
+    pub attack_table: AttackTable,
+}
 
 #[derive(Default)]
 pub struct CpuBotResults {
     configs: Vec<ComputedDropConfig>,
     scores: Vec<MoveResultScore>,
     fields: Vec<BitmapField>,
+    /// The move of each config, when it tucks. Empty if every config is a plain drop.
+    movements: Vec<MovementDescriptor>,
+    /// Whether each config holds before moving.
+    holds: Vec<bool>,
 }
 
 impl BotResults for CpuBotResults {
@@ -29,6 +50,27 @@ impl BotResults for CpuBotResults {
     fn fields(&self) -> &[BitmapField] {
         &self.fields
     }
+    fn movement(&self, config_idx: usize) -> Option<MovementDescriptor> {
+        let hold = self.holds.get(config_idx) == Some(&true);
+        match self.movements.get(config_idx) {
+            Some(movement) => Some(MovementDescriptor {
+                hold,
+                ..movement.clone()
+            }),
+            None => hold.then(|| MovementDescriptor {
+                hold,
+                ..MovementDescriptor::from_drop_config(&self.configs[config_idx])
+            }),
+        }
+    }
+}
+
+impl CpuBotResults {
+    /// The move of the config, however it's stored.
+    fn movement_of(&self, config_idx: usize) -> MovementDescriptor {
+        self.movement(config_idx)
+            .unwrap_or_else(|| MovementDescriptor::from_drop_config(&self.configs[config_idx]))
+    }
 }
 
 impl BotContext for CpuBotContext {
@@ -39,77 +81,877 @@ impl BotContext for CpuBotContext {
         search_depth: usize,
         upcoming_shapes: &UpcomingShapes,
         source_state: &GameState,
+        _weights: &WeightProfile,
     ) -> Result<CpuBotResults> {
-        let configs = make_drop_configs_cpu(&upcoming_shapes[0..search_depth]);
+        Ok(self
+            .search(search_depth, upcoming_shapes, source_state, None)
+            .unwrap())
+    }
+
+    fn compute_drop_search_until(
+        &self,
+        search_depth: usize,
+        upcoming_shapes: &UpcomingShapes,
+        source_state: &GameState,
+        _weights: &WeightProfile,
+        deadline: Instant,
+    ) -> Result<Option<CpuBotResults>> {
+        Ok(self.search(search_depth, upcoming_shapes, source_state, Some(deadline)))
+    }
+
+    fn supported_features(&self) -> Vec<Feature> {
+        enum_iterator::all().collect()
+    }
+
+    fn searches_hold(&self) -> bool {
+        true
+    }
+}
+
+impl CpuBotContext {
+    /// The exhaustive search, or None if the deadline passed before it finished.
+    fn search(
+        &self,
+        search_depth: usize,
+        upcoming_shapes: &UpcomingShapes,
+        source_state: &GameState,
+        deadline: Option<Instant>,
+    ) -> Option<CpuBotResults> {
+        let root = Hand::root(source_state);
+        let (configs, holds) = make_drop_configs_cpu(root, upcoming_shapes, search_depth);
 
-        let (fields, scores) = eval_configs(source_state, configs.as_slice());
+        let (fields, scores) =
+            eval_configs(source_state, &configs, &holds, &self.attack_table, deadline)?;
 
-        Ok(CpuBotResults {
+        Some(CpuBotResults {
             configs,
             fields,
             scores,
+            holds,
+            ..Default::default()
         })
     }
 }
 
-fn make_drop_configs_cpu(shapes: &[Shape]) -> Vec<ComputedDropConfig> {
+/// Every drop `search_depth` deep from the hand, holding first where that changes the shape
+/// dropped, with whether each config holds. Only the shapes given are drawn from the queue.
+fn make_drop_configs_cpu(
+    root: Hand,
+    shapes: &[Shape],
+    search_depth: usize,
+) -> (Vec<ComputedDropConfig>, Vec<bool>) {
     let mut res = vec![];
-    let mut prev_gen_range = 0..1;
-
-    for shape in shapes {
-        let cur_start = res.len() as u32 + 1;
-        for src_field_idx in prev_gen_range.clone() {
-            for cw_rotations in 0..4 {
-                for shifts in 0..10 {
-                    let left_shifts = max(4 - shifts, 0) as u8;
-                    let right_shifts = max(shifts - 4, 0) as u8;
-                    let dest_field_idx = (res.len() + 1) as u32;
-                    res.push(ComputedDropConfig {
-                        shape_idx: START_POSITIONS.shape_to_idx[*shape],
-                        cw_rotations,
-                        left_shifts,
-                        right_shifts,
-                        src_field_idx,
-                        dest_field_idx,
-                    });
+    let mut holds = vec![];
+    let mut prev_gen = vec![(0, root)];
+
+    for depth in 0..search_depth {
+        let mut cur_gen = vec![];
+        for (src_field_idx, hand) in prev_gen {
+            for (hold, shape, next_hand) in hand.plays(shapes, search_depth - depth) {
+                let cur_start = res.len();
+                push_drop_configs(&mut res, shape, src_field_idx);
+                holds.resize(res.len(), hold);
+                if let Some(next_hand) = next_hand {
+                    cur_gen.extend(
+                        res[cur_start..]
+                            .iter()
+                            .map(|c| (c.dest_field_idx, next_hand)),
+                    );
                 }
             }
         }
-        prev_gen_range = cur_start..(res.len() as u32 + 1);
+        prev_gen = cur_gen;
+    }
+    (res, holds)
+}
+
+/// Every placement of the shape onto the source field, numbered on from the configs so far.
+fn push_drop_configs(res: &mut Vec<ComputedDropConfig>, shape: Shape, src_field_idx: u32) {
+    for cw_rotations in 0..4 {
+        for shifts in 0..10 {
+            let left_shifts = max(4 - shifts, 0) as u8;
+            let right_shifts = max(shifts - 4, 0) as u8;
+            let dest_field_idx = (res.len() + 1) as u32;
+            res.push(ComputedDropConfig {
+                shape_idx: START_POSITIONS.shape_to_idx[shape],
+                cw_rotations,
+                left_shifts,
+                right_shifts,
+                src_field_idx,
+                dest_field_idx,
+            });
+        }
+    }
+}
+
+/// The tetrominoes in play at a point in a search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Hand {
+    active: Shape,
+    held: Option<Shape>,
+    can_hold: bool,
+    /// Index of the next shape to come, counting the root's active shape as 0.
+    next: usize,
+}
+
+impl Hand {
+    fn root(state: &GameState) -> Self {
+        Self {
+            active: state.active_shape(),
+            held: state.held_tetromino().map(|t| t.shape),
+            can_hold: state.can_hold(),
+            next: 1,
+        }
+    }
+
+    /// The hand after holding, like `GameState`, or None if it can't hold.
+    fn hold(&self, shapes: &[Shape]) -> Option<Self> {
+        if !self.can_hold {
+            return None;
+        }
+        let (active, next) = match self.held {
+            Some(held) => (held, self.next),
+            None => (*shapes.get(self.next)?, self.next + 1),
+        };
+        Some(Self {
+            active,
+            held: Some(self.active),
+            can_hold: false,
+            next,
+        })
+    }
+
+    /// The hand after the active tetromino locks, or None if the next shape isn't known.
+    fn lock(&self, shapes: &[Shape]) -> Option<Self> {
+        Some(Self {
+            active: *shapes.get(self.next)?,
+            held: self.held,
+            can_hold: true,
+            next: self.next + 1,
+        })
+    }
+
+    /// The shapes which can be dropped next, with whether they're held for, and the hand after
+    /// dropping them. Holding is skipped when it would drop the same shape, or would leave too
+    /// few of the shapes for `drops_left` drops.
+    fn plays(&self, shapes: &[Shape], drops_left: usize) -> Vec<(bool, Shape, Option<Self>)> {
+        let mut plays = vec![(false, self.active, self.lock(shapes))];
+        let held = self.hold(shapes).filter(|h| {
+            h.active != self.active && h.next + drops_left.saturating_sub(1) <= shapes.len()
+        });
+        if let Some(held) = held {
+            plays.push((true, held.active, held.lock(shapes)));
+        }
+        plays
     }
-    res
 }
 
+/// What a search knows of the game beyond the field, which is the same for every node.
+struct SearchRules<'a> {
+    /// The active shape, then the queue.
+    shapes: Vec<Shape>,
+    /// Lines of queued garbage which rise after each drop.
+    garbage_rising: Vec<usize>,
+    attack_table: &'a AttackTable,
+}
+
+/// A position reached by a chain of drops, and what the chain did along the way.
+///
+/// Nodes only keep the field, and play moves with row masks rather than through a `GameState`,
+/// following the same rules.
+#[derive(Clone)]
+struct SearchNode<'a> {
+    rules: &'a SearchRules<'a>,
+    field: BitmapField,
+    hand: Hand,
+    /// Drops made since the root.
+    drops: usize,
+    game_over: bool,
+    lines_cleared: u8,
+    attack: u8,
+    attack_state: AttackState,
+}
+
+/// Evaluate every config, each from its parent's cached position rather than replaying its whole
+/// chain from the root. The subtrees under each first drop are spread over the available cores.
+/// None if the deadline passed first.
 fn eval_configs(
     initial_state: &GameState,
     configs: &[ComputedDropConfig],
-) -> (Vec<BitmapField>, Vec<MoveResultScore>) {
+    holds: &[bool],
+    attack_table: &AttackTable,
+    deadline: Option<Instant>,
+) -> Option<(Vec<BitmapField>, Vec<MoveResultScore>)> {
+    // Indexes of the configs dropping onto each field.
+    let mut children = vec![vec![]; configs.len() + 1];
+    for (config_idx, config) in configs.iter().enumerate() {
+        debug_assert_eq!(config.dest_field_idx as usize, config_idx + 1);
+        children[config.src_field_idx as usize].push(config_idx);
+    }
+
+    let rules = SearchRules::new(initial_state, attack_table);
+    let root = SearchNode::root(initial_state, &rules);
+    let num_threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = children[0].len().div_ceil(num_threads).max(1);
+
+    let mut results = vec![None; configs.len()];
+    let finished = thread::scope(|scope| {
+        let workers: Vec<_> = children[0]
+            .chunks(chunk_size)
+            .map(|first_drops| {
+                let (root, children) = (&root, &children);
+                scope.spawn(move || {
+                    let mut subtree_results = vec![];
+                    for &config_idx in first_drops {
+                        let tree = (configs, holds, children.as_slice());
+                        if !eval_subtree(root, tree, config_idx, deadline, &mut subtree_results) {
+                            return None;
+                        }
+                    }
+                    Some(subtree_results)
+                })
+            })
+            .collect();
+        let mut finished = true;
+        for worker in workers {
+            match worker.join().unwrap() {
+                Some(subtree_results) => {
+                    for (config_idx, result) in subtree_results {
+                        results[config_idx] = Some(result);
+                    }
+                }
+                None => finished = false,
+            }
+        }
+        finished
+    });
+    if !finished {
+        return None;
+    }
+
     let mut fields = Vec::with_capacity(configs.len() + 1);
     fields.push(initial_state.make_bitmap_field());
-
     let mut scores = Vec::with_capacity(configs.len());
+    for (field, score) in results.into_iter().map(Option::unwrap) {
+        fields.push(field);
+        scores.push(score);
+    }
+
+    Some((fields, scores))
+}
+
+impl<'a> SearchRules<'a> {
+    fn new(state: &GameState, attack_table: &'a AttackTable) -> Self {
+        let shapes: Vec<_> = iter::once(state.active_shape())
+            .chain(state.queued_shapes().iter().copied())
+            .collect();
+
+        // Count the garbage down as each lock does.
+        let mut countdowns: VecDeque<_> = (0..)
+            .map_while(|i| state.get_garbage_element_countdown(i))
+            .collect();
+        let garbage_rising = shapes
+            .iter()
+            .map(|_| {
+                let mut rising = 0;
+                while countdowns.front() == Some(&1) {
+                    countdowns.pop_front();
+                    rising += 1;
+                }
+                countdowns.iter_mut().for_each(|cnt| *cnt -= 1);
+                rising
+            })
+            .collect();
 
-    for config in configs {
-        let mut cur_config = config;
-        let mut moves = vec![];
-        loop {
-            moves.push(MovementDescriptor::from_drop_config(cur_config));
-            if cur_config.src_field_idx == 0 {
-                break;
+        Self {
+            shapes,
+            garbage_rising,
+            attack_table,
+        }
+    }
+}
+
+impl<'a> SearchNode<'a> {
+    /// The searched position, carrying on the game's combo and back-to-back.
+    fn root(state: &GameState, rules: &'a SearchRules<'a>) -> Self {
+        Self {
+            rules,
+            field: state.make_bitmap_field(),
+            hand: Hand::root(state),
+            drops: 0,
+            game_over: false,
+            lines_cleared: 0,
+            attack: 0,
+            attack_state: state.attack_state().clone(),
+        }
+    }
+
+    /// The node reached by making the move from this one.
+    fn child(&self, movement: &MovementDescriptor) -> (Self, BitmapField, MoveResultScore) {
+        let mut node = self.clone();
+        node.play(movement);
+        let score = score_field_cpu(node.game_over, node.lines_cleared, node.attack, &node.field);
+        let field = node.field;
+        (node, field, score)
+    }
+
+    /// Tally a lock, like `evaluate_moves_cpu`.
+    fn record(&mut self, lr: LockResult) {
+        // Searches don't know the play time, so ignore the attack multiplier.
+        let sent = self
+            .attack_state
+            .record_lock(self.rules.attack_table, &lr, Duration::ZERO);
+        self.attack = self
+            .attack
+            .saturating_add(sent.try_into().unwrap_or(u8::MAX));
+        match lr {
+            LockResult::GameOver => self.game_over = true,
+            LockResult::Ok { lines_cleared, .. } => self.lines_cleared += lines_cleared as u8,
+        }
+    }
+
+    /// Hold if the move does, then move the tetromino from its start position and drop it, like
+    /// the move's tick mutations.
+    fn play(&mut self, movement: &MovementDescriptor) {
+        if movement.hold {
+            if let Some(held) = self.hand.hold(&self.rules.shapes) {
+                self.hand = held;
+                if !self.field.is_valid(&Tetromino::new(held.active)) {
+                    self.record(LockResult::GameOver);
+                }
+            }
+        }
+
+        let start = START_POSITIONS.bot_start_position(movement.shape, movement.cw_rotations);
+        let (dir, num_shifts) = movement.shifts();
+
+        let (piece, spin) = if movement.tuck.is_empty() {
+            let mut piece = PieceMask::from(start);
+            for _ in 0..num_shifts {
+                match piece.shift(dir).filter(|p| !self.field.collides(p)) {
+                    Some(shifted) => piece = shifted,
+                    None => break,
+                }
+            }
+            (self.field.hard_drop(&piece), Spin::None)
+        } else {
+            let mut t = start.clone();
+            let mut rotated = false;
+            let shifts = iter::repeat_n(TuckInput::Shift(dir), num_shifts);
+            for input in shifts.chain(movement.tuck.iter().copied()) {
+                if let Some(moved) = input.apply(&self.field, &t) {
+                    t = moved;
+                    rotated = matches!(input, TuckInput::Rotate(_));
+                }
+            }
+            let piece = PieceMask::from(&t);
+            let dropped = self.field.hard_drop(&piece);
+            let spin = if rotated && dropped == piece {
+                Spin::classify(&t, |p| {
+                    !p.out_of_bounds() && p.y < consts::MAX_H && self.field.occupied(p)
+                })
             } else {
-                cur_config = &configs[cur_config.src_field_idx as usize - 1];
+                Spin::None
+            };
+            (dropped, spin)
+        };
+
+        self.field.place(&piece);
+        let lines_cleared = self.field.clear_lines();
+        let perfect_clear = self.field.is_empty();
+        for _ in 0..self
+            .rules
+            .garbage_rising
+            .get(self.drops)
+            .copied()
+            .unwrap_or(0)
+        {
+            self.field.apply_garbage();
+        }
+        self.drops += 1;
+
+        let next = self.hand.lock(&self.rules.shapes);
+        let lr = if next.is_some_and(|hand| !self.field.is_valid(&Tetromino::new(hand.active))) {
+            LockResult::GameOver
+        } else {
+            LockResult::Ok {
+                lines_cleared,
+                spin,
+                perfect_clear,
             }
+        };
+        if let Some(next) = next {
+            self.hand = next;
         }
+        self.record(lr);
+    }
+}
 
-        moves.reverse();
-        let (_, score, field) = evaluate_moves_cpu(initial_state, moves.as_slice());
+/// The configs, whether each holds, and the indexes of the configs dropping onto each field.
+type ConfigTree<'a> = (&'a [ComputedDropConfig], &'a [bool], &'a [Vec<usize>]);
 
-        debug_assert_eq!(fields.len(), config.dest_field_idx as usize);
-        debug_assert_eq!(scores.len(), config.dest_field_idx as usize - 1);
+/// Evaluate the config dropped onto `parent`, then everything dropped after it, depth first.
+/// False if the deadline passed first, which is checked before each config's children.
+fn eval_subtree(
+    parent: &SearchNode,
+    tree: ConfigTree,
+    config_idx: usize,
+    deadline: Option<Instant>,
+    results: &mut Vec<(usize, (BitmapField, MoveResultScore))>,
+) -> bool {
+    let (configs, holds, children) = tree;
+    let config = &configs[config_idx];
+    let movement = MovementDescriptor {
+        hold: holds[config_idx],
+        ..MovementDescriptor::from_drop_config(config)
+    };
+    let (node, field, score) = parent.child(&movement);
+    results.push((config_idx, (field, score)));
 
-        scores.push(score);
-        fields.push(field);
+    let child_idxs = &children[config.dest_field_idx as usize];
+    if child_idxs.is_empty() {
+        return true;
+    }
+    if is_past(deadline) {
+        return false;
+    }
+    child_idxs
+        .iter()
+        .all(|&child_idx| eval_subtree(&node, tree, child_idx, deadline, results))
+}
+
+fn is_past(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() > deadline)
+}
+
+/// Searches as deep as the whole preview queue by only expanding the best few positions of each
+/// level, as judged by the weights the moves are ranked by.
+pub struct BeamSearchContext {
+    pub beam_width: usize,
+    /// Also search placements reached by soft dropping, tucking and spinning, not just hard drops.
+    pub tucks: bool,
+    /// Rules for scoring the attack each move sends.
+    pub attack_table: AttackTable,
+}
+
+impl BotContext for BeamSearchContext {
+    type ResultType = CpuBotResults;
+
+    /// Unlike the exhaustive searches, the results only hold the children of each level's beam.
+    fn compute_drop_search(
+        &self,
+        search_depth: usize,
+        upcoming_shapes: &UpcomingShapes,
+        source_state: &GameState,
+        weights: &WeightProfile,
+    ) -> Result<CpuBotResults> {
+        Ok(self
+            .search(search_depth, upcoming_shapes, source_state, weights, None)
+            .unwrap())
+    }
+
+    fn compute_drop_search_until(
+        &self,
+        search_depth: usize,
+        upcoming_shapes: &UpcomingShapes,
+        source_state: &GameState,
+        weights: &WeightProfile,
+        deadline: Instant,
+    ) -> Result<Option<CpuBotResults>> {
+        Ok(self.search(
+            search_depth,
+            upcoming_shapes,
+            source_state,
+            weights,
+            Some(deadline),
+        ))
+    }
+
+    fn supported_features(&self) -> Vec<Feature> {
+        enum_iterator::all().collect()
+    }
+
+    fn searches_hold(&self) -> bool {
+        true
+    }
+}
+
+impl BeamSearchContext {
+    /// The beam search, or None if the deadline passed before it finished. The deadline is
+    /// checked before expanding each position of the beam.
+    fn search(
+        &self,
+        search_depth: usize,
+        upcoming_shapes: &UpcomingShapes,
+        source_state: &GameState,
+        weights: &WeightProfile,
+        deadline: Option<Instant>,
+    ) -> Option<CpuBotResults> {
+        let rules = SearchRules::new(source_state, &self.attack_table);
+        let mut results = CpuBotResults {
+            fields: vec![source_state.make_bitmap_field()],
+            ..Default::default()
+        };
+        let mut beam = vec![(0, SearchNode::root(source_state, &rules))];
+
+        for depth in 0..search_depth {
+            let mut candidates = vec![];
+            for (src_field_idx, parent) in &beam {
+                if is_past(deadline) {
+                    return None;
+                }
+                let plays = parent.hand.plays(upcoming_shapes, search_depth - depth);
+                for (hold, shape, _) in plays {
+                    let level_start = results.configs.len();
+                    if self.tucks {
+                        for placement in reachable_placements(&parent.field, shape) {
+                            let config_idx = results.configs.len();
+                            let config = placement_config(&placement, *src_field_idx, config_idx);
+                            results.configs.push(config);
+                            results.movements.push(placement.movement);
+                        }
+                    } else {
+                        push_drop_configs(&mut results.configs, shape, *src_field_idx);
+                    }
+                    results.holds.resize(results.configs.len(), hold);
+
+                    for i in level_start..results.configs.len() {
+                        let (node, field, score) = parent.child(&results.movement_of(i));
+                        let weighted = OrderedFloat(weighted_result_score(&score, &field, weights));
+                        candidates.push((weighted, results.configs[i].dest_field_idx, node));
+                        results.fields.push(field);
+                        results.scores.push(score);
+                    }
+                }
+            }
+
+            candidates.sort_by_key(|(weighted, _, _)| Reverse(*weighted));
+            candidates.truncate(self.beam_width.max(1));
+            beam = candidates
+                .into_iter()
+                .map(|(_, field_idx, node)| (field_idx, node))
+                .collect();
+        }
+
+        Some(results)
+    }
+}
+
+/// The config of a placement's shape, rotation and shifts. Its tuck is kept with the results'
+/// movements, since configs can't describe one.
+fn placement_config(
+    placement: &Placement,
+    src_field_idx: u32,
+    config_idx: usize,
+) -> ComputedDropConfig {
+    let movement = &placement.movement;
+    ComputedDropConfig {
+        shape_idx: START_POSITIONS.shape_to_idx[movement.shape],
+        cw_rotations: movement.cw_rotations as u8,
+        left_shifts: max(-movement.shifts_right, 0) as u8,
+        right_shifts: max(movement.shifts_right, 0) as u8,
+        src_field_idx,
+        dest_field_idx: (config_idx + 1) as u32,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bot_player::select_next_move;
+    use crate::evaluate_moves_cpu;
+    use manytris_core::field::{Field, Pos};
+    use manytris_core::game_state::TickMutation;
+    use manytris_core::shapes::{Rot, Shift};
+
+    /// Evaluate each config by replaying its whole chain of drops from the root.
+    fn eval_configs_by_replay(
+        initial_state: &GameState,
+        configs: &[ComputedDropConfig],
+        holds: &[bool],
+        attack_table: &AttackTable,
+    ) -> (Vec<BitmapField>, Vec<MoveResultScore>) {
+        let movement = |config_idx: usize| MovementDescriptor {
+            hold: holds[config_idx],
+            ..MovementDescriptor::from_drop_config(&configs[config_idx])
+        };
+        let mut fields = vec![initial_state.make_bitmap_field()];
+        let mut scores = vec![];
+        for (config_idx, config) in configs.iter().enumerate() {
+            let mut cur_config = config;
+            let mut moves = vec![movement(config_idx)];
+            while cur_config.src_field_idx != 0 {
+                let parent_idx = cur_config.src_field_idx as usize - 1;
+                cur_config = &configs[parent_idx];
+                moves.push(movement(parent_idx));
+            }
+            moves.reverse();
+            let (_, score, field) = evaluate_moves_cpu(initial_state, &moves, attack_table);
+            fields.push(field);
+            scores.push(score);
+        }
+        (fields, scores)
+    }
+
+    #[test]
+    fn incremental_matches_replay() {
+        let shapes = [Shape::I, Shape::T, Shape::L]
+            .into_iter()
+            .cycle()
+            .take(consts::NUM_PREVIEWS * 2)
+            .collect();
+        let mut gs = GameState::new(shapes);
+        // Leave some stack behind for the drops to land on and clear.
+        let _ = gs.tick_mutation(vec![
+            TickMutation::ShiftInput(Shift::Left),
+            TickMutation::DropInput,
+            TickMutation::RotateInput(Rot::Cw),
+            TickMutation::DropInput,
+            TickMutation::ShiftInput(Shift::Right),
+            TickMutation::DropInput,
+        ]);
+        // Rising after the second drop.
+        gs.set_garbage_delay(2);
+        let _ = gs.tick_mutation(vec![TickMutation::EnqueueGarbage(2)]);
+        let mut shapes = vec![gs.active_shape()];
+        shapes.extend_from_slice(&gs.upcoming_shapes());
+        let (configs, holds) = make_drop_configs_cpu(Hand::root(&gs), &shapes, 2);
+        // Holding at either depth.
+        let held_at = |root: bool| {
+            (configs.iter().zip(&holds)).any(|(c, h)| *h && (c.src_field_idx == 0) == root)
+        };
+        assert!(held_at(true) && held_at(false));
+
+        let table = AttackTable::guideline();
+        assert!(
+            eval_configs(&gs, &configs, &holds, &table, None).unwrap()
+                == eval_configs_by_replay(&gs, &configs, &holds, &table)
+        );
+    }
+
+    #[test]
+    fn tucks_match_replay() {
+        // A T slot at x = 4, covered on its left.
+        let field = Field::with_initial_occupied(
+            (0..consts::W)
+                .filter(|x| *x != 4)
+                .map(|x| Pos { x, y: 0 })
+                .chain([0, 1, 2, 6, 7, 8, 9].map(|x| Pos { x, y: 1 }))
+                .chain([0, 1, 2, 3].map(|x| Pos { x, y: 2 })),
+        );
+        let gs = GameState::with_initial_state(vec![Shape::T; consts::NUM_PREVIEWS * 2], field);
+        let table = AttackTable::guideline();
+        let rules = SearchRules::new(&gs, &table);
+        let root = SearchNode::root(&gs, &rules);
+
+        for placement in reachable_placements(&gs.make_bitmap_field(), Shape::T) {
+            let moves = [placement.movement];
+            let (_, field, score) = root.child(&moves[0]);
+            let (_, replay_score, replay_field) = evaluate_moves_cpu(&gs, &moves, &table);
+            assert_eq!((score, field), (replay_score, replay_field), "{moves:?}");
+        }
+    }
+
+    #[test]
+    fn holds_deeper_in_the_search() {
+        // Four rows full but for the right column, ready for an I.
+        let field = Field::with_initial_occupied(
+            (0..4).flat_map(|y| (0..consts::W - 1).map(move |x| Pos { x, y })),
+        );
+        let shapes = [Shape::I, Shape::O, Shape::S]
+            .into_iter()
+            .chain(iter::repeat(Shape::O))
+            .take(consts::NUM_PREVIEWS * 2)
+            .collect();
+        let mut gs = GameState::with_initial_state(shapes, field);
+        // The I is held, and hold is used until the O locks, so it's only back for the S.
+        let _ = gs.tick_mutation(vec![TickMutation::HoldInput]);
+
+        let beam = BeamSearchContext {
+            beam_width: 8,
+            tucks: false,
+            attack_table: AttackTable::default(),
+        };
+        for mr in [
+            select_next_move(&gs, &CpuBotContext::default(), &WeightProfile::versus(), 2).unwrap(),
+            select_next_move(&gs, &beam, &WeightProfile::versus(), 2).unwrap(),
+        ] {
+            assert!(!mr.moves[0].hold);
+            assert!(mr.moves[1].hold);
+            assert_eq!(mr.moves[1].shape, Shape::I);
+            assert_eq!(mr.score.lines_cleared, 4);
+        }
+    }
+
+    #[test]
+    fn queued_garbage_lands_during_search() {
+        let mut gs = GameState::new(vec![Shape::O; consts::NUM_PREVIEWS * 2]);
+        let calm = select_next_move(
+            &gs,
+            &CpuBotContext::default(),
+            &WeightProfile::survival(),
+            2,
+        )
+        .unwrap();
+
+        // Due after the first drop.
+        gs.set_garbage_delay(1);
+        let _ = gs.tick_mutation(vec![TickMutation::EnqueueGarbage(3)]);
+        let attacked = select_next_move(
+            &gs,
+            &CpuBotContext::default(),
+            &WeightProfile::survival(),
+            2,
+        )
+        .unwrap();
+        assert_eq!(attacked.score.height, calm.score.height + 3);
+    }
+
+    #[test]
+    fn gives_up_at_the_deadline() {
+        let gs = GameState::new(vec![Shape::T; consts::NUM_PREVIEWS * 2]);
+        let mut shapes = vec![gs.active_shape()];
+        shapes.extend_from_slice(&gs.upcoming_shapes());
+        let us: UpcomingShapes = shapes.try_into().unwrap();
+        let weights = WeightProfile::survival();
+        let exhaustive = CpuBotContext::default();
+        let beam = BeamSearchContext {
+            beam_width: 8,
+            tucks: false,
+            attack_table: AttackTable::default(),
+        };
+
+        let passed = Instant::now();
+        let late = exhaustive.compute_drop_search_until(3, &us, &gs, &weights, passed);
+        assert!(late.unwrap().is_none());
+        let late = beam.compute_drop_search_until(3, &us, &gs, &weights, passed);
+        assert!(late.unwrap().is_none());
+
+        let far = Instant::now() + Duration::from_secs(3600);
+        let timely = exhaustive.compute_drop_search_until(2, &us, &gs, &weights, far);
+        let full = exhaustive
+            .compute_drop_search(2, &us, &gs, &weights)
+            .unwrap();
+        assert_eq!(timely.unwrap().unwrap().scores(), full.scores());
+        let timely = beam.compute_drop_search_until(3, &us, &gs, &weights, far);
+        let full = beam.compute_drop_search(3, &us, &gs, &weights).unwrap();
+        assert_eq!(timely.unwrap().unwrap().scores(), full.scores());
+    }
+
+    #[test]
+    fn scores_attack_under_the_table() {
+        // Four rows full but for the right column, ready for an I.
+        let field = Field::with_initial_occupied(
+            (0..4).flat_map(|y| (0..consts::W - 1).map(move |x| Pos { x, y })),
+        );
+        let gs = GameState::with_initial_state(vec![Shape::I; consts::NUM_PREVIEWS * 2], field);
+
+        let classic =
+            select_next_move(&gs, &CpuBotContext::default(), &WeightProfile::versus(), 1).unwrap();
+        assert_eq!(classic.score.lines_cleared, 4);
+        assert_eq!(classic.score.attack, 4);
+
+        let mut table = AttackTable::default();
+        table.line_clears[4] = 6;
+        let ctx = CpuBotContext {
+            attack_table: table,
+        };
+        let custom = select_next_move(&gs, &ctx, &WeightProfile::versus(), 1).unwrap();
+        assert_eq!(custom.score.attack, 6);
+    }
+
+    #[test]
+    fn carries_on_the_games_back_to_back() {
+        // Four rows full but for the right column, ready for an I.
+        let field = Field::with_initial_occupied(
+            (0..4).flat_map(|y| (0..consts::W - 1).map(move |x| Pos { x, y })),
+        );
+        let mut gs = GameState::with_initial_state(vec![Shape::I; consts::NUM_PREVIEWS * 2], field);
+        let table = AttackTable::guideline();
+        let ctx = CpuBotContext {
+            attack_table: table.clone(),
+        };
+        let fresh = select_next_move(&gs, &ctx, &WeightProfile::versus(), 1).unwrap();
+
+        gs.set_attack_state(AttackState::new(None, true));
+        let back_to_back = select_next_move(&gs, &ctx, &WeightProfile::versus(), 1).unwrap();
+        assert_eq!(
+            back_to_back.score.attack as u32,
+            fresh.score.attack as u32 + table.back_to_back_bonus
+        );
+    }
+
+    #[test]
+    fn wide_beam_matches_exhaustive_search() {
+        let gs = GameState::new(vec![Shape::S; consts::NUM_PREVIEWS * 2]);
+        // Wide enough to keep every position of the first level.
+        let beam = BeamSearchContext {
+            beam_width: consts::OUTPUTS_PER_INPUT_FIELD,
+            tucks: false,
+            attack_table: AttackTable::default(),
+        };
+        let exhaustive = select_next_move(
+            &gs,
+            &CpuBotContext::default(),
+            &WeightProfile::survival(),
+            2,
+        )
+        .unwrap();
+        let beamed = select_next_move(&gs, &beam, &WeightProfile::survival(), 2).unwrap();
+        assert_eq!(exhaustive.score, beamed.score);
     }
 
-    (fields, scores)
+    #[test]
+    fn beam_searches_whole_queue() {
+        let shapes = [Shape::I, Shape::O, Shape::T, Shape::Z]
+            .into_iter()
+            .cycle()
+            .take(consts::NUM_PREVIEWS * 2)
+            .collect();
+        let gs = GameState::new(shapes);
+        let beam = BeamSearchContext {
+            beam_width: 8,
+            tucks: false,
+            attack_table: AttackTable::default(),
+        };
+        let full_depth = consts::MAX_SEARCH_DEPTH + 1;
+        let mr = select_next_move(&gs, &beam, &WeightProfile::survival(), full_depth).unwrap();
+        assert_eq!(mr.moves.len(), full_depth);
+        assert!(!mr.score.is_game_over());
+    }
+
+    #[test]
+    fn beam_prunes_with_the_callers_weights() {
+        let gs = GameState::new(vec![Shape::I; consts::NUM_PREVIEWS * 2]);
+        let beam = BeamSearchContext {
+            beam_width: 1,
+            tucks: false,
+            attack_table: AttackTable::default(),
+        };
+        // Only a standing I is kept from the first level, so two of them can be stacked.
+        let towering = WeightProfile::new([(Feature::Height, 1.0)]);
+        let mr = select_next_move(&gs, &beam, &towering, 2).unwrap();
+        assert_eq!(mr.score.height, 8);
+    }
+
+    #[test]
+    fn beam_tucks_into_slots() {
+        // A T slot at x = 4, covered on its left, which no hard drop fills.
+        let field = Field::with_initial_occupied(
+            (0..consts::W)
+                .filter(|x| *x != 4)
+                .map(|x| Pos { x, y: 0 })
+                .chain([0, 1, 2, 6, 7, 8, 9].map(|x| Pos { x, y: 1 }))
+                .chain([0, 1, 2, 3].map(|x| Pos { x, y: 2 })),
+        );
+        let gs = GameState::with_initial_state(vec![Shape::T; consts::NUM_PREVIEWS * 2], field);
+        let beam = BeamSearchContext {
+            beam_width: 8,
+            tucks: true,
+            attack_table: AttackTable::default(),
+        };
+        let mr = select_next_move(&gs, &beam, &WeightProfile::survival(), 1).unwrap();
+        assert!(!mr.moves[0].tuck.is_empty());
+        assert_eq!(mr.score.lines_cleared, 2);
+    }
 }
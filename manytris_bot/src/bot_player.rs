@@ -1,27 +1,45 @@
 use std::iter;
+use std::time::{Duration, Instant};
 
 use crate::bot_start_positions::START_POSITIONS;
 use crate::compute_types::{ComputedDropConfig, MoveResultScore, UpcomingShapes};
-use crate::{evaluate_moves_cpu, BotContext, BotResults};
+use crate::features::{FeatureScore, WeightProfile};
+use crate::placements::TuckInput;
+use crate::{evaluate_moves_cpu, num_outputs, BotContext, BotResults};
 use anyhow::Result;
-use manytris_core::consts;
-use manytris_core::game_state::{GameState, TickMutation};
+use manytris_core::attack::AttackTable;
+use manytris_core::bitmap_field::BitmapField;
+use manytris_core::game_state::{GameState, LockResult, TickMutation, TickResult};
 use manytris_core::shapes::{Shape, Shift};
 use ordered_float::OrderedFloat;
+use rand::Rng;
 
 const VALIDATE_GPU_MOVES: bool = false;
 
+/// How far down the list of first moves a mistake can reach.
+const MAX_MISTAKE_RANK: usize = 5;
+
+/// How many first moves past the pick to rank, in case some of them can't be played.
+const MAX_UNPLAYABLE_MOVES: usize = 20;
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct MoveResult {
     pub moves: Vec<MovementDescriptor>,
     pub score: MoveResultScore,
+    /// The field left after the moves.
+    pub field: BitmapField,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct MovementDescriptor {
+    /// Swap the active tetromino with the held one before moving it. `shape` is the tetromino
+    /// dropped after the swap.
+    pub hold: bool,
     pub shape: Shape,
     pub cw_rotations: usize,
     pub shifts_right: isize,
+    /// Inputs after the shifts, before locking in place. Empty for a hard drop.
+    pub tuck: Vec<TuckInput>,
 }
 
 pub struct ComputedDropSearchResults {
@@ -29,137 +47,633 @@ pub struct ComputedDropSearchResults {
     pub upcoming_shapes: UpcomingShapes,
     pub drops: Vec<MovementDescriptor>,
     pub score: MoveResultScore,
+    pub field: BitmapField,
 }
 
-pub type ScoringKs = [f32; 4];
+/// A line of play the bot considered, and how its score breaks down by feature.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExplainedMove {
+    pub moves: Vec<MovementDescriptor>,
+    pub score: f32,
+    pub features: Vec<FeatureScore>,
+    /// The field left after the moves.
+    pub field: BitmapField,
+}
 
-pub const BEST_BOT_KS: ScoringKs = [-2447.9722, 7782.121, -6099.498, -1970.1172];
+/// Ways to make the bot play worse than its best, for sparring against people.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Imperfection {
+    /// Chance of playing one of the next best first moves instead of the best one.
+    pub mistake_chance: f32,
+    /// Each scoring weight is scaled by a random factor up to this fraction away from 1.
+    pub weight_noise: f32,
+}
+
+/// A deadline for choosing a move, so a bot keeps up its pace whatever the backend or board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeBudget {
+    pub time: Duration,
+    /// The deepest search to try, however much time is left.
+    pub max_depth: usize,
+}
 
 impl MovementDescriptor {
     pub fn as_tick_mutations(&self) -> Vec<TickMutation> {
-        let (dir, num_shifts) = if self.shifts_right >= 0 {
+        let (dir, num_shifts) = self.shifts();
+        iter::once(TickMutation::HoldInput)
+            .filter(|_| self.hold)
+            .chain(iter::once(TickMutation::JumpToBotStartPosition(
+                START_POSITIONS
+                    .bot_start_position(self.shape, self.cw_rotations)
+                    .clone(),
+            )))
+            .chain(iter::repeat(TickMutation::ShiftInput(dir)).take(num_shifts))
+            .chain(self.tuck.iter().map(TuckInput::as_tick_mutation))
+            // Locks in place at the end of a tuck.
+            .chain(iter::once(TickMutation::DropInput))
+            .collect()
+    }
+
+    /// The direction and number of shifts from the start position.
+    pub fn shifts(&self) -> (Shift, usize) {
+        if self.shifts_right >= 0 {
             (Shift::Right, self.shifts_right as usize)
         } else {
             (Shift::Left, (-self.shifts_right) as usize)
-        };
-        iter::once(TickMutation::JumpToBotStartPosition(
-            START_POSITIONS
-                .bot_start_position(self.shape, self.cw_rotations)
-                .clone(),
-        ))
-        .chain(iter::repeat(TickMutation::ShiftInput(dir)).take(num_shifts))
-        .chain(iter::once(TickMutation::DropInput))
-        .collect()
+        }
     }
 
     pub fn from_drop_config(drop_config: &ComputedDropConfig) -> Self {
         Self {
+            hold: false,
             shape: *START_POSITIONS
                 .idx_to_shape
                 .get(&drop_config.shape_idx)
                 .unwrap(),
             cw_rotations: drop_config.cw_rotations as usize,
             shifts_right: drop_config.right_shifts as isize - (drop_config.left_shifts as isize),
+            tuck: vec![],
+        }
+    }
+}
+
+impl MoveResult {
+    pub fn explain(&self, weights: &WeightProfile) -> ExplainedMove {
+        ExplainedMove {
+            moves: self.moves.clone(),
+            score: weighted_result_score(&self.score, &self.field, weights),
+            features: weights.explain(&self.score, &self.field),
+            field: self.field,
         }
     }
 }
 
 impl ComputedDropSearchResults {
     // Select the best results from the score
-    pub fn find_results<F: Fn(&MoveResultScore) -> OrderedFloat<f32>>(
+    pub fn find_results<F: Fn(&MoveResultScore, &BitmapField) -> OrderedFloat<f32>>(
         search_depth: usize,
         upcoming_shapes: UpcomingShapes,
         bot_results: &impl BotResults,
         scoring_fn: F,
     ) -> Self {
-        let (start_idx, end_idx) = Self::idx_range(search_depth);
-        let scores = bot_results.scores();
-        assert_eq!(end_idx, scores.len());
+        Self::find_ranked_results(search_depth, upcoming_shapes, bot_results, scoring_fn, 0)
+    }
 
-        // Find the best score
-        let (best_idx, best_score) = scores[start_idx..end_idx]
-            .into_iter()
-            .enumerate()
-            .max_by_key(|(_i, s)| scoring_fn(s))
-            .unwrap();
+    /// Select the best results starting with the `rank`th best first move, counting from 0.
+    ///
+    /// Asking for a rank past the number of distinct first moves gives the worst of them.
+    pub fn find_ranked_results<F: Fn(&MoveResultScore, &BitmapField) -> OrderedFloat<f32>>(
+        search_depth: usize,
+        upcoming_shapes: UpcomingShapes,
+        bot_results: &impl BotResults,
+        scoring_fn: F,
+        rank: usize,
+    ) -> Self {
+        Self::ranked_results(
+            search_depth,
+            upcoming_shapes,
+            bot_results,
+            scoring_fn,
+            rank + 1,
+        )
+        .pop()
+        .unwrap()
+    }
 
-        let mut next_config_idx = start_idx + best_idx;
-        let mut moves = vec![];
+    /// The best results of up to `count` distinct first moves, best first.
+    ///
+    /// The results don't need to be a complete tree: any config `search_depth` drops deep is a
+    /// leaf.
+    pub fn ranked_results<F: Fn(&MoveResultScore, &BitmapField) -> OrderedFloat<f32>>(
+        search_depth: usize,
+        upcoming_shapes: UpcomingShapes,
+        bot_results: &impl BotResults,
+        scoring_fn: F,
+        count: usize,
+    ) -> Vec<Self> {
+        let scores = bot_results.scores();
         let configs = bot_results.configs();
-        loop {
-            let cfg = &configs[next_config_idx];
-            moves.insert(0, MovementDescriptor::from_drop_config(cfg));
+        let fields = bot_results.fields();
+        let leaf_field = |config_idx: usize| &fields[configs[config_idx].dest_field_idx as usize];
+        assert_eq!(configs.len(), scores.len());
+
+        // Parents always come before their children.
+        let mut field_depths = vec![0; configs.len() + 1];
+        for cfg in configs {
+            field_depths[cfg.dest_field_idx as usize] =
+                field_depths[cfg.src_field_idx as usize] + 1;
+        }
+
+        let first_config_idx = |mut config_idx: usize| loop {
+            let src_field_idx = configs[config_idx].src_field_idx as usize;
+            if src_field_idx == 0 {
+                return config_idx;
+            }
+            config_idx = src_field_idx - 1;
+        };
 
-            if cfg.src_field_idx == 0 {
-                break;
+        // Best leaf of each first move, best first.
+        let mut leaves: Vec<usize> = (0..configs.len())
+            .filter(|i| field_depths[configs[*i].dest_field_idx as usize] == search_depth)
+            .collect();
+        leaves.sort_by_cached_key(|i| std::cmp::Reverse(scoring_fn(&scores[*i], leaf_field(*i))));
+        let mut seen_first_moves = vec![];
+        let mut ranked_leaves = vec![];
+        for leaf in leaves {
+            let first = first_config_idx(leaf);
+            if !seen_first_moves.contains(&first) {
+                seen_first_moves.push(first);
+                ranked_leaves.push(leaf);
+                if ranked_leaves.len() == count.max(1) {
+                    break;
+                }
             }
-            next_config_idx = cfg.src_field_idx as usize - 1;
         }
 
-        ComputedDropSearchResults {
+        ranked_leaves
+            .into_iter()
+            .map(|leaf_idx| {
+                let mut next_config_idx = leaf_idx;
+                let mut moves = vec![];
+                loop {
+                    let cfg = &configs[next_config_idx];
+                    let movement = bot_results.movement(next_config_idx);
+                    moves.insert(
+                        0,
+                        movement.unwrap_or_else(|| MovementDescriptor::from_drop_config(cfg)),
+                    );
+
+                    if cfg.src_field_idx == 0 {
+                        break;
+                    }
+                    next_config_idx = cfg.src_field_idx as usize - 1;
+                }
+
+                ComputedDropSearchResults {
+                    search_depth,
+                    upcoming_shapes,
+                    drops: moves,
+                    score: scores[leaf_idx],
+                    field: *leaf_field(leaf_idx),
+                }
+            })
+            .collect()
+    }
+
+    /// Like `ranked_results`, with each result's score broken down by feature.
+    pub fn explained_results(
+        search_depth: usize,
+        upcoming_shapes: UpcomingShapes,
+        bot_results: &impl BotResults,
+        weights: &WeightProfile,
+        count: usize,
+    ) -> Vec<ExplainedMove> {
+        let scoring_fn = |score: &MoveResultScore, field: &BitmapField| {
+            OrderedFloat(weighted_result_score(score, field, weights))
+        };
+        Self::ranked_results(
             search_depth,
-            upcoming_shapes: upcoming_shapes.clone(),
-            drops: moves,
-            score: best_score.clone(),
-        }
+            upcoming_shapes,
+            bot_results,
+            scoring_fn,
+            count,
+        )
+        .iter()
+        .map(|r| r.explain(weights))
+        .collect()
+    }
+
+    pub fn explain(&self, weights: &WeightProfile) -> ExplainedMove {
+        self.make_move_result().explain(weights)
     }
 
     pub fn make_move_result(&self) -> MoveResult {
         MoveResult {
             moves: self.drops.clone(),
             score: self.score.clone(),
+            field: self.field,
         }
     }
+}
 
-    fn idx_range(search_depth: usize) -> (usize, usize) {
-        let mut start_idx = 0;
-        let mut end_idx = 0;
-        for i in 0..search_depth {
-            start_idx = end_idx;
-            end_idx += consts::OUTPUTS_PER_INPUT_FIELD.pow(i as u32 + 1);
-        }
-        (start_idx, end_idx)
+pub fn select_next_move(
+    gs: &GameState,
+    ctx: &impl BotContext,
+    weights: &WeightProfile,
+    search_depth: usize,
+) -> Result<MoveResult> {
+    let move_result = search_ranked_moves(gs, ctx, weights, search_depth, 1)?.remove(0);
+
+    if VALIDATE_GPU_MOVES {
+        let (_, cpu_score, _) = evaluate_moves_cpu(gs, &move_result.moves, &AttackTable::default());
+        // The GPU searches don't score attack.
+        assert_eq!(&cpu_score.with_attack(0), &move_result.score);
     }
+
+    Ok(move_result)
 }
 
-pub fn select_next_move(
+/// The best moves of up to `count` distinct first moves, best first, with their scores broken
+/// down by feature. Holding is searched as it is for `select_next_move`.
+pub fn explain_next_moves(
     gs: &GameState,
     ctx: &impl BotContext,
-    ks: &ScoringKs,
+    weights: &WeightProfile,
     search_depth: usize,
+    count: usize,
+) -> Result<Vec<ExplainedMove>> {
+    Ok(search_ranked_moves(gs, ctx, weights, search_depth, count)?
+        .iter()
+        .map(|mr| mr.explain(weights))
+        .collect())
+}
+
+/// Like `select_next_move`, but searching as deep as the time budget allows.
+pub fn select_move_within(
+    gs: &GameState,
+    ctx: &impl BotContext,
+    weights: &WeightProfile,
+    budget: &TimeBudget,
 ) -> Result<MoveResult> {
-    let mut usv = vec![gs.active_shape()];
-    usv.extend_from_slice(&gs.upcoming_shapes());
-    let us: UpcomingShapes = usv.try_into().unwrap();
+    Ok(deepen_ranked_moves(gs, ctx, weights, budget, 1)?.remove(0))
+}
+
+/// Like `select_next_move`, but occasionally misjudging the field or picking a worse move, and
+/// only picking first moves `playable` accepts. None if none of the best first moves are
+/// playable.
+pub fn select_imperfect_move(
+    gs: &GameState,
+    ctx: &impl BotContext,
+    weights: &WeightProfile,
+    search_depth: usize,
+    imperfection: &Imperfection,
+    rng: &mut impl Rng,
+    playable: impl Fn(&MovementDescriptor) -> bool,
+) -> Result<Option<MoveResult>> {
+    pick_imperfect_move(weights, imperfection, rng, playable, |weights, count| {
+        search_ranked_moves(gs, ctx, weights, search_depth, count)
+    })
+}
 
-    let bot_results = ctx.compute_drop_search(search_depth, &us, gs)?;
+/// Like `select_imperfect_move`, but searching as deep as the time budget allows.
+pub fn select_imperfect_move_within(
+    gs: &GameState,
+    ctx: &impl BotContext,
+    weights: &WeightProfile,
+    budget: &TimeBudget,
+    imperfection: &Imperfection,
+    rng: &mut impl Rng,
+    playable: impl Fn(&MovementDescriptor) -> bool,
+) -> Result<Option<MoveResult>> {
+    pick_imperfect_move(weights, imperfection, rng, playable, |weights, count| {
+        deepen_ranked_moves(gs, ctx, weights, budget, count)
+    })
+}
 
-    let results =
-        ComputedDropSearchResults::find_results(search_depth, us, &bot_results, |score| {
-            OrderedFloat(weighted_result_score(score, ks))
-        });
+/// Rank moves with `search`, under noisy weights, drop those whose first move isn't playable,
+/// and sometimes pick one other than the best.
+fn pick_imperfect_move(
+    weights: &WeightProfile,
+    imperfection: &Imperfection,
+    rng: &mut impl Rng,
+    playable: impl Fn(&MovementDescriptor) -> bool,
+    search: impl FnOnce(&WeightProfile, usize) -> Result<Vec<MoveResult>>,
+) -> Result<Option<MoveResult>> {
+    let noise = imperfection.weight_noise;
+    let weights = weights.map(|_, k| {
+        if noise > 0.0 {
+            k * (1.0 + rng.gen_range(-noise..=noise))
+        } else {
+            k
+        }
+    });
+    let rank = if rng.gen::<f32>() < imperfection.mistake_chance {
+        rng.gen_range(1..=MAX_MISTAKE_RANK)
+    } else {
+        0
+    };
 
-    let move_result = results.make_move_result();
+    let mut ranked = search(&weights, rank + 1 + MAX_UNPLAYABLE_MOVES)?;
+    ranked.retain(|mr| playable(&mr.moves[0]));
+    if ranked.is_empty() {
+        return Ok(None);
+    }
+    let pick = rank.min(ranked.len() - 1);
+    Ok(Some(ranked.swap_remove(pick)))
+}
 
-    if VALIDATE_GPU_MOVES {
-        let (_, cpu_score, _) = evaluate_moves_cpu(gs, &move_result.moves);
-        assert_eq!(&cpu_score, &move_result.score);
+/// Search one drop deeper at a time until the budget runs out, and rank the deepest finished
+/// search's moves like `search_ranked_moves`.
+///
+/// Searches which can be stopped are given up on at the deadline. A depth isn't started if it's
+/// expected to miss it, guessing its time from how much longer the last depth took than the one
+/// before. The first depth is always searched in full, so there's a move to make.
+fn deepen_ranked_moves(
+    gs: &GameState,
+    ctx: &impl BotContext,
+    weights: &WeightProfile,
+    budget: &TimeBudget,
+    count: usize,
+) -> Result<Vec<MoveResult>> {
+    let start = Instant::now();
+    let deadline = start + budget.time;
+    let mut ranked = search_ranked_moves(gs, ctx, weights, 1, count)?;
+    let mut last_secs = start.elapsed().as_secs_f64();
+    // Until two depths have been timed, guess the exhaustive searches' growth in positions.
+    let mut growth = num_outputs(2) as f64 / num_outputs(1) as f64;
+
+    for depth in 2..=budget.max_depth {
+        let expected_secs = start.elapsed().as_secs_f64() + last_secs * growth;
+        if expected_secs > budget.time.as_secs_f64() {
+            break;
+        }
+        let depth_start = Instant::now();
+        match rank_moves(gs, ctx, weights, depth, count, Some(deadline))? {
+            Some(deeper) => ranked = deeper,
+            None => break,
+        }
+        let secs = depth_start.elapsed().as_secs_f64();
+        growth = secs / last_secs.max(1e-6);
+        last_secs = secs;
     }
+    Ok(ranked)
+}
 
-    Ok(move_result)
+/// The best moves of up to `count` distinct first moves, best first. For searches which don't
+/// hold as they go, holding before the first drop is searched as well as dropping the active
+/// tetromino, when hold is available.
+fn search_ranked_moves(
+    gs: &GameState,
+    ctx: &impl BotContext,
+    weights: &WeightProfile,
+    search_depth: usize,
+    count: usize,
+) -> Result<Vec<MoveResult>> {
+    Ok(rank_moves(gs, ctx, weights, search_depth, count, None)?.unwrap())
 }
 
-fn weighted_result_score(mrs: &MoveResultScore, ks: &ScoringKs) -> f32 {
-    let game_over_f32 = if mrs.is_game_over() { 1.0 } else { -1.0 };
-    game_over_f32 * ks[0]
-        + mrs.lines_cleared as f32 * ks[1]
-        + mrs.height as f32 * ks[2]
-        + mrs.covered as f32 * ks[3]
+/// Like `search_ranked_moves`, or None if the deadline passed before the search finished.
+fn rank_moves(
+    gs: &GameState,
+    ctx: &impl BotContext,
+    weights: &WeightProfile,
+    search_depth: usize,
+    count: usize,
+    deadline: Option<Instant>,
+) -> Result<Option<Vec<MoveResult>>> {
+    let scoring_fn = |score: &MoveResultScore, field: &BitmapField| {
+        OrderedFloat(weighted_result_score(score, field, weights))
+    };
+
+    let branches = if ctx.searches_hold() {
+        vec![(false, gs.clone())]
+    } else {
+        hold_branches(gs)
+    };
+    let mut ranked = vec![];
+    for (hold, branch_state) in branches {
+        let mut usv = vec![branch_state.active_shape()];
+        usv.extend_from_slice(&branch_state.upcoming_shapes());
+        let us: UpcomingShapes = usv.try_into().unwrap();
+
+        let bot_results = match deadline {
+            Some(deadline) => {
+                ctx.compute_drop_search_until(search_depth, &us, &branch_state, weights, deadline)?
+            }
+            None => Some(ctx.compute_drop_search(search_depth, &us, &branch_state, weights)?),
+        };
+        let Some(bot_results) = bot_results else {
+            return Ok(None);
+        };
+        let results = ComputedDropSearchResults::ranked_results(
+            search_depth,
+            us,
+            &bot_results,
+            scoring_fn,
+            count,
+        );
+        ranked.extend(results.iter().map(|r| {
+            let mut move_result = r.make_move_result();
+            move_result.moves[0].hold |= hold;
+            move_result
+        }));
+    }
+
+    // Stable, so ties keep the tetromino rather than holding it.
+    ranked.sort_by_cached_key(|mr| std::cmp::Reverse(scoring_fn(&mr.score, &mr.field)));
+    ranked.truncate(count.max(1));
+    Ok(Some(ranked))
+}
+
+/// The states to search from: as is, and after holding if that's allowed and changes anything.
+pub(crate) fn hold_branches(gs: &GameState) -> Vec<(bool, GameState)> {
+    let mut branches = vec![(false, gs.clone())];
+    let held_shape = gs.held_tetromino().map(|t| t.shape);
+    if gs.can_hold() && held_shape != Some(gs.active_shape()) {
+        let mut held = gs.clone();
+        let topped_out = held
+            .tick_mutation(vec![TickMutation::HoldInput])
+            .iter()
+            .any(|tr| matches!(tr, TickResult::Lock(LockResult::GameOver)));
+        if !topped_out {
+            branches.push((true, held));
+        }
+    }
+    branches
+}
+
+pub fn weighted_result_score(
+    mrs: &MoveResultScore,
+    field: &BitmapField,
+    weights: &WeightProfile,
+) -> f32 {
+    weights.score(mrs, field)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use manytris_core::consts;
+
+    use crate::bot_cpu::CpuBotContext;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn holds_for_a_better_piece() {
+        let mut shapes = vec![Shape::S, Shape::I];
+        shapes.extend([Shape::O; consts::NUM_PREVIEWS * 2]);
+        let gs = GameState::new(shapes);
+
+        let mr = select_next_move(
+            &gs,
+            &CpuBotContext::default(),
+            &WeightProfile::survival(),
+            1,
+        )
+        .unwrap();
+        assert!(mr.moves[0].hold);
+        assert_eq!(mr.moves[0].shape, Shape::I);
+        assert!(matches!(
+            mr.moves[0].as_tick_mutations()[..2],
+            [
+                TickMutation::HoldInput,
+                TickMutation::JumpToBotStartPosition(_)
+            ]
+        ));
+
+        let (after_hold, _, _) = evaluate_moves_cpu(&gs, &mr.moves, &AttackTable::default());
+        assert_eq!(after_hold.held_tetromino().map(|t| t.shape), Some(Shape::S));
+    }
+
+    #[test]
+    fn keeps_a_good_piece() {
+        let mut shapes = vec![Shape::I, Shape::S];
+        shapes.extend([Shape::O; consts::NUM_PREVIEWS * 2]);
+        let gs = GameState::new(shapes);
+
+        let mr = select_next_move(
+            &gs,
+            &CpuBotContext::default(),
+            &WeightProfile::survival(),
+            1,
+        )
+        .unwrap();
+        assert!(!mr.moves[0].hold);
+        assert_eq!(mr.moves[0].shape, Shape::I);
+    }
+
+    #[test]
+    fn perfect_play_matches_best_move() {
+        let gs = GameState::new(vec![Shape::T; consts::NUM_PREVIEWS * 2]);
+        let best = select_next_move(
+            &gs,
+            &CpuBotContext::default(),
+            &WeightProfile::survival(),
+            1,
+        )
+        .unwrap();
+        let imperfect = select_imperfect_move(
+            &gs,
+            &CpuBotContext::default(),
+            &WeightProfile::survival(),
+            1,
+            &Imperfection::default(),
+            &mut StdRng::seed_from_u64(36),
+            |_| true,
+        )
+        .unwrap();
+        assert_eq!(Some(best), imperfect);
+    }
+
+    #[test]
+    fn mistakes_pick_other_first_moves() {
+        let gs = GameState::new(vec![Shape::T; consts::NUM_PREVIEWS * 2]);
+        let best = select_next_move(
+            &gs,
+            &CpuBotContext::default(),
+            &WeightProfile::survival(),
+            2,
+        )
+        .unwrap();
+        let always_wrong = Imperfection {
+            mistake_chance: 1.0,
+            weight_noise: 0.0,
+        };
+        let mut rng = StdRng::seed_from_u64(36);
+        for _ in 0..5 {
+            let mr = select_imperfect_move(
+                &gs,
+                &CpuBotContext::default(),
+                &WeightProfile::survival(),
+                2,
+                &always_wrong,
+                &mut rng,
+                |_| true,
+            )
+            .unwrap()
+            .unwrap();
+            assert_ne!(mr.moves[0], best.moves[0]);
+        }
+    }
+
+    #[test]
+    fn picks_only_playable_moves() {
+        let gs = GameState::new(vec![Shape::T; consts::NUM_PREVIEWS * 2]);
+        let ctx = CpuBotContext::default();
+        let weights = WeightProfile::survival();
+        let best = select_next_move(&gs, &ctx, &weights, 1).unwrap();
+        let mut rng = StdRng::seed_from_u64(36);
+
+        let perfect = Imperfection::default();
+        let mr = select_imperfect_move(&gs, &ctx, &weights, 1, &perfect, &mut rng, |md| {
+            *md != best.moves[0]
+        })
+        .unwrap()
+        .unwrap();
+        assert_ne!(mr.moves[0], best.moves[0]);
+
+        let none = select_imperfect_move(&gs, &ctx, &weights, 1, &perfect, &mut rng, |_| false);
+        assert_eq!(none.unwrap(), None);
+    }
+
+    #[test]
+    fn deepens_within_the_budget() {
+        let gs = GameState::new(vec![Shape::T; consts::NUM_PREVIEWS * 2]);
+        let weights = WeightProfile::survival();
+        let ctx = CpuBotContext::default();
+
+        let rushed = TimeBudget {
+            time: Duration::ZERO,
+            max_depth: 3,
+        };
+        let mr = select_move_within(&gs, &ctx, &weights, &rushed).unwrap();
+        assert_eq!(mr, select_next_move(&gs, &ctx, &weights, 1).unwrap());
+
+        let unhurried = TimeBudget {
+            time: Duration::from_secs(3600),
+            max_depth: 2,
+        };
+        let mr = select_move_within(&gs, &ctx, &weights, &unhurried).unwrap();
+        assert_eq!(mr, select_next_move(&gs, &ctx, &weights, 2).unwrap());
+    }
+
+    #[test]
+    fn explains_the_best_moves() {
+        let gs = GameState::new(vec![Shape::T; consts::NUM_PREVIEWS * 2]);
+        let weights = WeightProfile::versus();
+        let best = select_next_move(&gs, &CpuBotContext::default(), &weights, 2).unwrap();
+        let explained = explain_next_moves(&gs, &CpuBotContext::default(), &weights, 2, 3).unwrap();
+
+        assert_eq!(explained.len(), 3);
+        assert_eq!(explained[0].moves, best.moves);
+        assert_eq!(explained[0].field, best.field);
+        assert!(explained.windows(2).all(|w| w[0].score >= w[1].score));
+        for em in &explained {
+            let total: f32 = em.features.iter().map(FeatureScore::contribution).sum();
+            assert_eq!(total, em.score);
+        }
+    }
 
     #[test]
     fn ordering() {
@@ -170,5 +684,8 @@ mod tests {
         assert!(MoveResultScore::init(false, 0, 0, 0) > MoveResultScore::init(false, 0, 1, 0));
 
         assert!(MoveResultScore::init(false, 0, 0, 0) > MoveResultScore::init(false, 0, 0, 1));
+
+        let attacking = MoveResultScore::init(false, 0, 0, 1).with_attack(1);
+        assert!(attacking > MoveResultScore::init(false, 0, 0, 0));
     }
 }
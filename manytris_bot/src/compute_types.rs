@@ -19,6 +19,9 @@ pub struct MoveResultScore {
     game_over: u8,
     pub lines_cleared: u8,
     pub height: u8,
+    /// Garbage lines sent to opponents. Fills what was padding, so the GPU searches' layout is
+    /// unchanged. They don't know the attack table, and leave it zero.
+    pub attack: u8,
     pub covered: u16,
 }
 
@@ -29,10 +32,15 @@ impl MoveResultScore {
             game_over,
             lines_cleared,
             height,
+            attack: 0,
             covered,
         }
     }
 
+    pub fn with_attack(self, attack: u8) -> Self {
+        Self { attack, ..self }
+    }
+
     pub fn is_game_over(&self) -> bool {
         self.game_over != 0
     }
@@ -84,8 +92,8 @@ impl From<Tetromino> for TetrominoPositions {
 impl Display for MoveResultScore {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
-            "Lost: {}, Cleared: {}, covered: {}, Height: {}",
-            self.game_over, self.lines_cleared, self.covered, self.height
+            "Lost: {}, Cleared: {}, Attack: {}, covered: {}, Height: {}",
+            self.game_over, self.lines_cleared, self.attack, self.covered, self.height
         ))
     }
 }
@@ -108,6 +116,9 @@ impl Ord for MoveResultScore {
         } else if self.lines_cleared != other.lines_cleared {
             // More lines cleared is better
             self.lines_cleared.cmp(&other.lines_cleared)
+        } else if self.attack != other.attack {
+            // More attack is better
+            self.attack.cmp(&other.attack)
         } else if self.covered != other.covered {
             // less coverage is better
             other.covered.cmp(&self.covered)
@@ -0,0 +1,275 @@
+use std::collections::{HashMap, VecDeque};
+
+use manytris_core::attack::AttackTable;
+use manytris_core::bitmap_field::BitmapField;
+use manytris_core::game_state::{GameState, TickMutation};
+use manytris_core::shapes::Shape;
+use manytris_core::tetromino::Tetromino;
+use ordered_float::OrderedFloat;
+
+use crate::bot_player::{hold_branches, weighted_result_score, MoveResult, MovementDescriptor};
+use crate::evaluate_moves_cpu;
+use crate::features::WeightProfile;
+use crate::placements::{hard_drop, lock_cells, reachable_placements, TuckInput, TUCK_INPUTS};
+
+/// Plays a bot move with inputs a person could make, starting from wherever the active tetromino
+/// is, for matches which don't allow teleporting it with `JumpToBotStartPosition`.
+///
+/// The path is planned again before every input, so gravity moving the tetromino in between
+/// doesn't throw it off. Bot searches start from above the field, so on a tall stack a move can
+/// be out of reach of the spawn: check with `fair_play_inputs` before picking a move, and fall
+/// back to `best_fair_drop`. A move which stops being reachable part way is given up on.
+#[derive(Clone, Debug)]
+pub struct FairPlayMove {
+    hold: bool,
+    shape: Shape,
+    /// Where the move leaves the tetromino before dropping it.
+    target: Tetromino,
+    /// The move ends with a rotation, so it scores as a spin.
+    spin: bool,
+    /// The field the move was planned on. It only changes once the tetromino locks.
+    field: BitmapField,
+    /// The last input was a rotation.
+    spun: bool,
+    dropped: bool,
+}
+
+type PathState = (Tetromino, bool);
+
+impl FairPlayMove {
+    pub fn new(gs: &GameState, md: &MovementDescriptor) -> Self {
+        let mut planned = gs.clone();
+        let mut mutations = md.as_tick_mutations();
+        // Stop short of the drop, to see where the move leaves the tetromino.
+        mutations.pop();
+        planned.tick_mutation(mutations);
+
+        Self {
+            hold: md.hold,
+            shape: md.shape,
+            target: planned.active_tetromino().clone(),
+            spin: matches!(md.tuck.last(), Some(TuckInput::Rotate(_))),
+            field: gs.make_bitmap_field(),
+            spun: false,
+            dropped: false,
+        }
+    }
+
+    /// The next input to make, or None once the tetromino has been dropped or the move can't be
+    /// played from the game's state.
+    pub fn next_input(&mut self, gs: &GameState) -> Option<TickMutation> {
+        if self.dropped || gs.make_bitmap_field() != self.field {
+            return None;
+        }
+        if gs.active_shape() != self.shape {
+            return (self.hold && gs.can_hold()).then_some(TickMutation::HoldInput);
+        }
+
+        let path = self.path_from(gs.active_tetromino())?;
+        Some(match path.first() {
+            Some(input) => {
+                self.spun = matches!(input, TuckInput::Rotate(_));
+                input.as_tick_mutation()
+            }
+            None => {
+                self.dropped = true;
+                TickMutation::DropInput
+            }
+        })
+    }
+
+    /// The tetromino has been dropped, so the move was played in full.
+    pub fn dropped(&self) -> bool {
+        self.dropped
+    }
+
+    /// The shortest inputs from the tetromino to where dropping it completes the move.
+    fn path_from(&self, start: &Tetromino) -> Option<Vec<TuckInput>> {
+        let field = &self.field;
+        let target_cells = lock_cells(&hard_drop(field, &self.target));
+        let is_goal = |(t, spun): &PathState| {
+            let landed = TuckInput::SoftDrop.apply(field, t).is_none();
+            if self.spin {
+                landed && *spun && *t == self.target
+            } else {
+                // Dropping a tetromino which can't fall straight after a rotation is a spin.
+                !(landed && *spun) && lock_cells(&hard_drop(field, t)) == target_cells
+            }
+        };
+
+        let start = (start.clone(), self.spun);
+        let mut parents: HashMap<PathState, (PathState, TuckInput)> = HashMap::new();
+        let mut queue = VecDeque::from([start.clone()]);
+        while let Some(state) = queue.pop_front() {
+            if is_goal(&state) {
+                let mut path = vec![];
+                let mut state = &state;
+                while let Some((parent, input)) = parents.get(state) {
+                    path.push(*input);
+                    state = parent;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for input in TUCK_INPUTS {
+                let Some(next) = input.apply(field, &state.0) else {
+                    continue;
+                };
+                let next = (next, matches!(input, TuckInput::Rotate(_)));
+                if next != start && !parents.contains_key(&next) {
+                    parents.insert(next.clone(), (state.clone(), input));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Every input to play the move from the game's state, or None if it can't be played.
+pub fn fair_play_inputs(gs: &GameState, md: &MovementDescriptor) -> Option<Vec<TickMutation>> {
+    let mut fair_move = FairPlayMove::new(gs, md);
+    let mut gs = gs.clone();
+    let mut inputs = vec![];
+    while let Some(input) = fair_move.next_input(&gs) {
+        gs.tick_mutation(vec![input.clone()]);
+        inputs.push(input);
+    }
+    fair_move.dropped.then_some(inputs)
+}
+
+/// The best scoring single drop which can be played from the game's state without teleporting,
+/// for when none of a search's moves can. None if no placement is reachable.
+pub fn best_fair_drop(
+    gs: &GameState,
+    weights: &WeightProfile,
+    attack_table: &AttackTable,
+) -> Option<MoveResult> {
+    hold_branches(gs)
+        .into_iter()
+        .flat_map(|(hold, branch)| {
+            reachable_placements(&branch.make_bitmap_field(), branch.active_shape())
+                .into_iter()
+                .map(move |p| MovementDescriptor { hold, ..p.movement })
+        })
+        .filter(|md| fair_play_inputs(gs, md).is_some())
+        .map(|md| {
+            let moves = vec![md];
+            let (_, score, field) = evaluate_moves_cpu(gs, &moves, attack_table);
+            MoveResult {
+                moves,
+                score,
+                field,
+            }
+        })
+        .max_by_key(|mr| OrderedFloat(weighted_result_score(&mr.score, &mr.field, weights)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bot_cpu::{BeamSearchContext, CpuBotContext};
+    use crate::bot_player::{select_imperfect_move, select_next_move, Imperfection};
+    use manytris_core::consts;
+    use manytris_core::field::{Field, Pos};
+    use manytris_core::game_state::{LockResult, Spin, TickResult};
+    use manytris_core::shape_bag::ShapeBag;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn plays_bot_moves_without_teleporting() {
+        let ctx = BeamSearchContext {
+            beam_width: 8,
+            tucks: true,
+            attack_table: AttackTable::default(),
+        };
+        let mut shape_bag = ShapeBag::seeded(50);
+        let mut gs = GameState::new(shape_bag.by_ref().take(consts::NUM_PREVIEWS * 2).collect());
+
+        for _ in 0..30 {
+            let mr = select_next_move(&gs, &ctx, &WeightProfile::versus(), 1).unwrap();
+            let (_, _, expected) = evaluate_moves_cpu(&gs, &mr.moves, &AttackTable::default());
+
+            let inputs = fair_play_inputs(&gs, &mr.moves[0]).unwrap();
+            assert!(!inputs
+                .iter()
+                .any(|m| matches!(m, TickMutation::JumpToBotStartPosition(_))));
+            gs.tick_mutation(inputs);
+            assert_eq!(gs.make_bitmap_field(), expected);
+
+            gs.tick_mutation(vec![TickMutation::EnqueueTetromino(
+                shape_bag.next().unwrap(),
+            )]);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_moves_in_reach_of_the_spawn() {
+        // A well at x < 2, walled off from the spawn by a column up to just below it, and a stack
+        // covering the rest.
+        let field = Field::with_initial_occupied(
+            (0..=20)
+                .map(|y| Pos { x: 2, y })
+                .chain((3..consts::W).flat_map(|x| (0..16).map(move |y| Pos { x, y }))),
+        );
+        let gs = GameState::with_initial_state(vec![Shape::T; consts::NUM_PREVIEWS * 2], field);
+        let ctx = CpuBotContext::default();
+        let weights = WeightProfile::survival();
+
+        // The best move drops into the well from above the field.
+        let best = select_next_move(&gs, &ctx, &weights, 1).unwrap();
+        assert!(fair_play_inputs(&gs, &best.moves[0]).is_none());
+
+        let fair = select_imperfect_move(
+            &gs,
+            &ctx,
+            &weights,
+            1,
+            &Imperfection::default(),
+            &mut StdRng::seed_from_u64(50),
+            |md| fair_play_inputs(&gs, md).is_some(),
+        )
+        .unwrap()
+        .unwrap();
+        assert!(fair_play_inputs(&gs, &fair.moves[0]).is_some());
+
+        let drop = best_fair_drop(&gs, &weights, &AttackTable::default()).unwrap();
+        assert!(fair_play_inputs(&gs, &drop.moves[0]).is_some());
+        assert_eq!(
+            weighted_result_score(&drop.score, &drop.field, &weights),
+            weighted_result_score(&fair.score, &fair.field, &weights)
+        );
+    }
+
+    #[test]
+    fn spins_from_the_spawn() {
+        // A T slot at x = 4, covered on its left.
+        let field = Field::with_initial_occupied(
+            (0..consts::W)
+                .filter(|x| *x != 4)
+                .map(|x| Pos { x, y: 0 })
+                .chain([0, 1, 2, 6, 7, 8, 9].map(|x| Pos { x, y: 1 }))
+                .chain([0, 1, 2, 3].map(|x| Pos { x, y: 2 })),
+        );
+        let spin = reachable_placements(&field.make_bitmap_field(), Shape::T)
+            .into_iter()
+            .find(|p| {
+                p.tetromino.contains(&Pos { x: 3, y: 1 })
+                    && p.tetromino.contains(&Pos { x: 4, y: 0 })
+            })
+            .unwrap();
+
+        let mut gs = GameState::with_initial_state(vec![Shape::T; consts::NUM_PREVIEWS * 2], field);
+        let inputs = fair_play_inputs(&gs, &spin.movement).unwrap();
+        let results = gs.tick_mutation(inputs);
+        assert!(results.iter().any(|tr| matches!(
+            tr,
+            TickResult::Lock(LockResult::Ok {
+                spin: Spin::Full(Shape::T),
+                ..
+            })
+        )));
+    }
+}
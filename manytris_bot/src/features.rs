@@ -0,0 +1,236 @@
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use enum_iterator::Sequence;
+use enum_map::{Enum, EnumMap};
+use manytris_core::bitmap_field::BitmapField;
+use manytris_core::field_metrics::FieldMetrics;
+use serde::{Deserialize, Serialize};
+
+use crate::compute_types::MoveResultScore;
+
+/// Something about a searched position which the bot can weigh when ranking it.
+#[derive(
+    Enum, Sequence, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// 1 if the moves topped out, -1 if not.
+    GameOver,
+    LinesCleared,
+    /// Height of the tallest column.
+    Height,
+    /// Empty cells with an occupied cell somewhere above them.
+    Covered,
+    /// Garbage lines sent. Only searches which play out the attack table score it.
+    Attack,
+    Bumpiness,
+    WellDepth,
+    RowTransitions,
+    ColumnTransitions,
+    TSlots,
+    /// Rows a single piece could clear, to keep a combo going.
+    ComboPotential,
+    /// Difference between the tallest and shortest columns.
+    HeightDifferential,
+}
+
+/// How much each feature counts towards a position's score. Saved as a map from feature names to
+/// weights, where missing features weigh nothing, so profiles keep loading as features are added.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "BTreeMap<Feature, f32>", into = "BTreeMap<Feature, f32>")]
+pub struct WeightProfile {
+    weights: EnumMap<Feature, f32>,
+}
+
+impl Feature {
+    fn value<'a>(&self, mrs: &MoveResultScore, metrics: impl FnOnce() -> &'a FieldMetrics) -> f32 {
+        use Feature::*;
+        match self {
+            GameOver => {
+                if mrs.is_game_over() {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            LinesCleared => mrs.lines_cleared as f32,
+            Height => mrs.height as f32,
+            Covered => mrs.covered as f32,
+            Attack => mrs.attack as f32,
+            Bumpiness => metrics().bumpiness as f32,
+            WellDepth => metrics().well_depth as f32,
+            RowTransitions => metrics().row_transitions as f32,
+            ColumnTransitions => metrics().column_transitions as f32,
+            TSlots => metrics().t_slots as f32,
+            ComboPotential => metrics().near_full_rows as f32,
+            HeightDifferential => metrics().height_differential as f32,
+        }
+    }
+}
+
+impl WeightProfile {
+    pub fn new(weights: impl IntoIterator<Item = (Feature, f32)>) -> Self {
+        let mut profile = Self::default();
+        for (feature, weight) in weights {
+            profile.weights[feature] = weight;
+        }
+        profile
+    }
+
+    /// Trained for survival against an endless stream of pieces, so attack isn't weighted.
+    pub fn survival() -> Self {
+        use Feature::*;
+        Self::new([
+            (GameOver, -2447.9722),
+            (LinesCleared, 7782.121),
+            (Height, -6099.498),
+            (Covered, -1970.1172),
+        ])
+    }
+
+    /// The survival weights, also valuing attack for versus play. The attack weight was picked by
+    /// hand, not trained.
+    pub fn versus() -> Self {
+        let mut profile = Self::survival();
+        profile.weights[Feature::Attack] = 2000.0;
+        profile
+    }
+
+    /// A built in profile by name, "survival" or "versus", or else a JSON profile from the path.
+    pub fn load(name: &str) -> Result<Self> {
+        Ok(match name {
+            "survival" => Self::survival(),
+            "versus" => Self::versus(),
+            path => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+        })
+    }
+
+    pub fn weight(&self, feature: Feature) -> f32 {
+        self.weights[feature]
+    }
+
+    /// Features with a non-zero weight.
+    pub fn weighted_features(&self) -> impl Iterator<Item = Feature> + '_ {
+        self.weights
+            .iter()
+            .filter(|(_, w)| **w != 0.0)
+            .map(|(feature, _)| feature)
+    }
+
+    /// The same profile with each weight transformed.
+    pub fn map(&self, mut f: impl FnMut(Feature, f32) -> f32) -> Self {
+        Self::new(
+            self.weights
+                .iter()
+                .map(|(feature, w)| (feature, f(feature, *w))),
+        )
+    }
+
+    /// Every feature's part in the position's score, including the unweighted ones.
+    pub fn explain(&self, mrs: &MoveResultScore, field: &BitmapField) -> Vec<FeatureScore> {
+        feature_values(mrs, field)
+            .into_iter()
+            .map(|(feature, value)| FeatureScore {
+                feature,
+                value,
+                weight: self.weight(feature),
+            })
+            .collect()
+    }
+
+    /// The position's score under these weights. The field is only measured if a weighted feature
+    /// needs it.
+    pub fn score(&self, mrs: &MoveResultScore, field: &BitmapField) -> f32 {
+        let metrics = OnceCell::new();
+        self.weighted_features()
+            .map(|feature| {
+                let value =
+                    feature.value(mrs, || metrics.get_or_init(|| FieldMetrics::compute(field)));
+                value * self.weight(feature)
+            })
+            .sum()
+    }
+}
+
+/// Every feature's value for a position, for explaining its score.
+pub fn feature_values(mrs: &MoveResultScore, field: &BitmapField) -> EnumMap<Feature, f32> {
+    let metrics = FieldMetrics::compute(field);
+    EnumMap::from_fn(|feature: Feature| feature.value(mrs, || &metrics))
+}
+
+/// How much one feature counted towards a position's score.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct FeatureScore {
+    pub feature: Feature,
+    pub value: f32,
+    pub weight: f32,
+}
+
+impl FeatureScore {
+    pub fn contribution(&self) -> f32 {
+        self.value * self.weight
+    }
+}
+
+impl From<BTreeMap<Feature, f32>> for WeightProfile {
+    fn from(weights: BTreeMap<Feature, f32>) -> Self {
+        Self::new(weights)
+    }
+}
+
+impl From<WeightProfile> for BTreeMap<Feature, f32> {
+    fn from(profile: WeightProfile) -> Self {
+        profile.weights.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use manytris_core::field::{Field, Pos};
+
+    #[test]
+    fn profiles_load_with_missing_features() {
+        let profile: WeightProfile =
+            serde_json::from_str(r#"{"lines_cleared": 2.0, "bumpiness": -1.5}"#).unwrap();
+        assert_eq!(profile.weight(Feature::LinesCleared), 2.0);
+        assert_eq!(profile.weight(Feature::Bumpiness), -1.5);
+        assert_eq!(profile.weight(Feature::Height), 0.0);
+
+        let saved = serde_json::to_string(&WeightProfile::versus()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<WeightProfile>(&saved).unwrap(),
+            WeightProfile::versus()
+        );
+    }
+
+    #[test]
+    fn scores_field_features() {
+        // Two columns, four and one tall.
+        let field = Field::with_initial_occupied(
+            (0..4).map(|y| Pos { x: 0, y }).chain([Pos { x: 1, y: 0 }]),
+        )
+        .make_bitmap_field();
+        let mrs = MoveResultScore::init(false, 0, 4, 0);
+
+        let profile = WeightProfile::new([(Feature::Height, 1.0), (Feature::Bumpiness, 10.0)]);
+        // Bumpiness is 3 between the columns, then 1 down to the floor.
+        assert_eq!(profile.score(&mrs, &field), 4.0 + 10.0 * 4.0);
+        assert_eq!(
+            feature_values(&mrs, &field)[Feature::HeightDifferential],
+            4.0
+        );
+
+        let explained = profile.explain(&mrs, &field);
+        assert_eq!(explained.len(), enum_iterator::cardinality::<Feature>());
+        assert_eq!(
+            explained
+                .iter()
+                .map(FeatureScore::contribution)
+                .sum::<f32>(),
+            profile.score(&mrs, &field)
+        );
+    }
+}
@@ -1,33 +1,82 @@
+pub mod backend;
 pub mod bot_cpu;
 pub mod bot_player;
 pub mod bot_start_positions;
 pub mod compute_types;
+pub mod fair_play;
+pub mod features;
+pub mod placements;
+pub mod simulation;
 
 use anyhow::Result;
 use bot_player::MovementDescriptor;
 use compute_types::{ComputedDropConfig, MoveResultScore, UpcomingShapes};
+use features::{Feature, WeightProfile};
 use manytris_core::{
+    attack::{AttackState, AttackTable},
     bitmap_field::BitmapField,
     consts,
-    field::Pos,
+    field_metrics::FieldMetrics,
     game_state::{GameState, LockResult, TickResult},
 };
+use std::time::{Duration, Instant};
 
 pub trait BotResults {
     fn configs(&self) -> &[ComputedDropConfig];
     fn scores(&self) -> &[MoveResultScore];
     fn fields(&self) -> &[BitmapField];
+
+    /// The move of a config, for searches whose moves can't all be described by a config. None
+    /// if it's a plain drop.
+    fn movement(&self, _config_idx: usize) -> Option<MovementDescriptor> {
+        None
+    }
 }
 
+/// Searches for the best drops from a game state.
+///
+/// The CPU searches play by the game's rules from its state, so its queued garbage lands during
+/// the search as it would in play. The GPU searches only see the field.
 pub trait BotContext {
     type ResultType: BotResults;
 
+    /// `weights` are what the results will be ranked by, for searches which prune as they go.
     fn compute_drop_search(
         &self,
         search_depth: usize,
         upcoming_shapes: &UpcomingShapes,
         source_state: &GameState,
+        weights: &WeightProfile,
     ) -> Result<Self::ResultType>;
+
+    /// Like `compute_drop_search`, but giving up with None once `deadline` passes. Searches which
+    /// can't be stopped part way, like the GPU searches, run to the end.
+    fn compute_drop_search_until(
+        &self,
+        search_depth: usize,
+        upcoming_shapes: &UpcomingShapes,
+        source_state: &GameState,
+        weights: &WeightProfile,
+        deadline: Instant,
+    ) -> Result<Option<Self::ResultType>> {
+        let results =
+            self.compute_drop_search(search_depth, upcoming_shapes, source_state, weights)?;
+        Ok((Instant::now() <= deadline).then_some(results))
+    }
+
+    /// Features the search's results can be ranked on. Every search returns the fields it
+    /// reaches, but only searches which play out the attack table score attack.
+    fn supported_features(&self) -> Vec<Feature> {
+        enum_iterator::all()
+            .filter(|f| *f != Feature::Attack)
+            .collect()
+    }
+
+    /// The search holds as it goes, at every depth, rather than only dropping the tetrominoes in
+    /// order. Callers search from after holding as well for searches which don't.
+    fn searches_hold(&self) -> bool {
+        false
+    }
 }
 
 pub fn num_outputs(search_depth: usize) -> usize {
@@ -52,72 +101,71 @@ where
     }
 }
 
+/// Play the moves in order, and score where they end up. Queued garbage lands as it would in the
+/// game, and attack is scored under the attack table, carrying on the game's combo and
+/// back-to-back.
 pub fn evaluate_moves_cpu(
     src_state: &GameState,
     moves: &[MovementDescriptor],
+    attack_table: &AttackTable,
 ) -> (GameState, MoveResultScore, BitmapField) {
     let mut gs = src_state.clone();
+    let mut attack_state = src_state.attack_state().clone();
     let mut game_over = false;
     let mut lines_cleared = 0;
+    let mut attack = 0;
 
     moves.iter().for_each(|md| {
-        let tick_results = gs.tick_mutation(md.as_tick_mutations());
-        for tr in tick_results {
-            match tr {
-                TickResult::Lock(LockResult::GameOver) => {
-                    game_over = true;
-                }
-                TickResult::Lock(LockResult::Ok { lines_cleared: lc }) => {
-                    lines_cleared += lc as u8;
-                }
-                _ => {}
-            }
-        }
+        let (move_game_over, move_lines_cleared, move_attack) =
+            apply_move_cpu(&mut gs, md, attack_table, &mut attack_state);
+        game_over |= move_game_over;
+        lines_cleared += move_lines_cleared;
+        attack = u8::saturating_add(attack, move_attack);
     });
     let cpu_field = gs.make_bitmap_field();
-    let height = find_height(&cpu_field) as u8;
-    let covered = find_covered(&cpu_field, height as i32) as u16;
-    let score = MoveResultScore::init(game_over, lines_cleared, height, covered);
+    let score = score_field_cpu(game_over, lines_cleared, attack, &cpu_field);
 
     (gs, score, cpu_field)
 }
 
-fn find_height(cf: &BitmapField) -> i32 {
-    for y in 0..consts::H {
-        let mut empty_row = true;
-        for x in 0..consts::W {
-            if cf.occupied(&Pos { x, y }) {
-                empty_row = false;
-                break;
-            }
-        }
-        if empty_row {
-            return y;
+/// Play one move, returning whether it topped out, the lines it cleared and the attack it sent.
+fn apply_move_cpu(
+    gs: &mut GameState,
+    md: &MovementDescriptor,
+    attack_table: &AttackTable,
+    attack_state: &mut AttackState,
+) -> (bool, u8, u8) {
+    let mut game_over = false;
+    let mut lines_cleared = 0;
+    let mut attack = 0;
+    for tr in gs.tick_mutation(md.as_tick_mutations()) {
+        if let TickResult::Lock(lr) = &tr {
+            // Searches don't know the play time, so ignore the attack multiplier.
+            let sent = attack_state.record_lock(attack_table, lr, Duration::ZERO);
+            attack = u8::saturating_add(attack, sent.try_into().unwrap_or(u8::MAX));
         }
-    }
-    consts::H
-}
-
-fn find_covered(cf: &BitmapField, height: i32) -> i32 {
-    let mut count = 0;
-    for x in 0..consts::W {
-        let mut y = height - 1;
-        // Find the top of this column
-        while y > 0 {
-            if cf.occupied(&Pos { x, y }) {
-                break;
+        match tr {
+            TickResult::Lock(LockResult::GameOver) => {
+                game_over = true;
             }
-            y -= 1;
-        }
-
-        // Count the holes
-        y -= 1;
-        while y >= 0 {
-            if !cf.occupied(&Pos { x, y }) {
-                count += 1;
+            TickResult::Lock(LockResult::Ok {
+                lines_cleared: lc, ..
+            }) => {
+                lines_cleared += lc as u8;
             }
-            y -= 1;
+            _ => {}
         }
     }
-    count
+    (game_over, lines_cleared, attack)
+}
+
+fn score_field_cpu(
+    game_over: bool,
+    lines_cleared: u8,
+    attack: u8,
+    field: &BitmapField,
+) -> MoveResultScore {
+    let metrics = FieldMetrics::compute(field);
+    MoveResultScore::init(game_over, lines_cleared, metrics.max_height, metrics.holes)
+        .with_attack(attack)
 }
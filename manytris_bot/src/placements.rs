@@ -0,0 +1,225 @@
+use std::collections::{HashSet, VecDeque};
+
+use manytris_core::bitmap_field::BitmapField;
+use manytris_core::game_state::{DownType, TickMutation};
+use manytris_core::shapes::{Rot, Shape, Shift};
+use manytris_core::tetromino::Tetromino;
+
+use crate::bot_player::MovementDescriptor;
+use crate::bot_start_positions::START_POSITIONS;
+
+/// Inputs which move a tetromino after its shifts from the start position, to reach places a
+/// hard drop can't.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TuckInput {
+    SoftDrop,
+    Shift(Shift),
+    Rotate(Rot),
+}
+
+/// A position a tetromino can lock in, and how to get it there.
+#[derive(Clone, Debug)]
+pub struct Placement {
+    pub tetromino: Tetromino,
+    pub movement: MovementDescriptor,
+}
+
+pub const TUCK_INPUTS: [TuckInput; 5] = [
+    TuckInput::SoftDrop,
+    TuckInput::Shift(Shift::Left),
+    TuckInput::Shift(Shift::Right),
+    TuckInput::Rotate(Rot::Cw),
+    TuckInput::Rotate(Rot::Ccw),
+];
+
+impl TuckInput {
+    pub fn as_tick_mutation(&self) -> TickMutation {
+        match self {
+            // Repeated soft drops don't lock the tetromino when it lands.
+            TuckInput::SoftDrop => TickMutation::DownInput(DownType::HoldRepeat),
+            TuckInput::Shift(dir) => TickMutation::ShiftInput(*dir),
+            TuckInput::Rotate(dir) => TickMutation::RotateInput(*dir),
+        }
+    }
+
+    /// Where the input moves the tetromino, or None if it can't move.
+    pub fn apply(&self, field: &BitmapField, t: &Tetromino) -> Option<Tetromino> {
+        match self {
+            TuckInput::SoftDrop => t.down().filter(|t| field.is_valid(t)),
+            TuckInput::Shift(dir) => t.shift(*dir).filter(|t| field.is_valid(t)),
+            // The first kick which fits, like the game.
+            TuckInput::Rotate(dir) => t
+                .rotation_options(*dir)
+                .into_iter()
+                .find(|t| field.is_valid(t)),
+        }
+    }
+}
+
+/// Every position the shape can lock in on the field, starting from its bot start positions.
+///
+/// Hard drops come first. Each other lock position is listed once, with the shortest tuck that
+/// reaches it, except that being spun into place counts as a different placement from sliding.
+pub fn reachable_placements(field: &BitmapField, shape: Shape) -> Vec<Placement> {
+    let mut placements = vec![];
+    let mut placed = HashSet::new();
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+
+    for cw_rotations in 0..4 {
+        let start = START_POSITIONS
+            .bot_start_position(shape, cw_rotations)
+            .clone();
+        if !field.is_valid(&start) {
+            continue;
+        }
+        for (dir, sign) in [(Shift::Left, -1), (Shift::Right, 1)] {
+            let mut t = start.clone();
+            let mut shifts = 0;
+            loop {
+                let movement = MovementDescriptor {
+                    hold: false,
+                    shape,
+                    cw_rotations,
+                    shifts_right: shifts * sign,
+                    tuck: vec![],
+                };
+                let dropped = hard_drop(field, &t);
+                if placed.insert((lock_cells(&dropped), false)) {
+                    placements.push(Placement {
+                        tetromino: dropped,
+                        movement: movement.clone(),
+                    });
+                }
+                if visited.insert((t.clone(), false)) {
+                    queue.push_back((t.clone(), movement));
+                }
+
+                match TuckInput::Shift(dir).apply(field, &t) {
+                    Some(next) => t = next,
+                    None => break,
+                }
+                shifts += 1;
+            }
+        }
+    }
+
+    while let Some((t, movement)) = queue.pop_front() {
+        for input in TUCK_INPUTS {
+            let Some(next) = input.apply(field, &t) else {
+                continue;
+            };
+            let spun = matches!(input, TuckInput::Rotate(_));
+            if !visited.insert((next.clone(), spun)) {
+                continue;
+            }
+            let mut next_movement = movement.clone();
+            next_movement.tuck.push(input);
+
+            let landed = TuckInput::SoftDrop.apply(field, &next).is_none();
+            if landed && placed.insert((lock_cells(&next), spun)) {
+                placements.push(Placement {
+                    tetromino: next.clone(),
+                    movement: next_movement.clone(),
+                });
+            }
+            queue.push_back((next, next_movement));
+        }
+    }
+
+    placements
+}
+
+pub fn hard_drop(field: &BitmapField, t: &Tetromino) -> Tetromino {
+    let mut t = t.clone();
+    while let Some(next) = TuckInput::SoftDrop.apply(field, &t) {
+        t = next;
+    }
+    t
+}
+
+/// The tetromino's cells in a fixed order, to compare where tetrominoes lock.
+pub fn lock_cells(t: &Tetromino) -> [(i32, i32); 4] {
+    let mut cells = t.get_blocks().map(|p| (p.x, p.y));
+    cells.sort();
+    cells
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use manytris_core::consts;
+    use manytris_core::field::{Field, Pos};
+    use manytris_core::game_state::{GameState, LockResult, Spin, TickResult};
+
+    /// A roof over the left of an empty floor, two rows up.
+    fn roofed_field() -> Field {
+        Field::with_initial_occupied((0..7).map(|x| Pos { x, y: 2 }))
+    }
+
+    #[test]
+    fn placements_are_legal_input_paths() {
+        let field = roofed_field();
+        for shape in [Shape::O, Shape::T, Shape::I, Shape::S] {
+            for placement in reachable_placements(&field.make_bitmap_field(), shape) {
+                let mut gs = GameState::with_initial_state(
+                    vec![shape; consts::NUM_PREVIEWS * 2],
+                    field.clone(),
+                );
+                let results = gs.tick_mutation(placement.movement.as_tick_mutations());
+                assert!(results
+                    .iter()
+                    .any(|tr| matches!(tr, TickResult::Lock(LockResult::Ok { .. }))));
+                for p in placement.tetromino.get_blocks() {
+                    assert!(
+                        gs.field().get_occupied_block(&p).is_some(),
+                        "{placement:?} didn't lock at {p:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tucks_under_overhangs() {
+        let placements = reachable_placements(&roofed_field().make_bitmap_field(), Shape::O);
+        let under_roof = placements
+            .iter()
+            .find(|p| p.tetromino.get_blocks().iter().all(|b| b.x < 7 && b.y < 2))
+            .unwrap();
+        assert!(under_roof.movement.tuck.contains(&TuckInput::SoftDrop));
+    }
+
+    #[test]
+    fn spins_into_t_slots() {
+        // A T slot at x = 4, covered on its left.
+        let field = Field::with_initial_occupied(
+            (0..consts::W)
+                .filter(|x| *x != 4)
+                .map(|x| Pos { x, y: 0 })
+                .chain([0, 1, 2, 6, 7, 8, 9].map(|x| Pos { x, y: 1 }))
+                .chain([0, 1, 2, 3].map(|x| Pos { x, y: 2 })),
+        );
+        let spin = reachable_placements(&field.make_bitmap_field(), Shape::T)
+            .into_iter()
+            .find(|p| {
+                p.tetromino.contains(&Pos { x: 3, y: 1 })
+                    && p.tetromino.contains(&Pos { x: 4, y: 0 })
+            })
+            .unwrap();
+        assert!(matches!(
+            spin.movement.tuck.last(),
+            Some(TuckInput::Rotate(_))
+        ));
+
+        let mut gs = GameState::with_initial_state(vec![Shape::T; consts::NUM_PREVIEWS * 2], field);
+        let results = gs.tick_mutation(spin.movement.as_tick_mutations());
+        assert!(results.iter().any(|tr| matches!(
+            tr,
+            TickResult::Lock(LockResult::Ok {
+                spin: Spin::Full(Shape::T),
+                ..
+            })
+        )));
+    }
+}
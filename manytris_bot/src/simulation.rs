@@ -0,0 +1,149 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use manytris_core::attack::AttackTable;
+use manytris_core::consts;
+use manytris_core::game_state::{GameState, LockResult, TickMutation, TickResult};
+use manytris_core::shape_bag::ShapeBag;
+use serde::{Deserialize, Serialize};
+
+use crate::bot_player::select_next_move;
+use crate::features::WeightProfile;
+use crate::BotContext;
+
+/// A game for the bot to play out, for training and benchmarks.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameConfig {
+    /// The game stops here if the bot hasn't topped out.
+    pub max_pieces: usize,
+    /// Seeds the shapes dealt, so the same bot plays the same game every time.
+    pub seed: u64,
+    /// Garbage sent at the bot, as if by opponents.
+    pub garbage: Option<GarbagePressure>,
+    /// Rules for scoring the attack the bot sends.
+    pub attack_table: AttackTable,
+}
+
+/// Lines of garbage queued on the bot's field every `interval` pieces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GarbagePressure {
+    pub lines: usize,
+    pub interval: usize,
+}
+
+/// How a game played out by the bot went.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameResult {
+    pub pieces: usize,
+    pub lines_cleared: usize,
+    pub attack: usize,
+    pub topped_out: bool,
+    /// Time spent choosing moves.
+    pub search_time: Duration,
+}
+
+impl GameConfig {
+    pub fn solo(max_pieces: usize, seed: u64) -> Self {
+        Self {
+            max_pieces,
+            seed,
+            garbage: None,
+            attack_table: AttackTable::default(),
+        }
+    }
+}
+
+impl GameResult {
+    pub fn search_time_per_move(&self) -> Duration {
+        // The move which topped out was searched too.
+        let moves = self.pieces + usize::from(self.topped_out);
+        self.search_time / moves.max(1) as u32
+    }
+}
+
+/// Let the bot play the game until it tops out or has placed the configured pieces.
+pub fn play_game(
+    ctx: &impl BotContext,
+    weights: &WeightProfile,
+    search_depth: usize,
+    config: &GameConfig,
+) -> Result<GameResult> {
+    let mut shape_bag = ShapeBag::seeded(config.seed);
+    let mut gs = GameState::new(shape_bag.by_ref().take(consts::NUM_PREVIEWS * 2).collect());
+    let mut result = GameResult {
+        pieces: 0,
+        lines_cleared: 0,
+        attack: 0,
+        topped_out: false,
+        search_time: Duration::ZERO,
+    };
+
+    while result.pieces < config.max_pieces {
+        if let Some(garbage) = config.garbage {
+            // Before every interval'th piece.
+            let interval = garbage.interval.max(1);
+            if result.pieces % interval == interval - 1 {
+                let _ = gs.tick_mutation(vec![TickMutation::EnqueueGarbage(garbage.lines)]);
+            }
+        }
+
+        let search_start = Instant::now();
+        let mr = select_next_move(&gs, ctx, weights, search_depth)?;
+        result.search_time += search_start.elapsed();
+
+        let mut attack_state = gs.attack_state().clone();
+        for tr in gs.tick_mutation(mr.moves[0].as_tick_mutations()) {
+            let TickResult::Lock(lr) = tr else {
+                continue;
+            };
+            // Like the searches, play as if no time has passed for the attack multiplier.
+            result.attack += attack_state.record_lock(&config.attack_table, &lr, Duration::ZERO);
+            match lr {
+                LockResult::GameOver => result.topped_out = true,
+                LockResult::Ok { lines_cleared, .. } => {
+                    result.lines_cleared += lines_cleared as usize
+                }
+            }
+        }
+        if result.topped_out {
+            break;
+        }
+        result.pieces += 1;
+
+        let _ = gs.tick_mutation(vec![TickMutation::EnqueueTetromino(
+            shape_bag.next().unwrap(),
+        )]);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bot_cpu::CpuBotContext;
+
+    #[test]
+    fn seeded_games_repeat() {
+        let ctx = CpuBotContext::default();
+        let play = |config: &GameConfig| {
+            let mut result = play_game(&ctx, &WeightProfile::survival(), 1, config).unwrap();
+            result.search_time = Duration::ZERO;
+            result
+        };
+
+        let solo = play(&GameConfig::solo(30, 46));
+        assert_eq!(solo.pieces, 30);
+        assert!(!solo.topped_out);
+        assert!(solo.lines_cleared > 0);
+        assert_eq!(play(&GameConfig::solo(30, 46)), solo);
+
+        let pressured = play(&GameConfig {
+            garbage: Some(GarbagePressure {
+                lines: 2,
+                interval: 3,
+            }),
+            ..GameConfig::solo(30, 46)
+        });
+        assert_ne!(pressured, solo);
+    }
+}
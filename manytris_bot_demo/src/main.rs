@@ -5,9 +5,10 @@ use std::time::Instant;
 
 use anyhow::{Context, Result};
 use genetic_algorithm::strategy::evolve::prelude::*;
-use manytris_bot::bot_player::ScoringKs;
+use manytris_bot::features::{Feature, WeightProfile};
 use manytris_bot::{bot_player, BotContext};
 use manytris_bot_metal::BotShaderContext;
+use manytris_core::attack::AttackTable;
 use manytris_core::consts;
 use manytris_core::game_state::{GameState, TickMutation};
 use manytris_core::shape_bag::ShapeBag;
@@ -22,7 +23,7 @@ pub fn main() -> Result<()> {
         for _ in 0..4 {
             println!(
                 "Game results {:?}",
-                run_game(&bot_player::BEST_BOT_KS, 600, &metal_bot)
+                run_game(&WeightProfile::survival(), 600, &metal_bot)
             );
         }
     }
@@ -33,14 +34,15 @@ pub fn main() -> Result<()> {
         for _ in 0..4 {
             println!(
                 "Game results {:?}",
-                run_game(&bot_player::BEST_BOT_KS, 600, &metal_bot)
+                run_game(&WeightProfile::survival(), 600, &metal_bot)
             );
         }
     }
 
     println!("Start evolving...");
+    let features = BotShaderContext::new()?.supported_features();
     let genotype = ContinuousGenotype::builder()
-        .with_genes_size(4)
+        .with_genes_size(features.len())
         .with_allele_range(-10000.0..10000.0)
         .build()
         .unwrap();
@@ -52,6 +54,7 @@ pub fn main() -> Result<()> {
         .with_target_fitness_score(550)
         .with_fitness(GameFitness {
             context_ctor: || BotShaderContext::new().unwrap(),
+            features,
         })
         .with_fitness_ordering(FitnessOrdering::Maximize)
         .with_multithreading(true)
@@ -103,6 +106,8 @@ where
     F: Fn() -> C + Send + Sync + Clone,
 {
     context_ctor: F,
+    /// The feature weighted by each gene.
+    features: Vec<Feature>,
 }
 
 impl<C, F> Fitness for GameFitness<C, F>
@@ -116,7 +121,12 @@ where
         &mut self,
         chromosome: &Chromosome<Self::Genotype>,
     ) -> Option<FitnessValue> {
-        let ks: ScoringKs = chromosome.genes.clone().try_into().unwrap();
+        let ks = WeightProfile::new(
+            self.features
+                .iter()
+                .copied()
+                .zip(chromosome.genes.iter().copied()),
+        );
         let ctx = (self.context_ctor)();
         Some(evaluate_ks(&ks, &ctx))
     }
@@ -140,11 +150,12 @@ where
     fn clone(&self) -> Self {
         Self {
             context_ctor: self.context_ctor.clone(),
+            features: self.features.clone(),
         }
     }
 }
 
-fn evaluate_ks(ks: &ScoringKs, bot_context: &impl BotContext) -> FitnessValue {
+fn evaluate_ks(ks: &WeightProfile, bot_context: &impl BotContext) -> FitnessValue {
     let num_games = 10;
     let mut worst_score = 600;
     for _ in 0..num_games {
@@ -165,7 +176,7 @@ struct RunGameResults {
 }
 
 fn run_game(
-    ks: &ScoringKs,
+    ks: &WeightProfile,
     max_game_length: usize,
     bot_context: &impl BotContext,
 ) -> RunGameResults {
@@ -185,7 +196,8 @@ fn run_game(
         game_length += 1;
 
         // Evaluate 1 move on the best result.
-        (gs, _, _) = manytris_bot::evaluate_moves_cpu(&gs, &mr.moves[0..1]);
+        (gs, _, _) =
+            manytris_bot::evaluate_moves_cpu(&gs, &mr.moves[0..1], &AttackTable::default());
         gs.tick_mutation(vec![TickMutation::EnqueueTetromino(
             shape_bag.next().unwrap(),
         )]);
@@ -3,6 +3,7 @@
 use anyhow::{Context, Result};
 use manytris_bot::bot_cpu::CpuBotContext;
 use manytris_bot::compute_types::{ComputedDropConfig, MoveResultScore};
+use manytris_bot::features::{Feature, WeightProfile};
 use manytris_bot::{BotContext, BotResults};
 use manytris_bot_metal::BotShaderContext;
 use manytris_bot_vulkan::VulkanBotContext;
@@ -24,22 +25,39 @@ macro_rules! assert_lists_eq {
 
 #[test]
 fn verify_metal_consistent_moves() -> Result<()> {
-    verify_consistent_moves(BotShaderContext::new()?)
+    verify_consistent_moves(BotShaderContext::new()?, Field::default())
 }
 
 #[test]
 fn verify_vulkan_consistent_moves() -> Result<()> {
-    verify_consistent_moves(VulkanBotContext::init()?)
+    verify_consistent_moves(VulkanBotContext::init()?, Field::default())
 }
 
-fn verify_consistent_moves(compare_ctx: impl BotContext) -> Result<()> {
-    let cpu_ctx = CpuBotContext;
+#[test]
+fn verify_metal_consistent_multi_line_clears() -> Result<()> {
+    verify_consistent_moves(BotShaderContext::new()?, multi_line_clear_field())
+}
+
+#[test]
+fn verify_vulkan_consistent_multi_line_clears() -> Result<()> {
+    verify_consistent_moves(VulkanBotContext::init()?, multi_line_clear_field())
+}
+
+/// Four rows full but for the right column, so an I can clear up to all four.
+fn multi_line_clear_field() -> Field {
+    Field::with_initial_occupied((0..4).flat_map(|y| (0..consts::W - 1).map(move |x| Pos { x, y })))
+}
+
+fn verify_consistent_moves(compare_ctx: impl BotContext, field: Field) -> Result<()> {
+    let cpu_ctx = CpuBotContext::default();
 
     let shapes = [Shape::I; 7];
 
-    let source_state = GameState::new(shapes.into());
-    let metal_results = compare_ctx.compute_drop_search(2, &shapes, &source_state)?;
-    let cpu_results = cpu_ctx.compute_drop_search(2, &shapes, &source_state)?;
+    let source_state = GameState::with_initial_state(shapes.into(), field);
+    let metal_results =
+        compare_ctx.compute_drop_search(2, &shapes, &source_state, &WeightProfile::default())?;
+    let cpu_results =
+        cpu_ctx.compute_drop_search(2, &shapes, &source_state, &WeightProfile::default())?;
 
     assert_lists_eq!(cpu_results.configs(), metal_results.configs());
 
@@ -53,20 +71,33 @@ fn verify_consistent_moves(compare_ctx: impl BotContext) -> Result<()> {
     }
 
     assert_lists_eq!(cpu_results.fields(), metal_results.fields());
-    assert_lists_eq!(cpu_results.scores(), metal_results.scores());
+
+    // Searches which don't play out the attack table leave attack zero.
+    let scores_attack = compare_ctx.supported_features().contains(&Feature::Attack);
+    let cpu_scores: Vec<_> = cpu_results
+        .scores()
+        .iter()
+        .map(|s| if scores_attack { *s } else { s.with_attack(0) })
+        .collect();
+    assert_lists_eq!(cpu_scores, metal_results.scores());
 
     Ok(())
 }
 
 #[test]
 fn verify_search_depth() -> Result<()> {
-    let ctx = CpuBotContext;
+    let ctx = CpuBotContext::default();
 
     use Shape::I;
     let upcoming_shapes = [I, I, I, I, I, I, I];
     let source_state = GameState::new(upcoming_shapes.into());
     {
-        let result = ctx.compute_drop_search(0, &upcoming_shapes, &source_state)?;
+        let result = ctx.compute_drop_search(
+            0,
+            &upcoming_shapes,
+            &source_state,
+            &WeightProfile::default(),
+        )?;
 
         assert_eq!(result.fields().len(), 1);
         assert_eq!(result.configs().len(), 0);
@@ -74,7 +105,12 @@ fn verify_search_depth() -> Result<()> {
     }
 
     {
-        let result = ctx.compute_drop_search(1, &upcoming_shapes, &source_state)?;
+        let result = ctx.compute_drop_search(
+            1,
+            &upcoming_shapes,
+            &source_state,
+            &WeightProfile::default(),
+        )?;
 
         assert_eq!(result.fields().len(), 41);
         assert_eq!(result.configs().len(), 40);
@@ -82,7 +118,12 @@ fn verify_search_depth() -> Result<()> {
     }
 
     {
-        let result = ctx.compute_drop_search(2, &upcoming_shapes, &source_state)?;
+        let result = ctx.compute_drop_search(
+            2,
+            &upcoming_shapes,
+            &source_state,
+            &WeightProfile::default(),
+        )?;
 
         assert_eq!(result.fields().len(), 1641);
         assert_eq!(result.configs().len(), 1640);
@@ -94,7 +135,7 @@ fn verify_search_depth() -> Result<()> {
 
 #[test]
 fn verify_clear_lines_cpu() -> Result<()> {
-    verify_clear_lines(CpuBotContext)
+    verify_clear_lines(CpuBotContext::default())
 }
 
 #[test]
@@ -115,7 +156,7 @@ fn verify_clear_lines(ctx: impl BotContext) -> Result<()> {
         Field::with_initial_occupied((0..9).map(|x| Pos { x, y: 0 })),
     );
 
-    let result = ctx.compute_drop_search(1, &upcoming_shapes, &gs)?;
+    let result = ctx.compute_drop_search(1, &upcoming_shapes, &gs, &WeightProfile::default())?;
 
     {
         let score = find_score(&result, |cfg| {
@@ -134,7 +175,7 @@ fn verify_clear_lines(ctx: impl BotContext) -> Result<()> {
 
 #[test]
 fn verify_covered_lines_cpu() -> Result<()> {
-    verify_covered_lines(CpuBotContext)
+    verify_covered_lines(CpuBotContext::default())
 }
 
 #[test]
@@ -155,7 +196,7 @@ fn verify_covered_lines(ctx: impl BotContext) -> Result<()> {
         Field::with_initial_occupied((0..9).map(|x| Pos { x, y: 0 })),
     );
 
-    let result = ctx.compute_drop_search(1, &upcoming_shapes, &gs)?;
+    let result = ctx.compute_drop_search(1, &upcoming_shapes, &gs, &WeightProfile::default())?;
 
     {
         let score = find_score(&result, |cfg| cfg.cw_rotations == 0 && cfg.left_shifts == 4)?;
@@ -173,7 +214,7 @@ fn verify_covered_lines(ctx: impl BotContext) -> Result<()> {
 
 #[test]
 fn verify_game_over_cpu() -> Result<()> {
-    verify_game_over(CpuBotContext)
+    verify_game_over(CpuBotContext::default())
 }
 
 #[test]
@@ -194,7 +235,7 @@ fn verify_game_over(ctx: impl BotContext) -> Result<()> {
         Field::with_initial_occupied((0..consts::MAX_H).map(|y| Pos { x: 4, y })),
     );
 
-    let result = ctx.compute_drop_search(1, &upcoming_shapes, &gs)?;
+    let result = ctx.compute_drop_search(1, &upcoming_shapes, &gs, &WeightProfile::default())?;
 
     // all moves should be game over
     for score in result.scores() {
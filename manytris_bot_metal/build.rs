@@ -0,0 +1,24 @@
+//! The Metal library is compiled by `src/build_shader.sh`, which needs Xcode, and checked in.
+//! Fail the build if the shader source has changed since, rather than running a stale library.
+
+use std::fs;
+
+use sha2::{Digest, Sha256};
+
+const SOURCE: &str = "src/bot_shader.metal";
+const STAMP: &str = "src/bot_shader.metallib.sha256";
+
+fn main() {
+    println!("cargo:rerun-if-changed={SOURCE}");
+    println!("cargo:rerun-if-changed={STAMP}");
+
+    let source = fs::read(SOURCE).expect("Unable to read the shader source");
+    let digest = format!("{:x}", Sha256::digest(source));
+    let stamp = fs::read_to_string(STAMP).unwrap_or_default();
+    if stamp.trim() != digest {
+        panic!(
+            "{SOURCE} has changed since bot_shader.metallib was compiled. \
+            Run build_shader.sh from src/ on macOS and commit the new library."
+        );
+    }
+}
@@ -5,3 +5,5 @@ set -e
 xcrun --sdk macosx metal -o bot_shader.air -c bot_shader.metal
 xcrun --sdk macosx metallib -o bot_shader.metallib bot_shader.air
 
+# build.rs checks the library was compiled from the current source.
+shasum -a 256 bot_shader.metal | cut -d ' ' -f 1 > bot_shader.metallib.sha256
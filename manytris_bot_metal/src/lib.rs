@@ -4,9 +4,11 @@ use manytris_bot::bot_start_positions::START_POSITIONS;
 use manytris_bot::compute_types::{
     ComputedDropConfig, MoveResultScore, SearchParams, ShapePositionConfig, UpcomingShapes,
 };
+use manytris_bot::features::WeightProfile;
 use manytris_bot::{BotContext, BotResults};
 use manytris_core::bitmap_field::BitmapField;
 use manytris_core::consts;
+use manytris_core::field::Pos;
 use manytris_core::game_state::GameState;
 use metal::objc::rc::autoreleasepool;
 use metal::{
@@ -17,14 +19,21 @@ use std::cmp::min;
 
 use std::slice;
 
+/// The shader's field layout: one bit per block, row by row from the bottom. The shader still
+/// packs fields this way, not in `BitmapField`'s row masks, until `bot_shader.metallib` is
+/// rebuilt on macOS, so fields are converted on the way in and out.
+const SHADER_FIELD_BYTES: usize = consts::NUM_POSITIONS.div_ceil(8);
+
+type ShaderField = [u8; SHADER_FIELD_BYTES];
+
 pub struct BotShaderContext {
     kc: KernalConfig,
 }
 
 pub struct MetalBotResults {
     configs_buffer: Buffer,
-    scores_buffer: Buffer,
-    fields_buffer: Buffer,
+    scores: Vec<MoveResultScore>,
+    fields: Vec<BitmapField>,
 }
 
 struct KernalConfig {
@@ -113,10 +122,10 @@ impl BotResults for MetalBotResults {
         slice_from_buffer::<ComputedDropConfig>(&self.configs_buffer)
     }
     fn scores(&self) -> &[MoveResultScore] {
-        slice_from_buffer::<MoveResultScore>(&self.scores_buffer)
+        &self.scores
     }
     fn fields(&self) -> &[BitmapField] {
-        slice_from_buffer::<BitmapField>(&self.fields_buffer)
+        &self.fields
     }
 }
 
@@ -128,6 +137,7 @@ impl BotContext for BotShaderContext {
         search_depth: usize,
         upcoming_shapes: &UpcomingShapes,
         source_state: &GameState,
+        _weights: &WeightProfile,
     ) -> Result<MetalBotResults> {
         let total_outputs = manytris_bot::num_outputs(search_depth);
 
@@ -143,8 +153,12 @@ impl BotContext for BotShaderContext {
             &START_POSITIONS.shape_position_config,
         );
 
-        let mut fields_buffer = self.kc.make_data_buffer::<BitmapField>(total_outputs + 1);
-        write_to_buffer(&mut fields_buffer, 0, &source_state.make_bitmap_field());
+        let mut fields_buffer = self.kc.make_data_buffer::<ShaderField>(total_outputs + 1);
+        write_to_buffer(
+            &mut fields_buffer,
+            0,
+            &to_shader_field(&source_state.make_bitmap_field()),
+        );
 
         let scores_buffer = self.kc.make_data_buffer::<MoveResultScore>(total_outputs);
 
@@ -212,14 +226,49 @@ impl BotContext for BotShaderContext {
             })?;
         }
 
+        // The shader's scores leave attack as padding, so it's undefined.
+        let scores = slice_from_buffer::<MoveResultScore>(&scores_buffer)
+            .iter()
+            .map(|score| score.with_attack(0))
+            .collect();
+        let fields = slice_from_buffer::<ShaderField>(&fields_buffer)
+            .iter()
+            .map(from_shader_field)
+            .collect();
         Ok(MetalBotResults {
             configs_buffer,
-            scores_buffer,
-            fields_buffer,
+            scores,
+            fields,
         })
     }
 }
 
+fn to_shader_field(field: &BitmapField) -> ShaderField {
+    let mut bytes = [0; SHADER_FIELD_BYTES];
+    for y in 0..consts::MAX_H_US {
+        for x in 0..consts::W_US {
+            if field.row(y) & (1 << x) != 0 {
+                let bit_index = y * consts::W_US + x;
+                bytes[bit_index / 8] |= 1 << (bit_index % 8);
+            }
+        }
+    }
+    bytes
+}
+
+fn from_shader_field(bytes: &ShaderField) -> BitmapField {
+    let mut field = BitmapField::default();
+    for y in 0..consts::MAX_H {
+        for x in 0..consts::W {
+            let bit_index = (y * consts::W + x) as usize;
+            if bytes[bit_index / 8] & (1 << (bit_index % 8)) != 0 {
+                field.set(&Pos { x, y });
+            }
+        }
+    }
+    field
+}
+
 fn slice_from_buffer<T>(buffer: &Buffer) -> &[T] {
     let items = buffer.length() as usize / size_of::<T>();
     unsafe { slice::from_raw_parts(buffer.contents() as *const T, items) }
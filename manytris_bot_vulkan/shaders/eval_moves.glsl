@@ -4,8 +4,6 @@
 
 const uint W = 10;
 const uint H = 26;
-const uint NUM_BLOCKS = W*H;
-const uint FIELD_BYTES = NUM_BLOCKS / 8 + ((NUM_BLOCKS % 8 != 0) ? 1 : 0);
 const uint NUM_SHAPES = 7;
 
 const uint LEFT = 1;
@@ -21,14 +19,17 @@ struct ShapeStartingPositions {
     TetrominoPositions player_position;
 };
 
+// One row per entry, bit x set when column x is occupied.
 struct Field {
-    uint8_t bytes[FIELD_BYTES];
+    uint16_t rows[H];
 };
 
 struct MoveResultScore {
     uint8_t game_over;
     uint8_t lines_cleared;
     uint8_t height;
+    // The attack table isn't known here, so no attack is scored.
+    uint8_t attack;
     uint16_t covered;
 };
 
@@ -139,6 +140,7 @@ void main() {
 
     scores.scores[drop_config_idx].lines_cleared += new_lines_removed;
     scores.scores[drop_config_idx].height = max_height;
+    scores.scores[drop_config_idx].attack = uint8_t(0);
     scores.scores[drop_config_idx].covered = covered;
 
     TetrominoPositions next = spc.starting_positions[search_params.sp.upcoming_shape_idxs[cur_search_depth+1]].player_position;
@@ -180,23 +182,17 @@ bool try_shift(inout TetrominoPositions tps, uint shift, uint32_t field_idx) {
 }
 
 bool is_occupied(uint field_idx, uint8_t x, uint8_t y) {
-    uint bit_index = y * W + x;
-    uint byte_index = bit_index / 8;
-    uint offset = bit_index % 8;
-    uint8_t mask = uint8_t(1) << offset;
+    uint16_t mask = uint16_t(1u << uint(x));
 
-    return (fields.fields[field_idx].bytes[byte_index] & mask) != 0;
+    return (fields.fields[field_idx].rows[y] & mask) != 0;
 }
 
 void apply_position(uint field_idx, uint8_t x, uint8_t y, bool set) {
-    uint bit_index = y * W + x;
-    uint byte_index = bit_index / 8;
-    uint offset = bit_index % 8;
-    uint8_t mask = uint8_t(1) << offset;
+    uint16_t mask = uint16_t(1u << uint(x));
 
     if (set) {
-        fields.fields[field_idx].bytes[byte_index] |= mask;
+        fields.fields[field_idx].rows[y] |= mask;
     } else {
-        fields.fields[field_idx].bytes[byte_index] &= ~mask;
+        fields.fields[field_idx].rows[y] &= ~mask;
     }
 }
@@ -3,6 +3,7 @@ use manytris_bot::bot_start_positions::START_POSITIONS;
 use manytris_bot::compute_types::{
     ComputedDropConfig, MoveResultScore, SearchParams, ShapePositionConfig, UpcomingShapes,
 };
+use manytris_bot::features::WeightProfile;
 use manytris_bot::{BotContext, BotResults};
 use manytris_core::bitmap_field::BitmapField;
 use manytris_core::game_state::GameState;
@@ -296,6 +297,7 @@ impl BotContext for VulkanBotContext {
         search_depth: usize,
         upcoming_shapes: &UpcomingShapes,
         source_state: &GameState,
+        _weights: &WeightProfile,
     ) -> Result<VulkanBotResults> {
         let num_outputs = manytris_bot::num_outputs(search_depth);
         let num_groups = num_outputs / 64 + (if num_outputs % 64 == 0 { 0 } else { 1 });
@@ -488,7 +490,7 @@ mod test {
         let ctx = VulkanBotContext::init()?;
         let upcoming_shapes = [Shape::I; consts::MAX_SEARCH_DEPTH + 1];
         let gs = GameState::new(upcoming_shapes.into());
-        ctx.compute_drop_search(2, &upcoming_shapes, &gs)?;
+        ctx.compute_drop_search(2, &upcoming_shapes, &gs, &WeightProfile::default())?;
 
         Ok(())
     }
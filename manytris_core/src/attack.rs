@@ -0,0 +1,240 @@
+use crate::game_state::{LockResult, Spin};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Rules for converting locks into garbage lines sent to opponents.
+///
+/// The default table is the classic manytris table: 2 lines send 1, 3 send 2, 4 send 4, with no
+/// other bonuses.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AttackTable {
+    /// Garbage for clearing 0 to 4 lines without a spin.
+    pub line_clears: [u32; 5],
+    /// Garbage for full spins clearing 0 to 4 lines.
+    pub full_spins: [u32; 5],
+    /// Garbage for mini spins clearing 0 to 4 lines.
+    pub mini_spins: [u32; 5],
+    /// Extra garbage by combo count, starting from the first clear of a combo. The last entry
+    /// repeats for longer combos.
+    pub combo_bonus: Vec<u32>,
+    /// Extra garbage for a difficult clear (4 lines or a spin) following another difficult clear,
+    /// with no easier clears between them.
+    pub back_to_back_bonus: u32,
+    /// Extra garbage for leaving the field empty.
+    pub perfect_clear_bonus: u32,
+    pub multiplier: Option<AttackMultiplier>,
+}
+
+/// Scales all attacks up over the course of a game, to force long games to end.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct AttackMultiplier {
+    /// Play time before the multiplier starts growing from 1.
+    pub start_after: Duration,
+    pub increase_per_minute: f32,
+    pub max: f32,
+}
+
+/// Combo and back-to-back state of one game, needed to apply an [AttackTable].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AttackState {
+    combo: Option<usize>,
+    back_to_back: bool,
+}
+
+impl Default for AttackTable {
+    fn default() -> Self {
+        let line_clears = [0, 0, 1, 2, 4];
+        Self {
+            line_clears,
+            full_spins: line_clears,
+            mini_spins: line_clears,
+            combo_bonus: vec![],
+            back_to_back_bonus: 0,
+            perfect_clear_bonus: 0,
+            multiplier: None,
+        }
+    }
+}
+
+impl AttackTable {
+    /// Attack values in the style of modern guideline games.
+    pub fn guideline() -> Self {
+        Self {
+            line_clears: [0, 0, 1, 2, 4],
+            full_spins: [0, 2, 4, 6, 8],
+            mini_spins: [0, 0, 1, 2, 4],
+            combo_bonus: vec![0, 0, 1, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5],
+            back_to_back_bonus: 1,
+            perfect_clear_bonus: 10,
+            multiplier: None,
+        }
+    }
+
+    fn base_attack(&self, lines_cleared: i32, spin: Spin) -> u32 {
+        let idx = lines_cleared.clamp(0, 4) as usize;
+        match spin {
+            Spin::None => self.line_clears[idx],
+            Spin::Mini(_) => self.mini_spins[idx],
+            Spin::Full(_) => self.full_spins[idx],
+        }
+    }
+
+    fn combo_bonus(&self, combo: usize) -> u32 {
+        self.combo_bonus
+            .get(combo)
+            .or(self.combo_bonus.last())
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn multiplier(&self, play_time: Duration) -> f32 {
+        let Some(m) = &self.multiplier else {
+            return 1.0;
+        };
+        let minutes = play_time.saturating_sub(m.start_after).as_secs_f32() / 60.0;
+        (1.0 + minutes * m.increase_per_minute).min(m.max)
+    }
+}
+
+impl AttackState {
+    /// `combo` counts line clears in a row before the last one, so is 0 after one clear.
+    pub fn new(combo: Option<usize>, back_to_back: bool) -> Self {
+        Self {
+            combo,
+            back_to_back,
+        }
+    }
+
+    pub fn combo(&self) -> Option<usize> {
+        self.combo
+    }
+
+    /// True if the last clear was difficult, so the next difficult one gets the bonus.
+    pub fn back_to_back(&self) -> bool {
+        self.back_to_back
+    }
+
+    /// Update the combo and back-to-back state, and return the garbage lines sent by the lock.
+    pub fn record_lock(
+        &mut self,
+        table: &AttackTable,
+        lock_result: &LockResult,
+        play_time: Duration,
+    ) -> usize {
+        let back_to_back = self.back_to_back;
+        self.update(lock_result);
+
+        let LockResult::Ok {
+            lines_cleared,
+            spin,
+            perfect_clear,
+        } = lock_result
+        else {
+            return 0;
+        };
+
+        let mut attack = table.base_attack(*lines_cleared, *spin);
+
+        if *lines_cleared > 0 {
+            attack += table.combo_bonus(self.combo.unwrap_or(0));
+            if self.back_to_back && back_to_back {
+                attack += table.back_to_back_bonus;
+            }
+            if *perfect_clear {
+                attack += table.perfect_clear_bonus;
+            }
+        }
+
+        (attack as f32 * table.multiplier(play_time)).floor() as usize
+    }
+
+    /// Update the combo and back-to-back state for the lock, without scoring it.
+    pub fn update(&mut self, lock_result: &LockResult) {
+        match lock_result {
+            LockResult::Ok {
+                lines_cleared,
+                spin,
+                ..
+            } if *lines_cleared > 0 => {
+                self.combo = Some(self.combo.map_or(0, |c| c + 1));
+                self.back_to_back = *lines_cleared >= 4 || *spin != Spin::None;
+            }
+            _ => self.combo = None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shapes::Shape;
+
+    fn lock(lines_cleared: i32, spin: Spin) -> LockResult {
+        LockResult::Ok {
+            lines_cleared,
+            spin,
+            perfect_clear: false,
+        }
+    }
+
+    fn attacks(table: &AttackTable, locks: &[LockResult]) -> Vec<usize> {
+        let mut state = AttackState::default();
+        locks
+            .iter()
+            .map(|lr| state.record_lock(table, lr, Duration::ZERO))
+            .collect()
+    }
+
+    #[test]
+    fn classic_table() {
+        let table = AttackTable::default();
+        let locks: Vec<_> = (0..=4).map(|n| lock(n, Spin::None)).collect();
+        assert_eq!(attacks(&table, &locks), vec![0, 0, 1, 2, 4]);
+    }
+
+    #[test]
+    fn combo_and_back_to_back() {
+        let table = AttackTable::guideline();
+        let t_spin = Spin::Full(Shape::T);
+        let locks = [
+            lock(4, Spin::None),
+            lock(2, t_spin),
+            lock(1, Spin::None),
+            lock(0, Spin::None),
+            lock(4, Spin::None),
+        ];
+        // Tetris, then TSD with b2b, then a single breaking b2b with combo 2, then a tetris
+        // without a combo or b2b.
+        assert_eq!(attacks(&table, &locks), vec![4, 5, 1, 0, 4]);
+    }
+
+    #[test]
+    fn perfect_clear() {
+        let table = AttackTable::guideline();
+        let pc = LockResult::Ok {
+            lines_cleared: 2,
+            spin: Spin::None,
+            perfect_clear: true,
+        };
+        assert_eq!(attacks(&table, &[pc]), vec![11]);
+    }
+
+    #[test]
+    fn multiplier_grows_to_max() {
+        let table = AttackTable {
+            multiplier: Some(AttackMultiplier {
+                start_after: Duration::from_secs(60),
+                increase_per_minute: 0.5,
+                max: 2.0,
+            }),
+            ..AttackTable::default()
+        };
+        let tetris = lock(4, Spin::None);
+        let attack_at =
+            |secs| AttackState::default().record_lock(&table, &tetris, Duration::from_secs(secs));
+        assert_eq!(attack_at(30), 4);
+        assert_eq!(attack_at(120), 6);
+        assert_eq!(attack_at(600), 8);
+    }
+}
@@ -1,26 +1,27 @@
 use crate::consts;
-use crate::consts::NUM_POSITIONS;
 use crate::field::Pos;
+use crate::shapes::Shift;
+use crate::tetromino::Tetromino;
 use bytemuck::{Pod, Zeroable};
 use std::fmt::{Debug, Formatter};
 
-pub const FIELD_BYTES: usize = NUM_POSITIONS / 8 + if (NUM_POSITIONS % 8) == 0 { 0 } else { 1 };
+/// Bits of a row which are inside the field.
+pub const FULL_ROW: u16 = (1 << consts::W) - 1;
 
+/// Compact occupancy-only field, one `u16` per row with bit `x` set for an occupied column.
+///
+/// The layout is shared with the Vulkan bot shader, so it must stay `repr(C)` and `Pod`.
 #[repr(C)]
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Default, Pod, Zeroable)]
 pub struct BitmapField {
-    bytes: [u8; FIELD_BYTES],
+    rows: [u16; consts::MAX_H_US],
 }
 
-unsafe impl Pod for BitmapField {}
-unsafe impl Zeroable for BitmapField {}
-
-impl Default for BitmapField {
-    fn default() -> Self {
-        Self {
-            bytes: [0; FIELD_BYTES],
-        }
-    }
+/// Row masks covering the blocks of a tetromino, starting from its lowest row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PieceMask {
+    bottom: i32,
+    rows: [u16; 4],
 }
 
 impl Debug for BitmapField {
@@ -45,19 +46,279 @@ impl Debug for BitmapField {
 
 impl BitmapField {
     pub fn set(&mut self, pos: &Pos) {
-        let (byte, mask) = Self::byte_and_mask(pos);
-        self.bytes[byte] |= mask;
+        self.rows[pos.y as usize] |= 1 << pos.x;
     }
 
     pub fn occupied(&self, pos: &Pos) -> bool {
-        let (byte, mask) = Self::byte_and_mask(pos);
-        (self.bytes[byte] & mask) != 0
+        (self.rows[pos.y as usize] & (1 << pos.x)) != 0
+    }
+
+    pub fn row(&self, y: usize) -> u16 {
+        self.rows[y]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|row| *row == 0)
+    }
+
+    /// True if the tetromino doesn't overlap any occupied cell. Like `Field::is_valid`, cells
+    /// above the top of the field are free.
+    pub fn is_valid(&self, t: &Tetromino) -> bool {
+        !self.collides(&PieceMask::from(t))
+    }
+
+    /// True if any block of the piece overlaps an occupied cell or sits below the floor.
+    pub fn collides(&self, piece: &PieceMask) -> bool {
+        piece.rows().any(|(y, mask)| {
+            if y < 0 {
+                true
+            } else {
+                self.rows
+                    .get(y as usize)
+                    .is_some_and(|row| (row & mask) != 0)
+            }
+        })
+    }
+
+    /// Lower the piece until it lands on the stack or the floor.
+    pub fn hard_drop(&self, piece: &PieceMask) -> PieceMask {
+        let mut piece = *piece;
+        loop {
+            let lowered = PieceMask {
+                bottom: piece.bottom - 1,
+                ..piece
+            };
+            if self.collides(&lowered) {
+                return piece;
+            }
+            piece = lowered;
+        }
+    }
+
+    /// Set the piece's blocks. Blocks above the top of the field are discarded.
+    pub fn place(&mut self, piece: &PieceMask) {
+        for (y, mask) in piece.rows() {
+            if let Some(row) = usize::try_from(y).ok().and_then(|y| self.rows.get_mut(y)) {
+                *row |= mask;
+            }
+        }
+    }
+
+    /// Remove every full row, compacting the rows above it downward. Returns the number cleared.
+    pub fn clear_lines(&mut self) -> i32 {
+        let mut dest = 0;
+        for y in 0..consts::MAX_H_US {
+            if self.rows[y] != FULL_ROW {
+                self.rows[dest] = self.rows[y];
+                dest += 1;
+            }
+        }
+        let cleared = consts::MAX_H_US - dest;
+        self.rows[dest..].fill(0);
+        cleared as i32
+    }
+
+    /// Place the tetromino and clear lines, return the number of lines cleared.
+    pub fn apply_tetromino(&mut self, t: &Tetromino) -> i32 {
+        self.place(&PieceMask::from(t));
+        self.clear_lines()
+    }
+
+    /// Push a garbage row in from the bottom, with the hole in the rightmost column.
+    pub fn apply_garbage(&mut self) {
+        self.rows.copy_within(0..(consts::MAX_H_US - 1), 1);
+        self.rows[0] = FULL_ROW & !(1 << (consts::W - 1));
+    }
+}
+
+impl PieceMask {
+    /// The piece moved a column over, or None if it would leave the field.
+    pub fn shift(&self, dir: Shift) -> Option<Self> {
+        let edge = match dir {
+            Shift::Left => 1,
+            Shift::Right => 1 << (consts::W - 1),
+        };
+        if self.rows.iter().any(|mask| mask & edge != 0) {
+            return None;
+        }
+        Some(Self {
+            rows: self.rows.map(|mask| match dir {
+                Shift::Left => mask >> 1,
+                Shift::Right => mask << 1,
+            }),
+            ..*self
+        })
+    }
+
+    fn rows(&self) -> impl Iterator<Item = (i32, u16)> + '_ {
+        self.rows
+            .iter()
+            .enumerate()
+            .filter(|(_, mask)| **mask != 0)
+            .map(|(i, mask)| (self.bottom + i as i32, *mask))
+    }
+}
+
+impl From<&Tetromino> for PieceMask {
+    fn from(t: &Tetromino) -> Self {
+        let blocks = t.get_blocks();
+        let bottom = blocks.iter().map(|p| p.y).min().unwrap();
+        let mut rows = [0; 4];
+        for p in &blocks {
+            rows[(p.y - bottom) as usize] |= 1 << p.x;
+        }
+        Self { bottom, rows }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::consts::NUM_POSITIONS;
+    use crate::field::Field;
+    use crate::shapes::{Rot, Shape};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// The previous flat bit-array packing, kept as a reference implementation.
+    struct LegacyBitmapField {
+        bytes: [u8; NUM_POSITIONS.div_ceil(8)],
+    }
+
+    impl LegacyBitmapField {
+        fn from_field(field: &Field) -> Self {
+            let mut bytes = [0; NUM_POSITIONS.div_ceil(8)];
+            for y in 0..consts::MAX_H {
+                for x in 0..consts::W {
+                    if field.get_occupied_block(&Pos { x, y }).is_some() {
+                        let bit_index = (y * consts::W + x) as usize;
+                        bytes[bit_index / 8] |= 1 << (bit_index % 8);
+                    }
+                }
+            }
+            Self { bytes }
+        }
+
+        fn occupied(&self, pos: &Pos) -> bool {
+            let bit_index = (pos.y * consts::W + pos.x) as usize;
+            (self.bytes[bit_index / 8] & (1 << (bit_index % 8))) != 0
+        }
+    }
+
+    /// Make a field without floating rows, like one produced by normal play.
+    fn random_field(rng: &mut StdRng) -> Field {
+        let height = rng.gen_range(0..consts::H);
+        Field::with_initial_occupied((0..height).flat_map(|y| {
+            let row: u16 = rng.gen_range(1..=FULL_ROW);
+            (0..consts::W)
+                .filter(move |x| row & (1 << x) != 0)
+                .map(move |x| Pos { x, y })
+        }))
+    }
+
+    fn random_tetromino(rng: &mut StdRng) -> Tetromino {
+        let shapes: Vec<Shape> = enum_iterator::all::<Shape>().collect();
+        let mut t = Tetromino::new(shapes[rng.gen_range(0..shapes.len())]);
+        for _ in 0..rng.gen_range(0..4) {
+            t = t.rotation_options(Rot::Cw).swap_remove(0);
+        }
+        let dir = if rng.gen() { Shift::Left } else { Shift::Right };
+        for _ in 0..rng.gen_range(0..5) {
+            t = t.shift(dir).unwrap_or(t);
+        }
+        t
+    }
+
+    fn assert_same_occupancy(field: &Field, bf: &BitmapField) {
+        for y in 0..consts::MAX_H {
+            for x in 0..consts::W {
+                let pos = Pos { x, y };
+                assert_eq!(
+                    field.get_occupied_block(&pos).is_some(),
+                    bf.occupied(&pos),
+                    "Mismatch at {pos:?}\n{bf:?}"
+                );
+            }
+        }
     }
 
-    fn byte_and_mask(pos: &Pos) -> (usize, u8) {
-        let bit_index = (pos.y * consts::W + pos.x) as usize;
-        let byte = bit_index / 8;
-        let mask_shift = bit_index - (byte * 8);
-        (byte, 1 << mask_shift)
+    #[test]
+    fn matches_legacy_packing() {
+        let mut rng = StdRng::seed_from_u64(26);
+        for _ in 0..100 {
+            let field = random_field(&mut rng);
+            let legacy = LegacyBitmapField::from_field(&field);
+            let bf = field.make_bitmap_field();
+            for y in 0..consts::MAX_H {
+                for x in 0..consts::W {
+                    let pos = Pos { x, y };
+                    assert_eq!(legacy.occupied(&pos), bf.occupied(&pos));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn collision_matches_field() {
+        let mut rng = StdRng::seed_from_u64(27);
+        for _ in 0..1000 {
+            let field = random_field(&mut rng);
+            let bf = field.make_bitmap_field();
+            let mut t = random_tetromino(&mut rng);
+            let lowest = t.get_blocks().iter().map(|p| p.y).min().unwrap();
+            t.raise(-rng.gen_range(0..=lowest));
+            assert_eq!(field.is_valid(&t), !bf.collides(&PieceMask::from(&t)));
+        }
+    }
+
+    #[test]
+    fn apply_tetromino_matches_field() {
+        let mut rng = StdRng::seed_from_u64(28);
+        for _ in 0..1000 {
+            let mut field = random_field(&mut rng);
+            let mut bf = field.make_bitmap_field();
+            let t = random_tetromino(&mut rng);
+            if !field.is_valid(&t) {
+                continue;
+            }
+            let t = field.find_shadow(&t);
+
+            assert_eq!(field.apply_tetrominio(&t), bf.apply_tetromino(&t));
+            assert_same_occupancy(&field, &bf);
+        }
+    }
+
+    #[test]
+    fn moves_match_tetromino() {
+        let mut rng = StdRng::seed_from_u64(30);
+        for _ in 0..1000 {
+            let field = random_field(&mut rng);
+            let bf = field.make_bitmap_field();
+            let t = random_tetromino(&mut rng);
+            if !field.is_valid(&t) {
+                continue;
+            }
+            let piece = PieceMask::from(&t);
+
+            assert_eq!(
+                bf.hard_drop(&piece),
+                PieceMask::from(&field.find_shadow(&t))
+            );
+            for dir in [Shift::Left, Shift::Right] {
+                assert_eq!(piece.shift(dir), t.shift(dir).map(|t| PieceMask::from(&t)));
+            }
+        }
+    }
+
+    #[test]
+    fn garbage_matches_field() {
+        let mut rng = StdRng::seed_from_u64(29);
+        for _ in 0..100 {
+            let mut field = random_field(&mut rng);
+            let mut bf = field.make_bitmap_field();
+            field.apply_garbage();
+            bf.apply_garbage();
+            assert_same_occupancy(&field, &bf);
+        }
     }
 }
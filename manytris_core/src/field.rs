@@ -117,6 +117,10 @@ impl Field {
         self.occupied[0][consts::W_US - 1] = None;
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.occupied.iter().flatten().all(|block| block.is_none())
+    }
+
     pub fn make_bitmap_field(&self) -> BitmapField {
         let mut bf = BitmapField::default();
         for y in 0..consts::MAX_H_US {
@@ -139,6 +143,22 @@ impl Field {
     }
 }
 
+#[cfg(test)]
+impl Field {
+    /// Build a field from rows of text given top to bottom, like they're drawn. 'X' is occupied.
+    pub fn from_rows(rows: &[&str]) -> Self {
+        Self::with_initial_occupied(rows.iter().rev().enumerate().flat_map(|(y, row)| {
+            row.chars()
+                .enumerate()
+                .filter(|(_, c)| *c == 'X')
+                .map(move |(x, _)| Pos {
+                    x: x as i32,
+                    y: y as i32,
+                })
+        }))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -0,0 +1,234 @@
+use crate::bitmap_field::BitmapField;
+use crate::consts;
+use crate::field::{Field, Pos};
+
+/// Read-only view of which cells of a board are occupied.
+pub trait OccupancyGrid {
+    fn is_occupied(&self, pos: &Pos) -> bool;
+}
+
+/// Shape measurements of a board, shared by the bot evaluator and any UI showing board stats.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FieldMetrics {
+    /// One past the highest occupied cell of each column, 0 for an empty column.
+    pub column_heights: [u8; consts::W_US],
+    pub max_height: u8,
+    /// Difference between the tallest and shortest columns.
+    pub height_differential: u8,
+    /// Sum of the absolute height differences between adjacent columns.
+    pub bumpiness: u16,
+    /// Number of columns lower than both of their neighbours. Walls count as infinitely tall.
+    pub wells: u8,
+    /// Depth of the deepest well.
+    pub well_depth: u8,
+    /// Occupied/empty changes along each row, with the walls counted as occupied.
+    pub row_transitions: u16,
+    /// Occupied/empty changes up each column, with the floor counted as occupied.
+    pub column_transitions: u16,
+    /// Empty cells with an occupied cell somewhere above them in the same column.
+    pub holes: u16,
+    /// Occupied cells directly above an empty cell.
+    pub overhangs: u16,
+    /// Places a T piece could be spun into to clear the row beneath its flat side.
+    pub t_slots: u8,
+    /// Rows with at most four empty cells, which a single piece could clear to keep a combo going.
+    pub near_full_rows: u8,
+}
+
+impl OccupancyGrid for Field {
+    fn is_occupied(&self, pos: &Pos) -> bool {
+        self.get_occupied_block(pos).is_some()
+    }
+}
+
+impl OccupancyGrid for BitmapField {
+    fn is_occupied(&self, pos: &Pos) -> bool {
+        self.occupied(pos)
+    }
+}
+
+impl FieldMetrics {
+    pub fn compute(grid: &impl OccupancyGrid) -> Self {
+        let occupied = |x: i32, y: i32| {
+            if !(0..consts::W).contains(&x) || y < 0 {
+                // Walls and floor
+                true
+            } else if y >= consts::MAX_H {
+                false
+            } else {
+                grid.is_occupied(&Pos { x, y })
+            }
+        };
+
+        let column_heights: [u8; consts::W_US] = std::array::from_fn(|x| {
+            (0..consts::MAX_H)
+                .rev()
+                .find(|y| occupied(x as i32, *y))
+                .map_or(0, |y| y + 1) as u8
+        });
+        let max_height = *column_heights.iter().max().unwrap();
+        let min_height = *column_heights.iter().min().unwrap();
+
+        let bumpiness = column_heights
+            .windows(2)
+            .map(|pair| pair[0].abs_diff(pair[1]) as u16)
+            .sum();
+
+        let mut wells = 0;
+        let mut well_depth = 0;
+        for x in 0..consts::W_US {
+            let left = if x == 0 {
+                u8::MAX
+            } else {
+                column_heights[x - 1]
+            };
+            let right = if x == consts::W_US - 1 {
+                u8::MAX
+            } else {
+                column_heights[x + 1]
+            };
+            let depth = left.min(right).saturating_sub(column_heights[x]);
+            if depth > 0 {
+                wells += 1;
+                well_depth = well_depth.max(depth);
+            }
+        }
+
+        let mut row_transitions = 0;
+        for y in 0..max_height as i32 {
+            for x in 0..=consts::W {
+                if occupied(x - 1, y) != occupied(x, y) {
+                    row_transitions += 1;
+                }
+            }
+        }
+
+        let mut column_transitions = 0;
+        let mut holes = 0;
+        let mut overhangs = 0;
+        for x in 0..consts::W {
+            for y in 0..max_height as i32 {
+                if occupied(x, y - 1) != occupied(x, y) {
+                    column_transitions += 1;
+                }
+            }
+            for y in 0..column_heights[x as usize] as i32 {
+                if !occupied(x, y) {
+                    holes += 1;
+                } else if !occupied(x, y - 1) {
+                    overhangs += 1;
+                }
+            }
+        }
+
+        let near_full_rows = (0..max_height as i32)
+            .filter(|y| {
+                let empty = (0..consts::W).filter(|x| !occupied(*x, *y)).count();
+                (1..=4).contains(&empty)
+            })
+            .count() as u8;
+
+        let mut t_slots = 0;
+        for y in 1..(max_height as i32) {
+            for x in 1..(consts::W - 1) {
+                let row_open = !occupied(x - 1, y) && !occupied(x, y) && !occupied(x + 1, y);
+                let stem_open = !occupied(x, y - 1) && !occupied(x, y + 1);
+                let floor_corners = occupied(x - 1, y - 1) && occupied(x + 1, y - 1);
+                let roof_corner = occupied(x - 1, y + 1) || occupied(x + 1, y + 1);
+                if row_open && stem_open && floor_corners && roof_corner {
+                    t_slots += 1;
+                }
+            }
+        }
+
+        Self {
+            column_heights,
+            max_height,
+            height_differential: max_height - min_height,
+            bumpiness,
+            wells,
+            well_depth,
+            row_transitions,
+            column_transitions,
+            holes,
+            overhangs,
+            t_slots,
+            near_full_rows,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty_field() {
+        let metrics = FieldMetrics::compute(&Field::default());
+        assert_eq!(metrics, FieldMetrics::default());
+    }
+
+    #[test]
+    fn heights_and_holes() {
+        let field = Field::from_rows(&[
+            "X         ", //
+            "X  XX     ", //
+            "XX X XXXXX", //
+        ]);
+        let metrics = FieldMetrics::compute(&field);
+
+        assert_eq!(metrics.column_heights, [3, 1, 0, 2, 2, 1, 1, 1, 1, 1]);
+        assert_eq!(metrics.max_height, 3);
+        assert_eq!(metrics.height_differential, 3);
+        assert_eq!(metrics.bumpiness, 2 + 1 + 2 + 1);
+        assert_eq!(metrics.holes, 1);
+        assert_eq!(metrics.overhangs, 1);
+        assert_eq!(metrics.wells, 1);
+        assert_eq!(metrics.well_depth, 1);
+    }
+
+    #[test]
+    fn bitmap_matches_field() {
+        let field = Field::from_rows(&[
+            " XX    X  ", //
+            "X  X  XX X", //
+            "XX XXXXX X", //
+        ]);
+        assert_eq!(
+            FieldMetrics::compute(&field),
+            FieldMetrics::compute(&field.make_bitmap_field())
+        );
+    }
+
+    #[test]
+    fn transitions() {
+        let field = Field::from_rows(&[
+            "XXXXX XXXX", //
+        ]);
+        let metrics = FieldMetrics::compute(&field);
+        assert_eq!(metrics.row_transitions, 2);
+        assert_eq!(metrics.column_transitions, 1);
+    }
+
+    #[test]
+    fn t_slot() {
+        let field = Field::from_rows(&[
+            "XX        ", //
+            "X   XXXXXX", //
+            "XX XXXXXXX", //
+        ]);
+        let metrics = FieldMetrics::compute(&field);
+        assert_eq!(metrics.t_slots, 1);
+    }
+
+    #[test]
+    fn near_full_rows() {
+        let field = Field::from_rows(&[
+            "X    X    ", //
+            "XXX  XXXXX", //
+            "XXXX XXXXX", //
+        ]);
+        let metrics = FieldMetrics::compute(&field);
+        assert_eq!(metrics.near_full_rows, 2);
+    }
+}
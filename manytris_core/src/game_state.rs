@@ -1,3 +1,4 @@
+use crate::attack::AttackState;
 use crate::bitmap_field::BitmapField;
 use crate::consts;
 use crate::field::{Field, OccupiedBlock, Pos};
@@ -14,9 +15,24 @@ pub struct GameState {
     active: Tetromino,
     upcoming: UpcomingTetrominios,
     garbage_queue: VecDeque<usize>,
+    /// Turns a line of garbage waits in the queue before rising.
+    #[serde(default = "default_garbage_delay")]
+    garbage_delay: usize,
 
     held: Option<Shape>,
     hold_used: bool,
+
+    /// True if the last successful movement of the active tetromino was a rotation.
+    #[serde(default)]
+    last_move_rotation: bool,
+
+    /// Combo and back-to-back going into the next lock.
+    #[serde(default)]
+    attack_state: AttackState,
+}
+
+fn default_garbage_delay() -> usize {
+    consts::GARBAGE_TURN_COUNT
 }
 
 pub enum BlockDisplayState {
@@ -29,7 +45,57 @@ pub enum BlockDisplayState {
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub enum LockResult {
     GameOver, // TODO: GameOver can occur during hold too
-    Ok { lines_cleared: i32 },
+    Ok {
+        lines_cleared: i32,
+        spin: Spin,
+        perfect_clear: bool,
+    },
+}
+
+/// Whether a locked tetromino was spun into place.
+#[derive(Copy, Clone, Deserialize, Serialize, Debug, Eq, PartialEq, Hash)]
+pub enum Spin {
+    None,
+    Mini(Shape),
+    Full(Shape),
+}
+
+impl Spin {
+    /// Classify a tetromino which was rotated into place, given which cells in the field are
+    /// occupied.
+    ///
+    /// T pieces use the 3-corner rule, with both corners on the pointing side making a full spin.
+    /// Any other piece which was rotated into a spot it can't move out of is a mini spin.
+    pub fn classify(t: &Tetromino, occupied: impl Fn(&Pos) -> bool) -> Self {
+        let shape = t.shape;
+
+        if let Some((front, back)) = t.spin_corners() {
+            let count_occupied = |corners: [Pos; 2]| {
+                corners
+                    .iter()
+                    .filter(|p| p.out_of_bounds() || occupied(p))
+                    .count()
+            };
+            return match (count_occupied(front), count_occupied(back)) {
+                (2, 1..) => Spin::Full(shape),
+                (1, 2) => Spin::Mini(shape),
+                _ => Spin::None,
+            };
+        }
+
+        let mut raised = t.clone();
+        raised.raise(1);
+        let immobile = [Shift::Left, Shift::Right]
+            .into_iter()
+            .map(|dir| t.shift(dir))
+            .chain([Some(raised)])
+            .all(|t| t.is_none_or(|t| t.get_blocks().iter().any(&occupied)));
+        if immobile {
+            Spin::Mini(shape)
+        } else {
+            Spin::None
+        }
+    }
 }
 
 #[derive(Clone, Deserialize, Serialize, Debug)]
@@ -57,6 +123,8 @@ pub enum TickResult {
     Lock(LockResult),
     RestartLockTimer,
     ClearLockTimer,
+    /// Lines of garbage pushed onto the field.
+    GarbageApplied(usize),
 }
 
 impl GameState {
@@ -71,8 +139,11 @@ impl GameState {
             field,
             active: Tetromino::new(upcoming.take()),
             garbage_queue: VecDeque::default(),
+            garbage_delay: consts::GARBAGE_TURN_COUNT,
             held: None,
             hold_used: false,
+            last_move_rotation: false,
+            attack_state: AttackState::default(),
             upcoming,
         }
     }
@@ -95,6 +166,7 @@ impl GameState {
                 }
                 JumpToBotStartPosition(new_tet) => {
                     self.active = new_tet;
+                    self.last_move_rotation = false;
                     vec![]
                 }
                 EnqueueGarbage(lines) => {
@@ -103,6 +175,11 @@ impl GameState {
                 }
             });
         }
+        for tr in &result {
+            if let TickResult::Lock(lr) = tr {
+                self.attack_state.update(lr);
+            }
+        }
         result
     }
 
@@ -111,6 +188,7 @@ impl GameState {
         match (self.active.down(), down_type) {
             (Some(new_t), _) if self.field.is_valid(&new_t) => {
                 self.active = new_t;
+                self.last_move_rotation = false;
                 vec![self.update_lock_timer_for_movement()]
             }
             // Can't drop any further on the first press, lock it.
@@ -123,7 +201,10 @@ impl GameState {
     fn drop(&mut self) -> Vec<TickResult> {
         loop {
             match self.active.down() {
-                Some(new_t) if self.field.is_valid(&new_t) => self.active = new_t,
+                Some(new_t) if self.field.is_valid(&new_t) => {
+                    self.active = new_t;
+                    self.last_move_rotation = false;
+                }
                 _ => break,
             };
         }
@@ -135,6 +216,7 @@ impl GameState {
             .active
             .shift(dir)
             .filter(|new_t| self.field.is_valid(&new_t))?;
+        self.last_move_rotation = false;
         Some(self.update_lock_timer_for_movement())
     }
 
@@ -145,15 +227,26 @@ impl GameState {
             .into_iter()
             .filter(|t| self.field.is_valid(t))
             .next()?;
+        self.last_move_rotation = true;
         Some(self.update_lock_timer_for_movement())
     }
 
     fn enqueue_garbage(&mut self, num_lines: usize) {
         for _ in 0..num_lines {
-            self.garbage_queue.push_back(consts::GARBAGE_TURN_COUNT)
+            self.garbage_queue.push_back(self.garbage_delay)
         }
     }
 
+    /// Change how many turns garbage enqueued from now on waits before rising.
+    pub fn set_garbage_delay(&mut self, turns: usize) {
+        self.garbage_delay = turns.max(1);
+    }
+
+    /// Lines of garbage waiting to be applied to the field.
+    pub fn pending_garbage(&self) -> usize {
+        self.garbage_queue.len()
+    }
+
     pub fn get_garbage_element_countdown(&self, index: usize) -> Option<usize> {
         return self.garbage_queue.get(index).copied();
     }
@@ -176,10 +269,34 @@ impl GameState {
             .map(|shape| Tetromino::for_preview(shape))
     }
 
+    /// False once the active tetromino has been swapped with the held one.
+    pub fn can_hold(&self) -> bool {
+        !self.hold_used
+    }
+
     pub fn held_tetromino(&self) -> Option<Tetromino> {
         Some(Tetromino::for_preview(self.held?))
     }
 
+    /// Replace the held shape, for setting up a position from outside the game.
+    pub fn set_held(&mut self, held: Option<Shape>) {
+        self.held = held;
+    }
+
+    /// Combo and back-to-back going into the next lock.
+    pub fn attack_state(&self) -> &AttackState {
+        &self.attack_state
+    }
+
+    /// Replace the combo and back-to-back, for setting up a position from outside the game.
+    pub fn set_attack_state(&mut self, attack_state: AttackState) {
+        self.attack_state = attack_state;
+    }
+
+    pub fn field(&self) -> &Field {
+        &self.field
+    }
+
     pub fn make_bitmap_field(&self) -> BitmapField {
         self.field.make_bitmap_field()
     }
@@ -188,10 +305,19 @@ impl GameState {
         self.active.shape
     }
 
+    pub fn active_tetromino(&self) -> &Tetromino {
+        &self.active
+    }
+
     pub fn upcoming_shapes(&self) -> [Shape; consts::NUM_PREVIEWS] {
         self.upcoming.preview()
     }
 
+    /// Every shape in the queue, including those past the previews.
+    pub fn queued_shapes(&self) -> &[Shape] {
+        self.upcoming.queue()
+    }
+
     fn hold(&mut self) -> Vec<TickResult> {
         if self.hold_used {
             return vec![];
@@ -225,12 +351,19 @@ impl GameState {
         self.hold_used = false;
         let mut result = vec![TickResult::ClearLockTimer];
 
+        let spin = self.detect_spin();
         let lines_cleared = self.field.apply_tetrominio(&self.active);
+        let perfect_clear = self.field.is_empty();
         let next_shape = self.upcoming.take();
 
+        let mut garbage_applied = 0;
         while (!self.garbage_queue.is_empty()) && self.garbage_queue[0] == 1 {
             self.field.apply_garbage();
             self.garbage_queue.pop_front();
+            garbage_applied += 1;
+        }
+        if garbage_applied > 0 {
+            result.push(TickResult::GarbageApplied(garbage_applied));
         }
 
         self.garbage_queue.iter_mut().for_each(|cnt| {
@@ -239,7 +372,11 @@ impl GameState {
 
         result.push(TickResult::Lock(
             if self.replace_active_tetromino(next_shape) {
-                LockResult::Ok { lines_cleared }
+                LockResult::Ok {
+                    lines_cleared,
+                    spin,
+                    perfect_clear,
+                }
             } else {
                 LockResult::GameOver
             },
@@ -247,9 +384,18 @@ impl GameState {
         result
     }
 
+    /// Classify the active tetromino's placement before it locks.
+    fn detect_spin(&self) -> Spin {
+        if !self.last_move_rotation {
+            return Spin::None;
+        }
+        Spin::classify(&self.active, |p| self.field.get_occupied_block(p).is_some())
+    }
+
     /// Place the new tetromino, return true if it has a valid placement.
     fn replace_active_tetromino(&mut self, shape: Shape) -> bool {
         self.active = Tetromino::new(shape);
+        self.last_move_rotation = false;
         self.field.is_valid(&self.active)
     }
 }
@@ -273,3 +419,134 @@ impl Display for GameState {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn t_slot_game() -> GameState {
+        let field = Field::from_rows(&[
+            "XX        ", //
+            "X   XXXXXX", //
+            "XX XXXXXXX", //
+        ]);
+        GameState::with_initial_state(vec![Shape::T; consts::NUM_PREVIEWS * 2], field)
+    }
+
+    /// A T at the bottom left of the field, after the given number of clockwise rotations.
+    fn t_at_slot(cw_rotations: usize) -> Tetromino {
+        let mut t = Tetromino::new(Shape::T);
+        for _ in 0..cw_rotations {
+            t = t.rotation_options(Rot::Cw).swap_remove(0);
+        }
+        t = t.shift(Shift::Left).unwrap().shift(Shift::Left).unwrap();
+        t.raise(-(consts::H - consts::PREVIEW_H - 1));
+        t
+    }
+
+    fn lock_results(results: Vec<TickResult>) -> Vec<LockResult> {
+        results
+            .into_iter()
+            .filter_map(|tr| match tr {
+                TickResult::Lock(lr) => Some(lr),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn t_spin_double() {
+        let mut gs = t_slot_game();
+        let results = gs.tick_mutation(vec![
+            TickMutation::JumpToBotStartPosition(t_at_slot(1)),
+            TickMutation::RotateInput(Rot::Cw),
+            TickMutation::LockTimerExpired,
+        ]);
+        assert!(matches!(
+            lock_results(results)[..],
+            [LockResult::Ok {
+                lines_cleared: 2,
+                spin: Spin::Full(Shape::T),
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn tracks_combo_and_back_to_back() {
+        let mut gs = t_slot_game();
+        let _ = gs.tick_mutation(vec![
+            TickMutation::JumpToBotStartPosition(t_at_slot(1)),
+            TickMutation::RotateInput(Rot::Cw),
+            TickMutation::LockTimerExpired,
+        ]);
+        assert_eq!(gs.attack_state(), &AttackState::new(Some(0), true));
+
+        // Dropping without a clear breaks the combo, but not back-to-back.
+        let _ = gs.tick_mutation(vec![TickMutation::DropInput]);
+        assert_eq!(gs.attack_state(), &AttackState::new(None, true));
+    }
+
+    #[test]
+    fn no_spin_without_rotation() {
+        let mut gs = t_slot_game();
+        let results = gs.tick_mutation(vec![
+            TickMutation::JumpToBotStartPosition(t_at_slot(2)),
+            TickMutation::LockTimerExpired,
+        ]);
+        assert!(matches!(
+            lock_results(results)[..],
+            [LockResult::Ok {
+                lines_cleared: 2,
+                spin: Spin::None,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn garbage_applied_after_countdown() {
+        let mut gs = GameState::new(vec![Shape::O; consts::NUM_PREVIEWS * 2]);
+        let _ = gs.tick_mutation(vec![TickMutation::EnqueueGarbage(2)]);
+
+        let applied: Vec<usize> = (0..consts::GARBAGE_TURN_COUNT)
+            .flat_map(|_| gs.tick_mutation(vec![TickMutation::DropInput]))
+            .filter_map(|tr| match tr {
+                TickResult::GarbageApplied(n) => Some(n),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(applied, vec![2]);
+        assert_eq!(gs.pending_garbage(), 0);
+    }
+
+    #[test]
+    fn custom_garbage_delay() {
+        let mut gs = GameState::new(vec![Shape::O; consts::NUM_PREVIEWS * 2]);
+        gs.set_garbage_delay(6);
+        let _ = gs.tick_mutation(vec![TickMutation::EnqueueGarbage(1)]);
+
+        let applied_turn = (1..=10).find(|_| {
+            gs.tick_mutation(vec![TickMutation::DropInput])
+                .iter()
+                .any(|tr| matches!(tr, TickResult::GarbageApplied(_)))
+        });
+        assert_eq!(applied_turn, Some(6));
+    }
+
+    #[test]
+    fn reads_snapshots_from_before_the_newer_fields() {
+        let mut gs = GameState::new(vec![Shape::O; consts::NUM_PREVIEWS * 2]);
+        gs.set_garbage_delay(6);
+        let mut snapshot = serde_json::to_value(&gs).unwrap();
+        let fields = snapshot.as_object_mut().unwrap();
+        for name in ["garbage_delay", "last_move_rotation", "attack_state"] {
+            fields.remove(name).unwrap();
+        }
+
+        let old: GameState = serde_json::from_value(snapshot).unwrap();
+        assert_eq!(old.garbage_delay, consts::GARBAGE_TURN_COUNT);
+        assert!(!old.last_move_rotation);
+        assert_eq!(old.attack_state(), &AttackState::default());
+    }
+}
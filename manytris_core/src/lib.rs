@@ -1,7 +1,10 @@
+pub mod attack;
 pub mod bitmap_field;
 pub mod consts;
 pub mod field;
+pub mod field_metrics;
 pub mod game_state;
+pub mod player_stats;
 pub mod shape_bag;
 pub mod shapes;
 pub mod tetromino;
@@ -0,0 +1,175 @@
+use crate::game_state::{DownType, LockResult, Spin, TickMutation};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+/// Running performance counters for one game.
+///
+/// Only fed from tick mutations, lock results and the attack computed from them, so every
+/// process replaying the same game computes the same stats.
+#[derive(Clone, Debug, Default)]
+pub struct PlayerStats {
+    pieces: u32,
+    lines: u32,
+    attack: u32,
+    keys: u32,
+    combo: u32,
+    max_combo: u32,
+    spins: HashMap<Spin, u32>,
+}
+
+/// Rates derived from [PlayerStats] over a span of play time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatsSummary {
+    pub pieces_per_second: f32,
+    pub attack_per_minute: f32,
+    pub keys_per_piece: f32,
+    pub lines_per_minute: f32,
+    /// Most consecutive pieces which each cleared at least one line.
+    pub max_combo: u32,
+    pub spins: Vec<(Spin, u32)>,
+}
+
+impl PlayerStats {
+    /// Count the key presses among the mutations applied to the game.
+    ///
+    /// Gravity and held-key repeats of soft drop are not presses. Bot teleports are not either,
+    /// only the shifts and drop which follow them.
+    pub fn record_mutation(&mut self, mutation: &TickMutation) {
+        use TickMutation::*;
+        match mutation {
+            ShiftInput(_) | RotateInput(_) | DropInput | HoldInput => self.keys += 1,
+            DownInput(DownType::FirstPress) => self.keys += 1,
+            DownInput(DownType::HoldRepeat | DownType::Gravity)
+            | LockTimerExpired
+            | EnqueueTetromino(_)
+            | JumpToBotStartPosition(_)
+            | EnqueueGarbage(_) => {}
+        }
+    }
+
+    pub fn record_lock(&mut self, lock_result: &LockResult) {
+        let LockResult::Ok {
+            lines_cleared,
+            spin,
+            ..
+        } = lock_result
+        else {
+            return;
+        };
+
+        self.pieces += 1;
+        self.lines += *lines_cleared as u32;
+
+        if *lines_cleared > 0 {
+            self.combo += 1;
+            self.max_combo = self.max_combo.max(self.combo);
+        } else {
+            self.combo = 0;
+        }
+
+        if *spin != Spin::None {
+            *self.spins.entry(*spin).or_default() += 1;
+        }
+    }
+
+    /// Count garbage lines sent to opponents.
+    pub fn record_attack(&mut self, lines: usize) {
+        self.attack += lines as u32;
+    }
+
+    pub fn summary(&self, play_time: Duration) -> StatsSummary {
+        let seconds = play_time.as_secs_f32();
+        let per_second = |count: u32| {
+            if seconds > 0.0 {
+                count as f32 / seconds
+            } else {
+                0.0
+            }
+        };
+
+        let mut spins: Vec<_> = self.spins.iter().map(|(s, n)| (*s, *n)).collect();
+        spins.sort_by_key(|(s, _)| format!("{s:?}"));
+
+        StatsSummary {
+            pieces_per_second: per_second(self.pieces),
+            attack_per_minute: per_second(self.attack) * 60.0,
+            keys_per_piece: if self.pieces > 0 {
+                self.keys as f32 / self.pieces as f32
+            } else {
+                0.0
+            },
+            lines_per_minute: per_second(self.lines) * 60.0,
+            max_combo: self.max_combo,
+            spins,
+        }
+    }
+}
+
+impl Display for StatsSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PPS: {:.2} APM: {:.1} KPP: {:.2} LPM: {:.1} Max combo: {}",
+            self.pieces_per_second,
+            self.attack_per_minute,
+            self.keys_per_piece,
+            self.lines_per_minute,
+            self.max_combo
+        )?;
+        for (spin, count) in &self.spins {
+            write!(f, " {spin:?}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shapes::{Shape, Shift};
+
+    fn lock(lines_cleared: i32, spin: Spin) -> LockResult {
+        LockResult::Ok {
+            lines_cleared,
+            spin,
+            perfect_clear: false,
+        }
+    }
+
+    #[test]
+    fn rates() {
+        let mut stats = PlayerStats::default();
+        for lines in [0, 4, 2, 0] {
+            stats.record_mutation(&TickMutation::ShiftInput(Shift::Left));
+            stats.record_mutation(&TickMutation::DownInput(DownType::Gravity));
+            stats.record_mutation(&TickMutation::DropInput);
+            stats.record_lock(&lock(lines, Spin::None));
+            stats.record_attack(lines as usize);
+        }
+
+        let summary = stats.summary(Duration::from_secs(2));
+        assert_eq!(summary.pieces_per_second, 2.0);
+        assert_eq!(summary.keys_per_piece, 2.0);
+        assert_eq!(summary.lines_per_minute, 6.0 * 30.0);
+        assert_eq!(summary.attack_per_minute, 6.0 * 30.0);
+        assert_eq!(summary.max_combo, 2);
+    }
+
+    #[test]
+    fn spins_and_game_over() {
+        let mut stats = PlayerStats::default();
+        stats.record_lock(&lock(2, Spin::Full(Shape::T)));
+        stats.record_lock(&lock(1, Spin::Mini(Shape::S)));
+        stats.record_lock(&lock(0, Spin::Full(Shape::T)));
+        stats.record_lock(&LockResult::GameOver);
+
+        let summary = stats.summary(Duration::ZERO);
+        assert_eq!(summary.pieces_per_second, 0.0);
+        assert_eq!(summary.max_combo, 2);
+        assert_eq!(
+            summary.spins,
+            vec![(Spin::Full(Shape::T), 2), (Spin::Mini(Shape::S), 1)]
+        );
+    }
+}
@@ -1,20 +1,39 @@
 use crate::shapes::Shape;
-use rand::{thread_rng, Rng};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-#[derive(Default)]
 pub struct ShapeBag {
     remaining: Vec<Shape>,
+    rng: StdRng,
 }
+
 impl ShapeBag {
+    /// A bag which always deals the same shapes for the seed.
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            remaining: vec![],
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
     fn take(&mut self) -> Shape {
         if self.remaining.is_empty() {
             self.remaining = enum_iterator::all::<Shape>().collect();
         }
-        let idx = thread_rng().gen_range(0..self.remaining.len());
+        let idx = self.rng.gen_range(0..self.remaining.len());
         self.remaining.remove(idx)
     }
 }
 
+impl Default for ShapeBag {
+    fn default() -> Self {
+        Self {
+            remaining: vec![],
+            rng: StdRng::from_entropy(),
+        }
+    }
+}
+
 impl Iterator for ShapeBag {
     type Item = Shape;
 
@@ -22,3 +41,17 @@ impl Iterator for ShapeBag {
         Some(self.take())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn seeded_bags_repeat() {
+        let shapes: Vec<_> = ShapeBag::seeded(46).take(70).collect();
+        assert_eq!(shapes, ShapeBag::seeded(46).take(70).collect::<Vec<_>>());
+        for bag in shapes.chunks(7) {
+            assert!(enum_iterator::all::<Shape>().all(|s| bag.contains(&s)));
+        }
+    }
+}
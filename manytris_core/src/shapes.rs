@@ -16,7 +16,7 @@ pub enum Shape {
     T,
 }
 
-#[derive(Copy, Clone, Deserialize, Serialize, Debug)]
+#[derive(Copy, Clone, Deserialize, Serialize, Debug, Eq, PartialEq, Hash)]
 pub enum Orientation {
     Up,
     Right,
@@ -24,13 +24,13 @@ pub enum Orientation {
     Left,
 }
 
-#[derive(Copy, Clone, Deserialize, Serialize, Debug)]
+#[derive(Copy, Clone, Deserialize, Serialize, Debug, Eq, PartialEq)]
 pub enum Shift {
     Left,
     Right,
 }
 
-#[derive(Copy, Clone, Deserialize, Serialize, Debug)]
+#[derive(Copy, Clone, Deserialize, Serialize, Debug, Eq, PartialEq)]
 pub enum Rot {
     Cw,
     Ccw,
@@ -39,7 +39,7 @@ pub enum Rot {
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct RelPos(pub i32, pub i32);
 
-#[derive(Clone, Deserialize, Serialize, Debug)]
+#[derive(Clone, Deserialize, Serialize, Debug, Eq, PartialEq, Hash)]
 pub struct TetrominoLocation(pub i32, pub i32);
 
 impl RelPos {
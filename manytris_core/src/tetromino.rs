@@ -3,7 +3,7 @@ use crate::shapes;
 use crate::shapes::{Orientation, Rot, Shape, Shift, TetrominoLocation};
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Deserialize, Serialize, Debug)]
+#[derive(Clone, Deserialize, Serialize, Debug, Eq, PartialEq, Hash)]
 pub struct Tetromino {
     pub shape: Shape,
     loc: TetrominoLocation,
@@ -84,6 +84,25 @@ impl Tetromino {
         result
     }
 
+    /// For a T, the diagonal corners around its center: the pair on the side it points to, and
+    /// the pair behind it.
+    pub fn spin_corners(&self) -> Option<([Pos; 2], [Pos; 2])> {
+        if self.shape != Shape::T {
+            return None;
+        }
+        let (front, back) = match self.orientation {
+            Orientation::Up => ([(0, 2), (2, 2)], [(0, 0), (2, 0)]),
+            Orientation::Right => ([(2, 2), (2, 0)], [(0, 2), (0, 0)]),
+            Orientation::Down => ([(0, 0), (2, 0)], [(0, 2), (2, 2)]),
+            Orientation::Left => ([(0, 0), (0, 2)], [(2, 0), (2, 2)]),
+        };
+        let to_pos = |(x, y): (i32, i32)| Pos {
+            x: self.loc.0 + x,
+            y: self.loc.1 + y,
+        };
+        Some((front.map(to_pos), back.map(to_pos)))
+    }
+
     pub fn raise(&mut self, dist: i32) {
         self.loc.1 += dist;
     }
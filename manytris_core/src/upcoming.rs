@@ -20,6 +20,10 @@ impl UpcomingTetrominios {
             .unwrap()
     }
 
+    pub fn queue(&self) -> &[Shape] {
+        &self.upcoming_blocks
+    }
+
     pub fn take(&mut self) -> Shape {
         self.upcoming_blocks.remove(0)
     }
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum GetAddressResponse {
@@ -40,4 +41,18 @@ pub struct StatsServerResponse {
     pub num_connected_players: u16,
     pub num_active_games: u16,
     pub connectionless_time_secs: u32,
+    #[serde(default)]
+    pub player_stats: Vec<PlayerStatsReport>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerStatsReport {
+    pub game_id: String,
+    pub pieces_per_second: f32,
+    pub attack_per_minute: f32,
+    pub keys_per_piece: f32,
+    pub lines_per_minute: f32,
+    pub max_combo: u32,
+    /// Number of pieces locked with each kind of spin, keyed like "Full(T)".
+    pub spins: BTreeMap<String, u32>,
 }
@@ -0,0 +1,237 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use anyhow::{anyhow, bail, Context, Result};
+use manytris_bot::bot_player::MovementDescriptor;
+use manytris_core::game_state::GameState;
+use manytris_core::shapes::Shape;
+
+use crate::messages::{BotInfo, BotMessage, FrontendMessage, Move, Start};
+use crate::state::TbpState;
+
+/// Drives a bot which speaks TBP, like Cold Clear, from manytris games.
+pub struct TbpFrontend<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+    info: BotInfo,
+    /// The bot's process, if the frontend started it.
+    process: Option<Child>,
+}
+
+impl TbpFrontend<BufReader<ChildStdout>, ChildStdin> {
+    /// Start the bot's executable, and talk to it over its stdin and stdout.
+    pub fn spawn(command: &mut Command) -> Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("Couldn't start the bot")?;
+        let reader = BufReader::new(child.stdout.take().unwrap());
+        let writer = child.stdin.take().unwrap();
+
+        let mut frontend = Self::connect(reader, writer)?;
+        frontend.process = Some(child);
+        Ok(frontend)
+    }
+}
+
+impl<R: BufRead, W: Write> TbpFrontend<R, W> {
+    /// Wait for the bot to introduce itself, then agree on the rules.
+    pub fn connect(mut reader: R, writer: W) -> Result<Self> {
+        let info = match receive(&mut reader)? {
+            BotMessage::Info(info) => info,
+            msg => bail!("Expected the bot's info, got {msg:?}"),
+        };
+        let mut frontend = Self {
+            reader,
+            writer,
+            info,
+            process: None,
+        };
+
+        frontend.send(&FrontendMessage::Rules)?;
+        match frontend.receive()? {
+            BotMessage::Ready => Ok(frontend),
+            BotMessage::Error { reason } => bail!("The bot rejected the rules: {reason}"),
+            msg => bail!("Expected the bot to be ready, got {msg:?}"),
+        }
+    }
+
+    pub fn info(&self) -> &BotInfo {
+        &self.info
+    }
+
+    pub fn start(&mut self, start: Start) -> Result<()> {
+        self.send(&FrontendMessage::Start(start))
+    }
+
+    pub fn stop(&mut self) -> Result<()> {
+        self.send(&FrontendMessage::Stop)
+    }
+
+    /// The moves the bot would make, best first.
+    pub fn suggest(&mut self) -> Result<Vec<Move>> {
+        self.send(&FrontendMessage::Suggest)?;
+        match self.receive()? {
+            BotMessage::Suggestion { moves } => Ok(moves),
+            msg => bail!("Expected a suggestion, got {msg:?}"),
+        }
+    }
+
+    pub fn play(&mut self, mv: Move) -> Result<()> {
+        self.send(&FrontendMessage::Play { mv })
+    }
+
+    pub fn new_piece(&mut self, piece: Shape) -> Result<()> {
+        self.send(&FrontendMessage::NewPiece { piece })
+    }
+
+    /// Ask the bot for its move in the game. The bot starts fresh from each position, since
+    /// garbage can change the field between moves.
+    pub fn select_move(&mut self, gs: &GameState) -> Result<MovementDescriptor> {
+        let state = TbpState::from_game(gs);
+        self.start(state.start())?;
+        let moves = self.suggest()?;
+        self.stop()?;
+
+        moves
+            .iter()
+            .filter_map(|mv| state.movement_for(mv).ok())
+            .find(|md| gs.can_hold() || !md.hold)
+            .ok_or_else(|| anyhow!("None of the bot's moves can be made: {moves:?}"))
+    }
+
+    fn send(&mut self, msg: &FrontendMessage) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, msg)?;
+        writeln!(self.writer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<BotMessage> {
+        receive(&mut self.reader)
+    }
+}
+
+/// The next message from the bot, skipping any this version of the protocol doesn't know.
+fn receive(reader: &mut impl BufRead) -> Result<BotMessage> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            bail!("The bot closed its output");
+        }
+        match serde_json::from_str(&line)? {
+            BotMessage::Unknown => continue,
+            msg => return Ok(msg),
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Drop for TbpFrontend<R, W> {
+    fn drop(&mut self) {
+        let _ = self.send(&FrontendMessage::Quit);
+        if let Some(child) = &mut self.process {
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{self, Read};
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::thread;
+
+    use manytris_bot::bot_cpu::CpuBotContext;
+    use manytris_bot::bot_player::select_next_move;
+    use manytris_bot::features::WeightProfile;
+    use manytris_core::consts;
+
+    use crate::server::serve_bot;
+
+    /// Reads what its `PipeWriter` writes, until the writer is dropped.
+    struct PipeReader {
+        chunks: Receiver<Vec<u8>>,
+        chunk: io::Cursor<Vec<u8>>,
+    }
+
+    struct PipeWriter(Sender<Vec<u8>>);
+
+    fn pipe() -> (PipeReader, PipeWriter) {
+        let (tx, rx) = mpsc::channel();
+        let reader = PipeReader {
+            chunks: rx,
+            chunk: io::Cursor::default(),
+        };
+        (reader, PipeWriter(tx))
+    }
+
+    impl Read for PipeReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            while self.chunk.position() as usize == self.chunk.get_ref().len() {
+                match self.chunks.recv() {
+                    Ok(chunk) => self.chunk = io::Cursor::new(chunk),
+                    Err(_) => return Ok(0),
+                }
+            }
+            self.chunk.read(buf)
+        }
+    }
+
+    impl Write for PipeWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0
+                .send(buf.to_vec())
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn plays_against_served_bot() {
+        let (bot_reader, frontend_writer) = pipe();
+        let (frontend_reader, bot_writer) = pipe();
+        let server = thread::spawn(move || {
+            serve_bot(
+                &CpuBotContext::default(),
+                &WeightProfile::survival(),
+                2,
+                BufReader::new(bot_reader),
+                bot_writer,
+            )
+        });
+
+        let mut frontend =
+            TbpFrontend::connect(BufReader::new(frontend_reader), frontend_writer).unwrap();
+        assert_eq!(frontend.info().name, "manytris");
+
+        let mut gs = GameState::new(
+            enum_iterator::all::<Shape>()
+                .cycle()
+                .take(consts::NUM_PREVIEWS * 3)
+                .collect(),
+        );
+        let local = select_next_move(
+            &gs,
+            &CpuBotContext::default(),
+            &WeightProfile::survival(),
+            2,
+        )
+        .unwrap();
+
+        let md = frontend.select_move(&gs).unwrap();
+        let mut local_gs = gs.clone();
+        let _ = gs.tick_mutation(md.as_tick_mutations());
+        let _ = local_gs.tick_mutation(local.moves[0].as_tick_mutations());
+        assert!(gs.make_bitmap_field() == local_gs.make_bitmap_field());
+
+        drop(frontend);
+        server.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,4 @@
+pub mod frontend;
+pub mod messages;
+pub mod server;
+pub mod state;
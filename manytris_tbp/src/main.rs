@@ -0,0 +1,88 @@
+use std::io;
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use manytris_bot::backend::{BotSearchArgs, CpuSearchContext};
+use manytris_bot::features::WeightProfile;
+use manytris_core::attack::AttackTable;
+use manytris_core::consts;
+use manytris_core::game_state::{GameState, LockResult, TickMutation, TickResult};
+use manytris_core::shape_bag::ShapeBag;
+use manytris_tbp::frontend::TbpFrontend;
+use manytris_tbp::server::serve_bot;
+
+/// Bridges manytris and bots speaking the Tetris Bot Protocol.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct TbpArgs {
+    #[command(subcommand)]
+    command: TbpCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum TbpCommand {
+    /// Play as manytris's bot over stdin and stdout.
+    Serve {
+        /// Drops searched ahead, as far as the known queue reaches.
+        #[arg(long, default_value = "3")]
+        search_depth: usize,
+
+        #[command(flatten)]
+        search: BotSearchArgs,
+    },
+    /// Play a solo game with an external bot, and report how it did.
+    Play {
+        /// Pieces to play before stopping.
+        #[arg(long, default_value = "500")]
+        pieces: usize,
+
+        /// The bot's executable and its arguments.
+        #[arg(required = true, last = true)]
+        bot: Vec<String>,
+    },
+}
+
+fn main() -> Result<()> {
+    match TbpArgs::parse().command {
+        TbpCommand::Serve {
+            search_depth,
+            search,
+        } => {
+            let weights = WeightProfile::load(&search.bot_weights)?;
+            let ctx =
+                CpuSearchContext::new(search.bot_backend, &search.cpu, AttackTable::default())
+                    .context("Only the CPU backends can serve TBP")?;
+            let (input, output) = (io::stdin().lock(), io::stdout().lock());
+            serve_bot(&ctx, &weights, search_depth, input, output)
+        }
+        TbpCommand::Play { pieces, bot } => play(pieces, &bot),
+    }
+}
+
+fn play(pieces: usize, bot: &[String]) -> Result<()> {
+    let mut frontend = TbpFrontend::spawn(Command::new(&bot[0]).args(&bot[1..]))?;
+    let info = frontend.info();
+    println!("Playing {} {} by {}", info.name, info.version, info.author);
+
+    let mut shape_bag = ShapeBag::default();
+    let mut gs = GameState::new(shape_bag.by_ref().take(consts::NUM_PREVIEWS * 2).collect());
+    let mut lines = 0;
+    let mut played = 0;
+    'game: while played < pieces {
+        let md = frontend.select_move(&gs)?;
+        played += 1;
+        for tr in gs.tick_mutation(md.as_tick_mutations()) {
+            match tr {
+                TickResult::Lock(LockResult::GameOver) => break 'game,
+                TickResult::Lock(LockResult::Ok { lines_cleared, .. }) => lines += lines_cleared,
+                _ => {}
+            }
+        }
+        let _ = gs.tick_mutation(vec![TickMutation::EnqueueTetromino(
+            shape_bag.next().unwrap(),
+        )]);
+    }
+    println!("Played {played} pieces, clearing {lines} lines");
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use manytris_core::shapes::Shape;
+use serde::{Deserialize, Serialize};
+
+/// Messages from the frontend running a game to the bot playing it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    Rules,
+    Start(Start),
+    Stop,
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        mv: Move,
+    },
+    NewPiece {
+        piece: Shape,
+    },
+    Quit,
+    /// A message from a later version of the protocol, which bots should ignore.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Messages from the bot to the frontend.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Error {
+        reason: String,
+    },
+    Ready,
+    Info(BotInfo),
+    /// Moves the bot would make, best first.
+    Suggestion {
+        moves: Vec<Move>,
+    },
+    /// A message from a later version of the protocol, which frontends should ignore.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BotInfo {
+    pub name: String,
+    pub version: String,
+    pub author: String,
+    /// Protocol extensions the bot supports.
+    pub features: Vec<String>,
+}
+
+/// The position to start thinking about.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Start {
+    pub hold: Option<Shape>,
+    /// The current piece, then the previews.
+    pub queue: Vec<Shape>,
+    pub combo: u32,
+    pub back_to_back: bool,
+    /// Rows of cells, bottom first. Filled cells hold the letter of the piece that filled them,
+    /// or "G" for garbage.
+    pub board: Vec<[Option<char>; 10]>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Move {
+    pub location: PieceLocation,
+    pub spin: Spin,
+}
+
+/// Where a piece locks, by the SRS center of the piece.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PieceLocation {
+    #[serde(rename = "type")]
+    pub piece: Shape,
+    pub orientation: Orientation,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+/// Rows in a board sent to bots.
+pub const BOARD_HEIGHT: usize = 40;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_frontend_messages() {
+        let start: FrontendMessage = serde_json::from_str(
+            r#"{"type": "start", "hold": null, "queue": ["T", "I"], "combo": 0,
+                "back_to_back": false, "board": [["G", null, null, null, null, null, null, null, null, null]]}"#,
+        )
+        .unwrap();
+        let FrontendMessage::Start(start) = start else {
+            panic!("Not a start: {start:?}");
+        };
+        assert_eq!(start.queue, vec![Shape::T, Shape::I]);
+        assert_eq!(start.board[0][0], Some('G'));
+
+        let play: FrontendMessage = serde_json::from_str(
+            r#"{"type": "play", "move": {"location": {"type": "L", "orientation": "east",
+                "x": 3, "y": 1}, "spin": "none"}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            play,
+            FrontendMessage::Play {
+                mv: Move {
+                    location: PieceLocation {
+                        piece: Shape::L,
+                        orientation: Orientation::East,
+                        ..
+                    },
+                    spin: Spin::None,
+                }
+            }
+        ));
+
+        let unknown: FrontendMessage =
+            serde_json::from_str(r#"{"type": "some_extension"}"#).unwrap();
+        assert_eq!(unknown, FrontendMessage::Unknown);
+    }
+
+    #[test]
+    fn writes_bot_messages() {
+        let suggestion = BotMessage::Suggestion {
+            moves: vec![Move {
+                location: PieceLocation {
+                    piece: Shape::T,
+                    orientation: Orientation::North,
+                    x: 4,
+                    y: 0,
+                },
+                spin: Spin::Full,
+            }],
+        };
+        assert_eq!(
+            serde_json::to_string(&suggestion).unwrap(),
+            r#"{"type":"suggestion","moves":[{"location":{"type":"T","orientation":"north","x":4,"y":0},"spin":"full"}]}"#
+        );
+    }
+}
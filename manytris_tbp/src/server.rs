@@ -0,0 +1,66 @@
+use std::io::{BufRead, Write};
+
+use anyhow::{anyhow, Result};
+use manytris_bot::bot_player::select_next_move;
+use manytris_bot::features::WeightProfile;
+use manytris_bot::BotContext;
+
+use crate::messages::{BotInfo, BotMessage, FrontendMessage};
+use crate::state::TbpState;
+
+/// Play as a TBP bot, answering the frontend's messages until it quits or closes the input.
+///
+/// Suggestions search up to `search_depth` drops, or as far as the known queue reaches.
+pub fn serve_bot(
+    ctx: &impl BotContext,
+    weights: &WeightProfile,
+    search_depth: usize,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> Result<()> {
+    let mut send = |msg: &BotMessage| -> Result<()> {
+        serde_json::to_writer(&mut output, msg)?;
+        writeln!(output)?;
+        output.flush()?;
+        Ok(())
+    };
+
+    send(&BotMessage::Info(BotInfo {
+        name: "manytris".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        author: "manytris".to_string(),
+        features: vec![],
+    }))?;
+
+    let mut state = None;
+    for line in input.lines() {
+        match serde_json::from_str(&line?)? {
+            FrontendMessage::Rules => send(&BotMessage::Ready)?,
+            FrontendMessage::Start(start) => state = Some(TbpState::from_start(&start)?),
+            FrontendMessage::Stop => state = None,
+            FrontendMessage::Suggest => {
+                let state = state
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("Asked for a move before the game started"))?;
+                let depth = search_depth.min(state.known_drops()).max(1);
+                let move_result = select_next_move(&state.game_state(), ctx, weights, depth)?;
+                send(&BotMessage::Suggestion {
+                    moves: vec![state.move_for(&move_result.moves[0])],
+                })?;
+            }
+            FrontendMessage::Play { mv } => {
+                if let Some(state) = &mut state {
+                    state.play(&mv)?;
+                }
+            }
+            FrontendMessage::NewPiece { piece } => {
+                if let Some(state) = &mut state {
+                    state.queue.push(piece);
+                }
+            }
+            FrontendMessage::Quit => break,
+            FrontendMessage::Unknown => {}
+        }
+    }
+    Ok(())
+}
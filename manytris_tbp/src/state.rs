@@ -0,0 +1,372 @@
+use std::iter;
+
+use anyhow::{anyhow, bail, ensure, Result};
+use manytris_bot::bot_player::MovementDescriptor;
+use manytris_bot::placements::{reachable_placements, TuckInput};
+use manytris_core::attack::AttackState;
+use manytris_core::consts;
+use manytris_core::field::{Field, OccupiedBlock, Pos};
+use manytris_core::game_state::{self, GameState, LockResult, TickMutation, TickResult};
+use manytris_core::shapes::Shape;
+use manytris_core::tetromino::Tetromino;
+
+use crate::messages::{Move, Orientation, PieceLocation, Spin, Start, BOARD_HEIGHT};
+
+/// Shapes in the games searched from a TBP position. The queue is repeated to fill it, but
+/// searches only look as far as the real queue.
+const PADDED_QUEUE_LEN: usize = 3 * consts::NUM_PREVIEWS;
+
+/// A position as TBP describes it: the field, the held shape, every known shape to come,
+/// starting with the current one, and the combo and back-to-back going.
+#[derive(Clone, Debug)]
+pub struct TbpState {
+    pub field: Field,
+    pub hold: Option<Shape>,
+    pub queue: Vec<Shape>,
+    pub attack_state: AttackState,
+}
+
+const ORIENTATIONS: [Orientation; 4] = [
+    Orientation::North,
+    Orientation::East,
+    Orientation::South,
+    Orientation::West,
+];
+
+impl Orientation {
+    /// Turn an offset from the center of a piece pointing north to point this way.
+    fn rotate(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        match self {
+            Orientation::North => (x, y),
+            Orientation::East => (y, -x),
+            Orientation::South => (-x, -y),
+            Orientation::West => (-y, x),
+        }
+    }
+}
+
+/// Cells of a piece pointing north, relative to its center.
+fn north_offsets(shape: Shape) -> [(i32, i32); 4] {
+    match shape {
+        Shape::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+        Shape::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        Shape::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+        Shape::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+        Shape::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+        Shape::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+        Shape::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+    }
+}
+
+/// The cells a piece covers at the location.
+pub fn location_cells(location: &PieceLocation) -> [Pos; 4] {
+    north_offsets(location.piece).map(|offset| {
+        let (x, y) = location.orientation.rotate(offset);
+        Pos {
+            x: location.x + x,
+            y: location.y + y,
+        }
+    })
+}
+
+/// A location covering the same cells as the tetromino. Some pieces can be described by more
+/// than one location, and any of them is as good to TBP.
+pub fn tetromino_location(t: &Tetromino) -> PieceLocation {
+    let blocks = t.get_blocks();
+    ORIENTATIONS
+        .iter()
+        .flat_map(|orientation| {
+            let first = orientation.rotate(north_offsets(t.shape)[0]);
+            blocks.iter().map(move |b| PieceLocation {
+                piece: t.shape,
+                orientation: *orientation,
+                x: b.x - first.0,
+                y: b.y - first.1,
+            })
+        })
+        .find(|location| same_cells(&location_cells(location), &blocks))
+        .unwrap()
+}
+
+fn same_cells(a: &[Pos; 4], b: &[Pos; 4]) -> bool {
+    a.iter().all(|p| b.contains(p))
+}
+
+fn tbp_spin(spin: game_state::Spin) -> Spin {
+    match spin {
+        game_state::Spin::None => Spin::None,
+        game_state::Spin::Mini(_) => Spin::Mini,
+        game_state::Spin::Full(_) => Spin::Full,
+    }
+}
+
+fn cell_letter(block: OccupiedBlock) -> char {
+    match block {
+        OccupiedBlock::FromShape(shape) => format!("{shape:?}").chars().next().unwrap(),
+        OccupiedBlock::FromGarbage => 'G',
+    }
+}
+
+impl TbpState {
+    pub fn from_start(start: &Start) -> Result<Self> {
+        ensure!(!start.queue.is_empty(), "The queue is empty");
+        let mut occupied = vec![];
+        for (y, row) in start.board.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if cell.is_some() {
+                    ensure!(
+                        y < consts::MAX_H_US,
+                        "Cells are filled above the {} rows manytris has",
+                        consts::MAX_H
+                    );
+                    occupied.push(Pos {
+                        x: x as i32,
+                        y: y as i32,
+                    });
+                }
+            }
+        }
+        Ok(Self {
+            field: Field::with_initial_occupied(occupied),
+            hold: start.hold,
+            queue: start.queue.clone(),
+            // TBP counts every clear of the combo, and manytris those after the first.
+            attack_state: AttackState::new(
+                start.combo.checked_sub(1).map(|c| c as usize),
+                start.back_to_back,
+            ),
+        })
+    }
+
+    /// The position of a game, with the active shape first in the queue.
+    pub fn from_game(gs: &GameState) -> Self {
+        Self {
+            field: gs.field().clone(),
+            hold: gs.held_tetromino().map(|t| t.shape),
+            queue: iter::once(gs.active_shape())
+                .chain(gs.upcoming_shapes())
+                .collect(),
+            attack_state: gs.attack_state().clone(),
+        }
+    }
+
+    pub fn start(&self) -> Start {
+        let board = (0..BOARD_HEIGHT as i32)
+            .map(|y| {
+                std::array::from_fn(|x| {
+                    self.field
+                        .get_occupied_block(&Pos { x: x as i32, y })
+                        .map(cell_letter)
+                })
+            })
+            .collect();
+        Start {
+            hold: self.hold,
+            queue: self.queue.clone(),
+            combo: self.attack_state.combo().map_or(0, |c| c as u32 + 1),
+            back_to_back: self.attack_state.back_to_back(),
+            board,
+        }
+    }
+
+    /// A game at this position, for searching. Drops past the end of the queue see repeated
+    /// shapes.
+    pub fn game_state(&self) -> GameState {
+        let shapes = self
+            .queue
+            .iter()
+            .copied()
+            .cycle()
+            .take(PADDED_QUEUE_LEN.max(self.queue.len()))
+            .collect();
+        let mut gs = GameState::with_initial_state(shapes, self.field.clone());
+        gs.set_held(self.hold);
+        gs.set_attack_state(self.attack_state.clone());
+        gs
+    }
+
+    /// Drops which can be searched without running past the known queue.
+    pub fn known_drops(&self) -> usize {
+        // Holding into an empty hold uses up a shape.
+        self.queue.len() - usize::from(self.hold.is_none())
+    }
+
+    /// How to make the move, holding first if the move isn't for the current piece.
+    pub fn movement_for(&self, mv: &Move) -> Result<MovementDescriptor> {
+        let mut gs = self.game_state();
+        let hold = mv.location.piece != gs.active_shape();
+        if hold {
+            let _ = gs.tick_mutation(vec![TickMutation::HoldInput]);
+            ensure!(
+                self.queue.len() > 1 || self.hold.is_some(),
+                "Nothing to hold into for {mv:?}"
+            );
+            ensure!(
+                gs.active_shape() == mv.location.piece,
+                "Neither the current nor the held piece is {:?}",
+                mv.location.piece
+            );
+        }
+
+        let cells = location_cells(&mv.location);
+        let want_spun = mv.spin != Spin::None;
+        let matching: Vec<_> = reachable_placements(&gs.make_bitmap_field(), mv.location.piece)
+            .into_iter()
+            .filter(|p| same_cells(&p.tetromino.get_blocks(), &cells))
+            .collect();
+        let spun = |md: &MovementDescriptor| matches!(md.tuck.last(), Some(TuckInput::Rotate(_)));
+        let placement = matching
+            .iter()
+            .find(|p| spun(&p.movement) == want_spun)
+            .or(matching.first())
+            .ok_or_else(|| anyhow!("Can't reach {:?}", mv.location))?;
+
+        Ok(MovementDescriptor {
+            hold,
+            ..placement.movement.clone()
+        })
+    }
+
+    /// The TBP move which the movement makes.
+    pub fn move_for(&self, md: &MovementDescriptor) -> Move {
+        let mut gs = self.game_state();
+        let mut mutations = md.as_tick_mutations();
+        // Everything up to the final drop, so the active tetromino is where the drop starts.
+        let drop = mutations.pop().unwrap();
+        let _ = gs.tick_mutation(mutations);
+        let location = tetromino_location(&gs.field().find_shadow(gs.active_tetromino()));
+
+        let spin = gs
+            .tick_mutation(vec![drop])
+            .into_iter()
+            .find_map(|tr| match tr {
+                TickResult::Lock(LockResult::Ok { spin, .. }) => Some(tbp_spin(spin)),
+                _ => None,
+            })
+            .unwrap_or(Spin::None);
+        Move { location, spin }
+    }
+
+    /// Make the move, taking its pieces off the queue.
+    pub fn play(&mut self, mv: &Move) -> Result<()> {
+        let md = self.movement_for(mv)?;
+        let mut gs = self.game_state();
+        for tr in gs.tick_mutation(md.as_tick_mutations()) {
+            if let TickResult::Lock(LockResult::GameOver) = tr {
+                bail!("{mv:?} tops out");
+            }
+        }
+
+        self.field = gs.field().clone();
+        self.attack_state = gs.attack_state().clone();
+        if md.hold {
+            let held = self.queue.remove(0);
+            if self.hold.is_none() {
+                self.queue.remove(0);
+            }
+            self.hold = Some(held);
+        } else {
+            self.queue.remove(0);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn locations_cover_placements() {
+        let t = TbpState::from_start(&Start {
+            hold: None,
+            queue: vec![Shape::T],
+            combo: 0,
+            back_to_back: false,
+            board: vec![],
+        })
+        .unwrap();
+        assert_eq!(
+            location_cells(&PieceLocation {
+                piece: Shape::T,
+                orientation: Orientation::North,
+                x: 4,
+                y: 1
+            }),
+            [
+                Pos { x: 3, y: 1 },
+                Pos { x: 4, y: 1 },
+                Pos { x: 5, y: 1 },
+                Pos { x: 4, y: 2 }
+            ]
+        );
+
+        for shape in enum_iterator::all::<Shape>() {
+            for placement in reachable_placements(&t.field.make_bitmap_field(), shape) {
+                let location = tetromino_location(&placement.tetromino);
+                assert!(same_cells(
+                    &location_cells(&location),
+                    &placement.tetromino.get_blocks()
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_combo_and_back_to_back() {
+        let start = Start {
+            hold: None,
+            queue: vec![Shape::T],
+            combo: 3,
+            back_to_back: true,
+            board: vec![],
+        };
+        let state = TbpState::from_start(&start).unwrap();
+        assert_eq!(
+            state.game_state().attack_state(),
+            &AttackState::new(Some(2), true)
+        );
+        let restarted = state.start();
+        assert_eq!((restarted.combo, restarted.back_to_back), (3, true));
+    }
+
+    #[test]
+    fn moves_round_trip() {
+        let mut state = TbpState {
+            field: Field::with_initial_occupied([0, 1, 2, 3, 6, 7, 8, 9].map(|x| Pos { x, y: 0 })),
+            hold: None,
+            queue: vec![Shape::T, Shape::O, Shape::I, Shape::L],
+            attack_state: AttackState::default(),
+        };
+
+        // Hold the T and drop the O into the gap, clearing the row.
+        let mut gs = state.game_state();
+        let _ = gs.tick_mutation(vec![TickMutation::HoldInput]);
+        let placement = reachable_placements(&gs.make_bitmap_field(), Shape::O)
+            .into_iter()
+            .find(|p| p.tetromino.get_blocks().contains(&Pos { x: 4, y: 0 }))
+            .unwrap();
+        let md = MovementDescriptor {
+            hold: true,
+            ..placement.movement
+        };
+        let mv = state.move_for(&md);
+        assert_eq!(mv.location.piece, Shape::O);
+        assert_eq!(mv.spin, Spin::None);
+        assert_eq!(state.movement_for(&mv).unwrap(), md);
+
+        state.play(&mv).unwrap();
+        // The top of the O is all that's left after the clear.
+        assert_eq!(state.hold, Some(Shape::T));
+        assert_eq!(state.queue, vec![Shape::I, Shape::L]);
+        assert_eq!(state.start().combo, 1);
+        assert!(state
+            .field
+            .get_occupied_block(&Pos { x: 0, y: 0 })
+            .is_none());
+        assert!(state
+            .field
+            .get_occupied_block(&Pos { x: 4, y: 0 })
+            .is_some());
+    }
+}
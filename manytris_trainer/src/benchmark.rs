@@ -0,0 +1,193 @@
+use std::io::Write;
+use std::time::Duration;
+
+use anyhow::Result;
+use manytris_bot::features::WeightProfile;
+use manytris_bot::simulation::{play_game, GameConfig, GarbagePressure};
+use manytris_bot::BotContext;
+use manytris_core::attack::AttackTable;
+use serde::Serialize;
+
+use crate::report_header::ReportHeader;
+
+/// A fixed set of seeded games which every benchmarked bot plays.
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub name: String,
+    pub games: Vec<GameConfig>,
+}
+
+/// How the bot did in one game of a scenario.
+#[derive(Clone, Debug, Serialize)]
+pub struct GameRecord {
+    pub seed: u64,
+    pub pieces: usize,
+    pub lines_cleared: usize,
+    pub attack: usize,
+    pub topped_out: bool,
+    pub millis_per_move: f64,
+}
+
+/// How one bot configuration did over a scenario's games.
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchmarkReport {
+    pub bot: String,
+    pub scenario: String,
+    pub mean_pieces: f64,
+    pub mean_lines_cleared: f64,
+    pub mean_attack: f64,
+    /// Fraction of the games the bot topped out in.
+    pub game_over_rate: f64,
+    /// Search time over every move of every game.
+    pub millis_per_move: f64,
+    pub games: Vec<GameRecord>,
+}
+
+impl Scenario {
+    /// `games` games seeded from `seed` up, all under the same garbage pressure.
+    pub fn new(
+        name: &str,
+        games: usize,
+        max_pieces: usize,
+        seed: u64,
+        garbage: Option<GarbagePressure>,
+        attack_table: &AttackTable,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            games: (0..games as u64)
+                .map(|game| GameConfig {
+                    max_pieces,
+                    seed: seed + game,
+                    garbage,
+                    attack_table: attack_table.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Play every game of the scenario with the bot.
+pub fn run_scenario(
+    bot: &str,
+    ctx: &impl BotContext,
+    weights: &WeightProfile,
+    search_depth: usize,
+    scenario: &Scenario,
+) -> Result<BenchmarkReport> {
+    let mut games = vec![];
+    let mut search_time = Duration::ZERO;
+    let mut moves = 0;
+    for config in &scenario.games {
+        let result = play_game(ctx, weights, search_depth, config)?;
+        search_time += result.search_time;
+        moves += result.pieces + usize::from(result.topped_out);
+        games.push(GameRecord {
+            seed: config.seed,
+            pieces: result.pieces,
+            lines_cleared: result.lines_cleared,
+            attack: result.attack,
+            topped_out: result.topped_out,
+            millis_per_move: millis(result.search_time_per_move()),
+        });
+    }
+
+    let count = games.len().max(1) as f64;
+    let mean = |f: fn(&GameRecord) -> usize| games.iter().map(f).sum::<usize>() as f64 / count;
+    Ok(BenchmarkReport {
+        bot: bot.to_string(),
+        scenario: scenario.name.clone(),
+        mean_pieces: mean(|g| g.pieces),
+        mean_lines_cleared: mean(|g| g.lines_cleared),
+        mean_attack: mean(|g| g.attack),
+        game_over_rate: mean(|g| usize::from(g.topped_out)),
+        millis_per_move: millis(search_time) / moves.max(1) as f64,
+        games,
+    })
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// Quote a text field, in case it holds commas.
+fn csv_text(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// Write the header as comment lines, then a row for each report, without its games.
+pub fn write_csv(
+    header: &ReportHeader,
+    reports: &[BenchmarkReport],
+    mut out: impl Write,
+) -> Result<()> {
+    write!(out, "{}", header.comment_lines())?;
+    writeln!(
+        out,
+        "bot,scenario,games,mean_pieces,mean_lines_cleared,mean_attack,game_over_rate,millis_per_move"
+    )?;
+    for r in reports {
+        writeln!(
+            out,
+            "{},{},{},{:.2},{:.2},{:.2},{:.3},{:.3}",
+            csv_text(&r.bot),
+            csv_text(&r.scenario),
+            r.games.len(),
+            r.mean_pieces,
+            r.mean_lines_cleared,
+            r.mean_attack,
+            r.game_over_rate,
+            r.millis_per_move
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the header and the reports, with every game's results, as one JSON object.
+pub fn write_json(
+    header: &ReportHeader,
+    reports: &[BenchmarkReport],
+    out: impl Write,
+) -> Result<()> {
+    #[derive(Serialize)]
+    struct Run<'a> {
+        #[serde(flatten)]
+        header: &'a ReportHeader,
+        reports: &'a [BenchmarkReport],
+    }
+    serde_json::to_writer_pretty(out, &Run { header, reports })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use manytris_bot::bot_cpu::CpuBotContext;
+
+    #[test]
+    fn reports_scenarios() {
+        let scenario = Scenario::new("solo", 2, 10, 47, None, &AttackTable::default());
+        let report = run_scenario(
+            "cpu,survival",
+            &CpuBotContext::default(),
+            &WeightProfile::survival(),
+            1,
+            &scenario,
+        )
+        .unwrap();
+        assert_eq!(report.games.len(), 2);
+        assert_eq!(report.games[1].seed, 48);
+        assert_eq!(report.mean_pieces, 10.0);
+        assert_eq!(report.game_over_rate, 0.0);
+
+        let header = ReportHeader::new(&scenario.name).unwrap();
+        let mut csv = vec![];
+        write_csv(&header, &[report], &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("# commit: "));
+        assert_eq!(lines[1], "# config: \"solo\"");
+        assert!(lines[3].starts_with("\"cpu,survival\",\"solo\",2,10.00,"));
+    }
+}
@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{ensure, Result};
+use clap::Parser;
+use manytris_bot::backend::{BotBackend, CpuSearchContext, CpuSearchOptions};
+use manytris_bot::features::WeightProfile;
+use manytris_bot::simulation::GarbagePressure;
+use manytris_bot::BotContext;
+use manytris_core::attack::AttackTable;
+use manytris_trainer::benchmark::{run_scenario, write_csv, write_json, BenchmarkReport, Scenario};
+use manytris_trainer::report_header::ReportHeader;
+use serde::Serialize;
+
+/// Play the same seeded games with each bot configuration, and report how they did.
+#[derive(Parser, Debug, Serialize)]
+#[command(version, about, long_about = None)]
+struct BenchmarkArgs {
+    /// Comma separated backends to benchmark.
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "cpu")]
+    backends: Vec<BotBackend>,
+
+    /// Comma separated weights to benchmark each backend with: "survival", "versus", or paths of
+    /// JSON weight profiles.
+    #[arg(long, value_delimiter = ',', default_value = "survival,versus")]
+    weights: Vec<String>,
+
+    #[arg(long, default_value = "2")]
+    search_depth: usize,
+
+    #[command(flatten)]
+    cpu: CpuSearchOptions,
+
+    /// Games in each scenario.
+    #[arg(long, default_value = "10")]
+    games: usize,
+
+    /// Pieces after which a game ends if the bot hasn't topped out.
+    #[arg(long, default_value = "500")]
+    max_pieces: usize,
+
+    /// The seed of each scenario's first game. Each later game's seed is one more.
+    #[arg(long, default_value = "0")]
+    seed: u64,
+
+    /// Lines of garbage sent at the bot at a time in the garbage scenario.
+    #[arg(long, default_value = "2")]
+    garbage_lines: usize,
+
+    /// Pieces between each send of garbage in the garbage scenario.
+    #[arg(long, default_value = "5")]
+    garbage_interval: usize,
+
+    /// Scores the bot's attack: "classic", "guideline", or the path of a JSON attack table.
+    #[arg(long, default_value = "classic", value_parser = parse_attack_table)]
+    attack_table: AttackTable,
+
+    /// Write a row per bot and scenario here.
+    #[arg(long)]
+    csv: Option<PathBuf>,
+
+    /// Write the reports, with every game's results, here.
+    #[arg(long)]
+    json: Option<PathBuf>,
+}
+
+/// Backends the trainer can run, with the exhaustive searches fastest first. Vulkan needs the
+/// trainer built with the bot_vulkan feature.
+fn available_backends() -> Vec<BotBackend> {
+    let mut backends = vec![];
+    if cfg!(feature = "bot_vulkan") {
+        backends.push(BotBackend::Vulkan);
+    }
+    backends.extend([BotBackend::Cpu, BotBackend::Beam]);
+    backends
+}
+
+fn parse_attack_table(arg: &str) -> Result<AttackTable> {
+    Ok(match arg {
+        "classic" => AttackTable::default(),
+        "guideline" => AttackTable::guideline(),
+        path => serde_json::from_str(&fs::read_to_string(path)?)?,
+    })
+}
+
+fn main() -> Result<()> {
+    let args = BenchmarkArgs::parse();
+    let header = ReportHeader::new(&args)?;
+    // Fail before spending time on the other backends.
+    let available = available_backends();
+    for backend in &args.backends {
+        ensure!(
+            *backend == BotBackend::Auto || available.contains(backend),
+            "The {backend:?} backend isn't in this build"
+        );
+    }
+    let garbage = GarbagePressure {
+        lines: args.garbage_lines,
+        interval: args.garbage_interval,
+    };
+    let scenarios = [("solo", None), ("garbage", Some(garbage))].map(|(name, garbage)| {
+        Scenario::new(
+            name,
+            args.games,
+            args.max_pieces,
+            args.seed,
+            garbage,
+            &args.attack_table,
+        )
+    });
+
+    let mut reports = vec![];
+    for backend in &args.backends {
+        for weights_name in &args.weights {
+            let weights = WeightProfile::load(weights_name)?;
+            let bot = format!("{backend:?} {weights_name}");
+            let attack_table = args.attack_table.clone();
+            let backend = backend.candidates(&available)[0];
+            if let Some(ctx) = CpuSearchContext::new(backend, &args.cpu, attack_table) {
+                benchmark(&bot, &ctx, &weights, &args, &scenarios, &mut reports)?;
+            } else if backend == BotBackend::Vulkan {
+                #[cfg(feature = "bot_vulkan")]
+                {
+                    let ctx = manytris_bot_vulkan::VulkanBotContext::init()?;
+                    benchmark(&bot, &ctx, &weights, &args, &scenarios, &mut reports)?;
+                }
+            }
+        }
+    }
+
+    if let Some(path) = &args.csv {
+        write_csv(&header, &reports, fs::File::create(path)?)?;
+    }
+    if let Some(path) = &args.json {
+        write_json(&header, &reports, fs::File::create(path)?)?;
+    }
+    Ok(())
+}
+
+fn benchmark(
+    bot: &str,
+    ctx: &impl BotContext,
+    weights: &WeightProfile,
+    args: &BenchmarkArgs,
+    scenarios: &[Scenario],
+    reports: &mut Vec<BenchmarkReport>,
+) -> Result<()> {
+    for scenario in scenarios {
+        let report = run_scenario(bot, ctx, weights, args.search_depth, scenario)?;
+        println!(
+            "{bot}, {}: {:.1} pieces, {:.1} lines, {:.1} attack, {:.0}% topped out, {:.2} ms/move",
+            report.scenario,
+            report.mean_pieces,
+            report.mean_lines_cleared,
+            report.mean_attack,
+            report.game_over_rate * 100.0,
+            report.millis_per_move
+        );
+        reports.push(report);
+    }
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context, Result};
+use clap::Parser;
+use manytris_bot::backend::{BotSearchArgs, CpuSearchContext};
+use manytris_bot::bot_player::explain_next_moves;
+use manytris_bot::features::WeightProfile;
+use manytris_core::attack::AttackTable;
+use manytris_core::game_state::GameState;
+use manytris_trainer::explain::{describe_move, render_feature_table, render_side_by_side};
+use manytris_trainer::report_header::ReportHeader;
+use serde::Serialize;
+
+/// Show the bot's best candidate moves for a saved game state, with their resulting boards and
+/// how each feature scored them.
+#[derive(Parser, Debug, Serialize)]
+#[command(version, about, long_about = None)]
+struct ExplainArgs {
+    /// A JSON serialized game state.
+    game_state: PathBuf,
+
+    #[arg(long, default_value = "2")]
+    search_depth: usize,
+
+    /// Candidates to show, each with a different first move.
+    #[arg(long, default_value = "3")]
+    count: usize,
+
+    #[command(flatten)]
+    search: BotSearchArgs,
+}
+
+fn main() -> Result<()> {
+    let args = ExplainArgs::parse();
+    let json = fs::read_to_string(&args.game_state)
+        .with_context(|| format!("Couldn't read game state {}", args.game_state.display()))?;
+    let gs: GameState = serde_json::from_str(&json)?;
+    let search = &args.search;
+    let weights = WeightProfile::load(&search.bot_weights)?;
+    let ctx = CpuSearchContext::new(search.bot_backend, &search.cpu, AttackTable::default())
+        .context("Only the CPU backends can explain moves")?;
+
+    let explained = explain_next_moves(&gs, &ctx, &weights, args.search_depth, args.count)?;
+    ensure!(!explained.is_empty(), "The bot found no moves");
+
+    print!("{}", ReportHeader::new(&args)?.comment_lines());
+
+    for (i, em) in explained.iter().enumerate() {
+        let moves: Vec<_> = em.moves.iter().map(describe_move).collect();
+        println!("#{}: {:.1}: {}", i + 1, em.score, moves.join(" / "));
+    }
+    println!();
+
+    let current = gs.make_bitmap_field();
+    let titles: Vec<_> = (1..=explained.len()).map(|i| format!("#{i}")).collect();
+    let mut boards = vec![("now", &current)];
+    boards.extend(
+        titles
+            .iter()
+            .map(String::as_str)
+            .zip(explained.iter().map(|em| &em.field)),
+    );
+    println!("{}", render_side_by_side(&boards));
+    print!("{}", render_feature_table(&explained));
+    Ok(())
+}
@@ -0,0 +1,250 @@
+use rand::rngs::StdRng;
+use rand_distr::{Distribution, StandardNormal};
+use serde::{Deserialize, Serialize};
+
+use crate::optimizer::{rank, Optimizer};
+
+/// The covariance matrix adaptation evolution strategy. Candidates are sampled from a normal
+/// distribution, which moves towards the fittest and stretches along the directions they
+/// improved in. The constants follow Hansen's "The CMA Evolution Strategy: A Tutorial".
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CmaEs {
+    mean: Vec<f64>,
+    step_size: f64,
+    covariance: Vec<Vec<f64>>,
+    step_path: Vec<f64>,
+    covariance_path: Vec<f64>,
+    population_size: usize,
+    generation: usize,
+}
+
+/// The eigenvalues and eigenvectors of a symmetric matrix, found with Jacobi rotations. The
+/// eigenvectors are the columns of the returned matrix.
+fn symmetric_eigen(matrix: &[Vec<f64>]) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let n = matrix.len();
+    let mut a = matrix.to_vec();
+    let mut v: Vec<Vec<f64>> = (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect();
+
+    for _ in 0..100 {
+        let off_diagonal: f64 = (0..n)
+            .flat_map(|i| (0..n).filter(move |j| *j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a[i][j].powi(2))
+            .sum();
+        let diagonal: f64 = (0..n).map(|i| a[i][i].powi(2)).sum();
+        if off_diagonal <= 1e-24 * diagonal.max(f64::MIN_POSITIVE) {
+            break;
+        }
+
+        for p in 0..n {
+            for q in (p + 1)..n {
+                if a[p][q] == 0.0 {
+                    continue;
+                }
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in a.iter_mut().chain(v.iter_mut()) {
+                    let (kp, kq) = (row[p], row[q]);
+                    row[p] = c * kp - s * kq;
+                    row[q] = s * kp + c * kq;
+                }
+                let (upper, lower) = a.split_at_mut(q);
+                for (pk, qk) in upper[p].iter_mut().zip(lower[0].iter_mut()) {
+                    (*pk, *qk) = (c * *pk - s * *qk, s * *pk + c * *qk);
+                }
+            }
+        }
+    }
+    ((0..n).map(|i| a[i][i]).collect(), v)
+}
+
+impl CmaEs {
+    /// Start sampling around the initial genes, `step_size` away on average in each gene.
+    /// Without a population size, the tutorial's default for the number of genes is used.
+    pub fn new(initial: Vec<f64>, step_size: f64, population_size: Option<usize>) -> Self {
+        let n = initial.len();
+        Self {
+            covariance: (0..n)
+                .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+                .collect(),
+            step_path: vec![0.0; n],
+            covariance_path: vec![0.0; n],
+            population_size: population_size
+                .unwrap_or(4 + (3.0 * (n as f64).ln()) as usize)
+                .max(4),
+            generation: 0,
+            mean: initial,
+            step_size,
+        }
+    }
+
+    pub fn mean(&self) -> &[f64] {
+        &self.mean
+    }
+
+    /// Recombination weights of the fittest half of the candidates, and their effective number.
+    fn recombination_weights(&self) -> (Vec<f64>, f64) {
+        let mu = self.population_size / 2;
+        let raw: Vec<_> = (1..=mu)
+            .map(|i| (mu as f64 + 0.5).ln() - (i as f64).ln())
+            .collect();
+        let total: f64 = raw.iter().sum();
+        let weights: Vec<_> = raw.iter().map(|w| w / total).collect();
+        let mu_eff = 1.0 / weights.iter().map(|w| w * w).sum::<f64>();
+        (weights, mu_eff)
+    }
+
+    /// Eigenvectors of the covariance, and the square roots of its eigenvalues.
+    fn decompose(&self) -> (Vec<Vec<f64>>, Vec<f64>) {
+        let (values, vectors) = symmetric_eigen(&self.covariance);
+        (
+            vectors,
+            values.iter().map(|v| v.max(1e-20).sqrt()).collect(),
+        )
+    }
+}
+
+impl Optimizer for CmaEs {
+    fn ask(&mut self, rng: &mut StdRng) -> Vec<Vec<f64>> {
+        let n = self.mean.len();
+        let (b, d) = self.decompose();
+        (0..self.population_size)
+            .map(|_| {
+                let z: Vec<f64> = (0..n).map(|_| StandardNormal.sample(rng)).collect();
+                (0..n)
+                    .map(|i| {
+                        let y: f64 = (0..n).map(|j| b[i][j] * d[j] * z[j]).sum();
+                        self.mean[i] + self.step_size * y
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn tell(&mut self, candidates: Vec<Vec<f64>>, fitnesses: &[f64], _rng: &mut StdRng) {
+        let n = self.mean.len() as f64;
+        let (weights, mu_eff) = self.recombination_weights();
+        let c_c = (4.0 + mu_eff / n) / (n + 4.0 + 2.0 * mu_eff / n);
+        let c_s = (mu_eff + 2.0) / (n + mu_eff + 5.0);
+        let c_1 = 2.0 / ((n + 1.3).powi(2) + mu_eff);
+        let c_mu =
+            (1.0 - c_1).min(2.0 * (mu_eff - 2.0 + 1.0 / mu_eff) / ((n + 2.0).powi(2) + mu_eff));
+        let damping = 1.0 + 2.0 * (((mu_eff - 1.0) / (n + 1.0)).sqrt() - 1.0).max(0.0) + c_s;
+        let expected_norm = n.sqrt() * (1.0 - 1.0 / (4.0 * n) + 1.0 / (21.0 * n * n));
+
+        // Steps from the old mean to the fittest candidates, in units of the step size.
+        let steps: Vec<Vec<f64>> = rank(fitnesses)
+            .iter()
+            .take(weights.len())
+            .map(|i| {
+                candidates[*i]
+                    .iter()
+                    .zip(&self.mean)
+                    .map(|(x, m)| (x - m) / self.step_size)
+                    .collect()
+            })
+            .collect();
+        let mean_step: Vec<f64> = (0..self.mean.len())
+            .map(|i| weights.iter().zip(&steps).map(|(w, y)| w * y[i]).sum())
+            .collect();
+        for (m, y) in self.mean.iter_mut().zip(&mean_step) {
+            *m += self.step_size * y;
+        }
+
+        // The mean step with the covariance's stretching undone.
+        let (b, d) = self.decompose();
+        let rotated: Vec<f64> = (0..d.len())
+            .map(|j| (0..d.len()).map(|i| b[i][j] * mean_step[i]).sum::<f64>() / d[j])
+            .collect();
+        let whitened: Vec<f64> = (0..d.len())
+            .map(|i| (0..d.len()).map(|j| b[i][j] * rotated[j]).sum())
+            .collect();
+
+        let s_scale = (c_s * (2.0 - c_s) * mu_eff).sqrt();
+        for (p, w) in self.step_path.iter_mut().zip(&whitened) {
+            *p = (1.0 - c_s) * *p + s_scale * w;
+        }
+        let step_norm = self.step_path.iter().map(|p| p * p).sum::<f64>().sqrt();
+        self.generation += 1;
+        // Hold the covariance path still while the step path is unusually long, so a sudden jump
+        // in step size doesn't stretch the covariance too.
+        let steady =
+            step_norm / (1.0 - (1.0 - c_s).powi(2 * self.generation as i32)).sqrt() / expected_norm
+                < 1.4 + 2.0 / (n + 1.0);
+        let h_sigma = if steady { 1.0 } else { 0.0 };
+
+        let c_scale = h_sigma * (c_c * (2.0 - c_c) * mu_eff).sqrt();
+        for (p, y) in self.covariance_path.iter_mut().zip(&mean_step) {
+            *p = (1.0 - c_c) * *p + c_scale * y;
+        }
+
+        let pc = &self.covariance_path;
+        let lost_variance = (1.0 - h_sigma) * c_c * (2.0 - c_c);
+        for (i, row) in self.covariance.iter_mut().enumerate() {
+            for (j, c) in row.iter_mut().enumerate() {
+                let rank_mu: f64 = weights
+                    .iter()
+                    .zip(&steps)
+                    .map(|(w, y)| w * y[i] * y[j])
+                    .sum();
+                *c = (1.0 - c_1 - c_mu) * *c
+                    + c_1 * (pc[i] * pc[j] + lost_variance * *c)
+                    + c_mu * rank_mu;
+            }
+        }
+
+        self.step_size *= ((c_s / damping) * (step_norm / expected_norm - 1.0)).exp();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn decomposes_symmetric_matrices() {
+        let m = vec![
+            vec![4.0, 1.0, -2.0],
+            vec![1.0, 3.0, 0.5],
+            vec![-2.0, 0.5, 5.0],
+        ];
+        let (values, vectors) = symmetric_eigen(&m);
+        for i in 0..3 {
+            for j in 0..3 {
+                let rebuilt: f64 = (0..3)
+                    .map(|k| vectors[i][k] * values[k] * vectors[j][k])
+                    .sum();
+                assert!((rebuilt - m[i][j]).abs() < 1e-9, "{rebuilt} vs {}", m[i][j]);
+            }
+        }
+    }
+
+    #[test]
+    fn converges_on_the_peak() {
+        // Stretched so the covariance has to adapt.
+        let peak = [300.0, -200.0, 50.0, 0.0];
+        let fitness = |genes: &Vec<f64>| -> f64 {
+            -genes
+                .iter()
+                .zip(peak)
+                .enumerate()
+                .map(|(i, (g, p))| (g - p).powi(2) * 10f64.powi(i as i32))
+                .sum::<f64>()
+        };
+        let mut rng = StdRng::seed_from_u64(46);
+        let mut optimizer = CmaEs::new(vec![0.0; 4], 100.0, None);
+
+        for _ in 0..300 {
+            let candidates = optimizer.ask(&mut rng);
+            let fitnesses: Vec<_> = candidates.iter().map(fitness).collect();
+            optimizer.tell(candidates, &fitnesses, &mut rng);
+        }
+        for (m, p) in optimizer.mean().iter().zip(peak) {
+            assert!((m - p).abs() < 0.1, "{:?}", optimizer.mean());
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt::Write;
+
+use manytris_bot::bot_player::{ExplainedMove, MovementDescriptor};
+use manytris_core::bitmap_field::BitmapField;
+use manytris_core::consts;
+use manytris_core::field::Pos;
+
+/// Columns between boards drawn side by side.
+const GAP: &str = "  ";
+
+/// One line describing a move, e.g. "hold, T cw x1, right 3, tuck [SoftDrop]".
+pub fn describe_move(md: &MovementDescriptor) -> String {
+    let mut desc = String::new();
+    if md.hold {
+        desc.push_str("hold, ");
+    }
+    write!(desc, "{:?} cw x{}", md.shape, md.cw_rotations).unwrap();
+    match md.shifts_right {
+        0 => {}
+        s if s > 0 => write!(desc, ", right {s}").unwrap(),
+        s => write!(desc, ", left {}", -s).unwrap(),
+    }
+    if !md.tuck.is_empty() {
+        write!(desc, ", tuck {:?}", md.tuck).unwrap();
+    }
+    desc
+}
+
+/// Draw the boards next to each other under their titles, up to the tallest stack.
+pub fn render_side_by_side(boards: &[(&str, &BitmapField)]) -> String {
+    let width = consts::W_US + 2;
+    let height = boards
+        .iter()
+        .map(|(_, field)| {
+            (0..consts::MAX_H_US)
+                .rfind(|y| field.row(*y) != 0)
+                .map_or(0, |y| y + 1)
+        })
+        .max()
+        .unwrap_or(0);
+
+    let mut lines = vec![];
+    lines.push(
+        boards
+            .iter()
+            .map(|(title, _)| format!("{:width$.width$}", title))
+            .collect::<Vec<_>>(),
+    );
+    for y in (0..height as i32).rev() {
+        lines.push(
+            boards
+                .iter()
+                .map(|(_, field)| {
+                    let row: String = (0..consts::W)
+                        .map(|x| {
+                            if field.occupied(&Pos { x, y }) {
+                                'X'
+                            } else {
+                                ' '
+                            }
+                        })
+                        .collect();
+                    format!("|{row}|")
+                })
+                .collect(),
+        );
+    }
+    lines.push(vec![
+        format!("+{}+", "-".repeat(consts::W_US));
+        boards.len()
+    ]);
+
+    lines
+        .iter()
+        .map(|cells| format!("{}\n", cells.join(GAP).trim_end()))
+        .collect()
+}
+
+/// A row per feature with each candidate's value and, after it, its contribution to the score.
+/// Features no candidate weighs are left out.
+pub fn render_feature_table(explained: &[ExplainedMove]) -> String {
+    let mut table = format!("{:20}", "feature");
+    for i in 0..explained.len() {
+        write!(table, "{:>24}", format!("#{} value (score)", i + 1)).unwrap();
+    }
+    table.push('\n');
+
+    let Some(first) = explained.first() else {
+        return table;
+    };
+    for (i, fs) in first.features.iter().enumerate() {
+        if explained.iter().all(|em| em.features[i].weight == 0.0) {
+            continue;
+        }
+        write!(table, "{:20}", format!("{:?}", fs.feature)).unwrap();
+        for em in explained {
+            let fs = &em.features[i];
+            write!(
+                table,
+                "{:>24}",
+                format!("{} ({:.1})", fs.value, fs.contribution())
+            )
+            .unwrap();
+        }
+        table.push('\n');
+    }
+    write!(table, "{:20}", "total").unwrap();
+    for em in explained {
+        write!(table, "{:>24}", format!("{:.1}", em.score)).unwrap();
+    }
+    table.push('\n');
+    table
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn renders_boards_side_by_side() {
+        let mut low = BitmapField::default();
+        low.set(&Pos { x: 0, y: 0 });
+        let mut high = BitmapField::default();
+        high.set(&Pos { x: 9, y: 0 });
+        high.set(&Pos { x: 9, y: 1 });
+
+        let rendered = render_side_by_side(&[("now", &low), ("#1", &high)]);
+        assert_eq!(
+            rendered,
+            [
+                "now           #1",
+                "|          |  |         X|",
+                "|X         |  |         X|",
+                "+----------+  +----------+",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+
+use crate::optimizer::{rank, Optimizer};
+
+/// Chance of each gene of a child being mutated.
+const MUTATION_RATE: f64 = 0.2;
+
+/// A genetic algorithm: the fittest candidates carry over, and the rest of each generation are
+/// children of tournament winners, crossed over gene by gene and mutated.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Genetic {
+    population: Vec<Vec<f64>>,
+    population_size: usize,
+    /// Standard deviation of a mutation.
+    mutation_scale: f64,
+}
+
+impl Genetic {
+    /// The first generation is the initial genes and mutations of them.
+    pub fn new(initial: Vec<f64>, population_size: usize, mutation_scale: f64) -> Self {
+        Self {
+            population: vec![initial],
+            population_size: population_size.max(2),
+            mutation_scale,
+        }
+    }
+
+    /// Candidates carried over to the next generation unchanged.
+    fn elites(&self) -> usize {
+        (self.population_size / 5).max(1)
+    }
+
+    fn mutate(&self, genes: &mut [f64], rate: f64, rng: &mut StdRng) {
+        let normal = Normal::new(0.0, self.mutation_scale).unwrap();
+        for gene in genes {
+            if rng.gen_bool(rate) {
+                *gene += normal.sample(rng);
+            }
+        }
+    }
+}
+
+impl Optimizer for Genetic {
+    fn ask(&mut self, rng: &mut StdRng) -> Vec<Vec<f64>> {
+        while self.population.len() < self.population_size {
+            let mut genes = self.population[0].clone();
+            self.mutate(&mut genes, 1.0, rng);
+            self.population.push(genes);
+        }
+        self.population.clone()
+    }
+
+    fn tell(&mut self, candidates: Vec<Vec<f64>>, fitnesses: &[f64], rng: &mut StdRng) {
+        let ranked = rank(fitnesses);
+        let tournament = |rng: &mut StdRng| {
+            let a = rng.gen_range(0..candidates.len());
+            let b = rng.gen_range(0..candidates.len());
+            &candidates[if fitnesses[a] >= fitnesses[b] { a } else { b }]
+        };
+
+        let mut next: Vec<_> = ranked[..self.elites()]
+            .iter()
+            .map(|i| candidates[*i].clone())
+            .collect();
+        while next.len() < self.population_size {
+            let (mother, father) = (tournament(rng), tournament(rng));
+            let mut child: Vec<_> = mother
+                .iter()
+                .zip(father)
+                .map(|(m, f)| if rng.gen_bool(0.5) { *m } else { *f })
+                .collect();
+            self.mutate(&mut child, MUTATION_RATE, rng);
+            next.push(child);
+        }
+        self.population = next;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn climbs_towards_the_peak() {
+        let peak = [300.0, -200.0, 50.0];
+        let fitness = |genes: &Vec<f64>| -> f64 {
+            -genes
+                .iter()
+                .zip(peak)
+                .map(|(g, p)| (g - p).powi(2))
+                .sum::<f64>()
+        };
+        let mut rng = StdRng::seed_from_u64(46);
+        let mut optimizer = Genetic::new(vec![0.0; 3], 20, 50.0);
+
+        let start = fitness(&vec![0.0; 3]);
+        for _ in 0..60 {
+            let candidates = optimizer.ask(&mut rng);
+            let fitnesses: Vec<_> = candidates.iter().map(fitness).collect();
+            optimizer.tell(candidates, &fitnesses, &mut rng);
+        }
+        let best = optimizer
+            .population
+            .iter()
+            .map(fitness)
+            .fold(f64::MIN, f64::max);
+        assert!(best > start / 100.0, "{best} vs {start}");
+    }
+}
@@ -0,0 +1,7 @@
+pub mod benchmark;
+pub mod cma_es;
+pub mod explain;
+pub mod genetic;
+pub mod optimizer;
+pub mod report_header;
+pub mod training;
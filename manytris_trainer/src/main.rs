@@ -0,0 +1,160 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{ensure, Result};
+use clap::{Parser, ValueEnum};
+use manytris_bot::bot_cpu::CpuBotContext;
+use manytris_bot::features::{Feature, WeightProfile};
+use manytris_bot::BotContext;
+use manytris_trainer::cma_es::CmaEs;
+use manytris_trainer::genetic::Genetic;
+use manytris_trainer::optimizer::OptimizerState;
+use manytris_trainer::training::{Checkpoint, FitnessConfig};
+
+/// Train the bot's feature weights with seeded solo games searched on the CPU.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct TrainerArgs {
+    /// Where training is saved after every generation.
+    #[arg(long, default_value = "trainer_checkpoint.json")]
+    checkpoint: PathBuf,
+
+    /// Carry on from the checkpoint. Its own training settings are used, and the ones given here
+    /// are ignored.
+    #[arg(long)]
+    resume: bool,
+
+    /// Where the best weights so far are written, for loading with the bot's weights option.
+    #[arg(long, default_value = "trained_weights.json")]
+    output: PathBuf,
+
+    /// Generations to train for, including any before resuming.
+    #[arg(long, default_value = "50")]
+    generations: u64,
+
+    #[arg(long, value_enum, default_value_t)]
+    optimizer: OptimizerKind,
+
+    /// Candidates in each generation. CMA-ES picks its own for the number of features if unset.
+    #[arg(long)]
+    population: Option<usize>,
+
+    /// Weights to start from: "survival", "versus", or the path of a JSON weight profile.
+    #[arg(long, default_value = "survival")]
+    weights: String,
+
+    /// Comma separated features to train. Defaults to every feature but attack, which solo games
+    /// never score.
+    #[arg(long, value_delimiter = ',', value_parser = parse_feature)]
+    features: Vec<Feature>,
+
+    /// How far, in weight units, the first generation strays from the starting weights.
+    #[arg(long, default_value = "500")]
+    step_size: f64,
+
+    /// Games each candidate plays.
+    #[arg(long, default_value = "5")]
+    games: usize,
+
+    /// Pieces after which a game ends if the bot hasn't topped out.
+    #[arg(long, default_value = "300")]
+    max_pieces: usize,
+
+    #[arg(long, default_value = "1")]
+    search_depth: usize,
+
+    /// Seeds the games' shapes and the optimizer.
+    #[arg(long, default_value = "0")]
+    seed: u64,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+enum OptimizerKind {
+    #[default]
+    Genetic,
+    CmaEs,
+}
+
+fn parse_feature(name: &str) -> Result<Feature, String> {
+    serde_json::from_value(serde_json::Value::String(name.to_string())).map_err(|e| e.to_string())
+}
+
+fn new_checkpoint(args: &TrainerArgs, ctx: &impl BotContext) -> Result<Checkpoint> {
+    let supported = ctx.supported_features();
+    let features = if args.features.is_empty() {
+        supported
+            .into_iter()
+            .filter(|f| *f != Feature::Attack)
+            .collect()
+    } else {
+        for feature in &args.features {
+            ensure!(supported.contains(feature), "Can't train {feature:?}");
+        }
+        args.features.clone()
+    };
+
+    let weights = WeightProfile::load(&args.weights)?;
+    let initial = features.iter().map(|f| weights.weight(*f) as f64).collect();
+    let optimizer = match args.optimizer {
+        OptimizerKind::Genetic => OptimizerState::Genetic(Genetic::new(
+            initial,
+            args.population.unwrap_or(20),
+            args.step_size,
+        )),
+        OptimizerKind::CmaEs => {
+            OptimizerState::CmaEs(CmaEs::new(initial, args.step_size, args.population))
+        }
+    };
+
+    Ok(Checkpoint {
+        features,
+        fitness: FitnessConfig {
+            games: args.games,
+            max_pieces: args.max_pieces,
+            search_depth: args.search_depth,
+            seed: args.seed,
+        },
+        optimizer,
+        seed: args.seed,
+        generation: 0,
+        best: None,
+    })
+}
+
+fn main() -> Result<()> {
+    let args = TrainerArgs::parse();
+    let ctx = CpuBotContext::default();
+    let mut checkpoint = if args.resume {
+        Checkpoint::load(&args.checkpoint)?
+    } else {
+        new_checkpoint(&args, &ctx)?
+    };
+    println!(
+        "Training {:?} from generation {}",
+        checkpoint.features, checkpoint.generation
+    );
+
+    while checkpoint.generation < args.generations {
+        let fitnesses = checkpoint.step(&ctx)?;
+        checkpoint.save(&args.checkpoint)?;
+
+        let mean = fitnesses.iter().sum::<f64>() / fitnesses.len() as f64;
+        let generation_best = fitnesses.iter().copied().fold(f64::MIN, f64::max);
+        println!(
+            "Generation {}: best {generation_best:.1}, mean {mean:.1}",
+            checkpoint.generation
+        );
+        if let Some(best) = &checkpoint.best {
+            fs::write(&args.output, serde_json::to_string_pretty(&best.weights)?)?;
+        }
+    }
+
+    if let Some(best) = &checkpoint.best {
+        println!(
+            "Best fitness {:.1}, weights written to {}",
+            best.fitness,
+            args.output.display()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+use crate::cma_es::CmaEs;
+use crate::genetic::Genetic;
+
+/// Searches for the genes with the highest fitness, a generation at a time. Optimizers draw all
+/// their randomness from the generator they're given, so seeding it makes training reproducible.
+pub trait Optimizer {
+    /// The candidates to score this generation.
+    fn ask(&mut self, rng: &mut StdRng) -> Vec<Vec<f64>>;
+
+    /// Learn from the fitness of each candidate from `ask`, where higher is better.
+    fn tell(&mut self, candidates: Vec<Vec<f64>>, fitnesses: &[f64], rng: &mut StdRng);
+}
+
+/// Any of the optimizers, for saving to and resuming from a checkpoint.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OptimizerState {
+    Genetic(Genetic),
+    CmaEs(CmaEs),
+}
+
+impl Optimizer for OptimizerState {
+    fn ask(&mut self, rng: &mut StdRng) -> Vec<Vec<f64>> {
+        match self {
+            OptimizerState::Genetic(optimizer) => optimizer.ask(rng),
+            OptimizerState::CmaEs(optimizer) => optimizer.ask(rng),
+        }
+    }
+
+    fn tell(&mut self, candidates: Vec<Vec<f64>>, fitnesses: &[f64], rng: &mut StdRng) {
+        match self {
+            OptimizerState::Genetic(optimizer) => optimizer.tell(candidates, fitnesses, rng),
+            OptimizerState::CmaEs(optimizer) => optimizer.tell(candidates, fitnesses, rng),
+        }
+    }
+}
+
+/// Indexes of the candidates, fittest first.
+pub fn rank(fitnesses: &[f64]) -> Vec<usize> {
+    let mut order: Vec<_> = (0..fitnesses.len()).collect();
+    order.sort_by(|a, b| fitnesses[*b].total_cmp(&fitnesses[*a]));
+    order
+}
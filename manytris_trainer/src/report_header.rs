@@ -0,0 +1,46 @@
+use std::process::Command;
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// What a report was made by: the commit and the options it ran with, so runs can be compared
+/// and repeated.
+#[derive(Clone, Debug, Serialize)]
+pub struct ReportHeader {
+    /// The checked out commit, with "-dirty" after it if there were uncommitted changes, or
+    /// "unknown" if git couldn't tell.
+    pub commit: String,
+    pub config: serde_json::Value,
+}
+
+impl ReportHeader {
+    pub fn new(config: &impl Serialize) -> Result<Self> {
+        Ok(Self {
+            commit: current_commit(),
+            config: serde_json::to_value(config)?,
+        })
+    }
+
+    /// The header as "#" comment lines, for the top of a text report.
+    pub fn comment_lines(&self) -> String {
+        format!("# commit: {}\n# config: {}\n", self.commit, self.config)
+    }
+}
+
+/// The commit of the checkout the trainer was built from.
+fn current_commit() -> String {
+    let git = |args: &[&str]| {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    match (git(&["rev-parse", "HEAD"]), git(&["status", "--porcelain"])) {
+        (Some(commit), Some(status)) if !status.is_empty() => format!("{commit}-dirty"),
+        (Some(commit), _) => commit,
+        (None, _) => "unknown".into(),
+    }
+}
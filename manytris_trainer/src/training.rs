@@ -0,0 +1,154 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use manytris_bot::features::{Feature, WeightProfile};
+use manytris_bot::simulation::{play_game, GameConfig};
+use manytris_bot::BotContext;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use crate::optimizer::{Optimizer, OptimizerState};
+
+/// How candidates are scored. Every candidate plays the same seeded solo games.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FitnessConfig {
+    pub games: usize,
+    /// Games stop here if the bot hasn't topped out.
+    pub max_pieces: usize,
+    pub search_depth: usize,
+    /// The seed of the first game's shapes. Each later game's seed is one more.
+    pub seed: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub weights: WeightProfile,
+    pub fitness: f64,
+}
+
+/// Everything needed to carry on training, saved between generations.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The feature weighted by each gene.
+    pub features: Vec<Feature>,
+    pub fitness: FitnessConfig,
+    pub optimizer: OptimizerState,
+    /// Seeds the optimizer's randomness, along with the generation.
+    pub seed: u64,
+    /// Generations finished so far.
+    pub generation: u64,
+    pub best: Option<Candidate>,
+}
+
+impl FitnessConfig {
+    /// The mean of the pieces placed plus the lines cleared in each game, so bots which survive
+    /// to the piece limit are still told apart by how cleanly they played.
+    pub fn fitness(&self, ctx: &impl BotContext, weights: &WeightProfile) -> Result<f64> {
+        let mut total = 0;
+        for game in 0..self.games {
+            let config = GameConfig::solo(self.max_pieces, self.seed + game as u64);
+            let result = play_game(ctx, weights, self.search_depth, &config)?;
+            total += result.pieces + result.lines_cleared;
+        }
+        Ok(total as f64 / self.games.max(1) as f64)
+    }
+}
+
+impl Checkpoint {
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read checkpoint {}", path.display()))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Save by replacing the old checkpoint, so an interrupted save doesn't lose it.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    pub fn profile(&self, genes: &[f64]) -> WeightProfile {
+        WeightProfile::new(
+            self.features
+                .iter()
+                .copied()
+                .zip(genes.iter().map(|g| *g as f32)),
+        )
+    }
+
+    /// Score a generation of candidates and move on to the next, returning the scores.
+    pub fn step(&mut self, ctx: &impl BotContext) -> Result<Vec<f64>> {
+        let mut rng = StdRng::seed_from_u64(self.seed.wrapping_add(self.generation));
+        let candidates = self.optimizer.ask(&mut rng);
+        let fitnesses = candidates
+            .iter()
+            .map(|genes| self.fitness.fitness(ctx, &self.profile(genes)))
+            .collect::<Result<Vec<_>>>()?;
+
+        for (genes, fitness) in candidates.iter().zip(&fitnesses) {
+            if self
+                .best
+                .as_ref()
+                .is_none_or(|best| *fitness > best.fitness)
+            {
+                self.best = Some(Candidate {
+                    weights: self.profile(genes),
+                    fitness: *fitness,
+                });
+            }
+        }
+
+        self.optimizer.tell(candidates, &fitnesses, &mut rng);
+        self.generation += 1;
+        Ok(fitnesses)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cma_es::CmaEs;
+    use manytris_bot::bot_cpu::CpuBotContext;
+
+    #[test]
+    fn resumed_training_matches() {
+        let survival = WeightProfile::survival();
+        let features = vec![Feature::LinesCleared, Feature::Height, Feature::Covered];
+        let mut checkpoint = Checkpoint {
+            optimizer: OptimizerState::CmaEs(CmaEs::new(
+                features
+                    .iter()
+                    .map(|f| survival.weight(*f) as f64)
+                    .collect(),
+                500.0,
+                Some(4),
+            )),
+            features,
+            fitness: FitnessConfig {
+                games: 2,
+                max_pieces: 15,
+                search_depth: 1,
+                seed: 46,
+            },
+            seed: 46,
+            generation: 0,
+            best: None,
+        };
+        let ctx = CpuBotContext::default();
+
+        checkpoint.step(&ctx).unwrap();
+        let mut resumed: Checkpoint =
+            serde_json::from_str(&serde_json::to_string(&checkpoint).unwrap()).unwrap();
+        assert_eq!(resumed, checkpoint);
+
+        let fitnesses = checkpoint.step(&ctx).unwrap();
+        assert_eq!(resumed.step(&ctx).unwrap(), fitnesses);
+        assert_eq!(resumed, checkpoint);
+        assert_eq!(checkpoint.generation, 2);
+        assert!(checkpoint.best.is_some());
+    }
+}